@@ -0,0 +1,335 @@
+use super::{
+    Error, Recipe,
+    io::{extension, glob, invalid_output, is_pattern},
+    log::{set_logfile, unset_logfile},
+    recipe::resolve,
+};
+use std::{
+    collections::HashSet,
+    fs::{File, read_to_string},
+    io::{BufWriter, Write},
+    num::NonZeroUsize,
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+    thread::{available_parallelism, scope},
+    time::{Duration, Instant},
+};
+
+const PLACEHOLDER: &str = "{name}";
+
+/// Runs a [`Recipe`] on many inputs in parallel worker threads.
+///
+/// The input of the recipe is either a pattern of `*` and `?` wildcards, or a
+/// manifest (csv) listing one input per line with an optional case name. The
+/// outputs and metrics of the recipe name each case with `{name}`, which is
+/// the file stem of the input unless the manifest names it. Each case logs to
+/// its own file, next to its first output, and a failing case does not stop
+/// the others.
+///
+/// ```no_run
+/// use automesh::{Batch, Recipe};
+///
+/// let recipe = Recipe::read("batch.toml")?;
+/// let cases = Batch::new(recipe).jobs(Some(4)).run(true)?;
+/// let failed = cases.iter().filter(|case| case.error.is_some()).count();
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Batch {
+    recipe: Recipe,
+    jobs: Option<usize>,
+}
+
+/// The outcome of a single case of a [`Batch`].
+#[derive(Clone, Debug)]
+pub struct Case {
+    /// Name of the case.
+    pub name: String,
+    /// Input file of the case.
+    pub input: String,
+    /// Number of elements in the final mesh.
+    pub elements: usize,
+    /// Number of nodes in the final mesh.
+    pub nodes: usize,
+    /// Wall time of the case.
+    pub time: Duration,
+    /// Why the case failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Batch {
+    /// Creates a batch from a recipe with placeholder outputs.
+    pub fn new(recipe: Recipe) -> Self {
+        Self { recipe, jobs: None }
+    }
+    /// Number of worker threads [default: available parallelism].
+    pub fn jobs(mut self, jobs: Option<usize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+    /// Lists the input and name of each case.
+    pub fn cases(&self) -> Result<Vec<(String, String)>, Error> {
+        let input = &self.recipe.input;
+        let cases: Vec<(String, String)> = if is_pattern(input) {
+            glob(input)?
+                .into_iter()
+                .map(|file| {
+                    let file = file.to_string_lossy().into_owned();
+                    let name = name(&file);
+                    (file, name)
+                })
+                .collect()
+        } else if extension(input) == Some("csv") {
+            manifest(input)?
+        } else {
+            return Err(Error::InvalidOption(format!(
+                "Batch input {input} must be a pattern or a manifest (csv)"
+            )));
+        };
+        if cases.is_empty() {
+            return Err(Error::InvalidData(format!(
+                "No batch inputs found for {input}"
+            )));
+        }
+        let mut names = HashSet::new();
+        match cases.iter().find(|(_, name)| !names.insert(name)) {
+            Some((_, name)) => Err(Error::InvalidData(format!(
+                "Batch has more than one case named {name}"
+            ))),
+            None => Ok(cases),
+        }
+    }
+    /// Runs every case, returning their outcomes in order.
+    pub fn run(&self, quiet: bool) -> Result<Vec<Case>, Error> {
+        let recipe = &self.recipe;
+        if recipe.outputs.is_empty() {
+            return Err(Error::InvalidOption(
+                "Recipe needs at least one output".into(),
+            ));
+        }
+        if recipe
+            .outputs
+            .iter()
+            .chain(&recipe.metrics)
+            .any(|file| !file.contains(PLACEHOLDER))
+        {
+            return Err(Error::InvalidOption(format!(
+                "Batch outputs and metrics need a {PLACEHOLDER} placeholder"
+            )));
+        }
+        let cases = self.cases()?;
+        let jobs = self
+            .jobs
+            .unwrap_or_else(|| available_parallelism().map_or(1, NonZeroUsize::get))
+            .clamp(1, cases.len());
+        crate::echo!(
+            quiet,
+            "    \x1b[1;96mBatching\x1b[0m {} cases \x1b[2m[{jobs} threads]\x1b[0m",
+            cases.len()
+        );
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = channel();
+        let mut results = scope(|scope| {
+            for _ in 0..jobs {
+                let sender = sender.clone();
+                let (cases, next) = (&cases, &next);
+                scope.spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((input, name)) = cases.get(index) else {
+                            break;
+                        };
+                        let case = run_case(recipe, input, name);
+                        if sender.send((index, case)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+            receiver
+                .iter()
+                .inspect(|(_, case)| match &case.error {
+                    None => crate::echo!(
+                        quiet,
+                        "        \x1b[1;92mDone\x1b[0m {} \x1b[2m[{} elements, {} nodes]\x1b[0m {:?}",
+                        case.name,
+                        case.elements,
+                        case.nodes,
+                        case.time
+                    ),
+                    Some(error) => crate::echo!(
+                        quiet,
+                        "      \x1b[1;91mFailed\x1b[0m {} \x1b[2m[{error}]\x1b[0m {:?}",
+                        case.name,
+                        case.time
+                    ),
+                })
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        let cases: Vec<Case> = results.into_iter().map(|(_, case)| case).collect();
+        summarize(&cases, quiet);
+        Ok(cases)
+    }
+}
+
+/// Writes the outcome of each case to a summary file (csv).
+pub fn write_summary(cases: &[Case], file: &str, quiet: bool) -> Result<(), Error> {
+    let time = Instant::now();
+    if extension(file) != Some("csv") {
+        return Err(invalid_output(file, extension(file)));
+    }
+    crate::echo!(quiet, "     \x1b[1;96mWriting\x1b[0m {file}");
+    let mut writer = BufWriter::new(File::create(file)?);
+    writeln!(writer, "case,input,status,elements,nodes,seconds,error")?;
+    cases.iter().try_for_each(|case| {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{:.6},{}",
+            quote(&case.name),
+            quote(&case.input),
+            if case.error.is_none() {
+                "done"
+            } else {
+                "failed"
+            },
+            case.elements,
+            case.nodes,
+            case.time.as_secs_f64(),
+            case.error.as_deref().map_or(String::new(), quote)
+        )
+    })?;
+    writer.flush()?;
+    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+    Ok(())
+}
+
+/// Quotes a field of a csv file, doubling any quotes within it.
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Runs the recipe on a single case, in its own log, catching any panic.
+fn run_case(recipe: &Recipe, input: &str, name: &str) -> Case {
+    let time = Instant::now();
+    let mut recipe = recipe.clone();
+    recipe.input = input.to_string();
+    recipe.outputs = recipe
+        .outputs
+        .iter()
+        .map(|file| file.replace(PLACEHOLDER, name))
+        .collect();
+    recipe.metrics = recipe.metrics.map(|file| file.replace(PLACEHOLDER, name));
+    let log = Path::new(&recipe.outputs[0]).with_file_name(format!("{name}.log"));
+    let result = set_logfile(&log.to_string_lossy())
+        .map_err(Error::from)
+        .and_then(|_| {
+            catch_unwind(AssertUnwindSafe(|| recipe.run(true))).unwrap_or_else(|panic| {
+                Err(Error::Mesh(
+                    panic
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "Case panicked".into()),
+                ))
+            })
+        });
+    if let Err(error) = &result {
+        crate::log::write_log(&format!("Error: {error}"));
+    }
+    unset_logfile();
+    let (elements, nodes, error) = match result {
+        Ok(mesh) => (mesh.number_of_elements(), mesh.number_of_nodes(), None),
+        Err(error) => (0, 0, Some(error.to_string())),
+    };
+    Case {
+        name: name.to_string(),
+        input: input.to_string(),
+        elements,
+        nodes,
+        time: time.elapsed(),
+        error,
+    }
+}
+
+/// Prints a table of the outcome of each case.
+fn summarize(cases: &[Case], quiet: bool) {
+    let width = cases
+        .iter()
+        .map(|case| case.name.len())
+        .chain([4])
+        .max()
+        .unwrap_or(4);
+    let failed = cases.iter().filter(|case| case.error.is_some()).count();
+    crate::echo!(
+        quiet,
+        "     \x1b[1;96mSummary\x1b[0m {} of {} cases done",
+        cases.len() - failed,
+        cases.len()
+    );
+    crate::echo!(
+        quiet,
+        "             \x1b[1m{:<width$} {:>10} {:>10} {:>12}  status\x1b[0m",
+        "case",
+        "elements",
+        "nodes",
+        "time"
+    );
+    cases.iter().for_each(|case| {
+        crate::echo!(
+            quiet,
+            "             {:<width$} {:>10} {:>10} {:>12}  {}",
+            case.name,
+            case.elements,
+            case.nodes,
+            format!("{:.3?}", case.time),
+            match &case.error {
+                None => "\x1b[92mdone\x1b[0m".to_string(),
+                Some(error) => format!("\x1b[91mfailed\x1b[0m \x1b[2m[{error}]\x1b[0m"),
+            }
+        )
+    });
+}
+
+/// Names a case after the file stem of its input.
+fn name(input: &str) -> String {
+    let stem = Path::new(input)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(input);
+    stem.strip_suffix(".nii").unwrap_or(stem).to_string()
+}
+
+/// Reads the inputs and names of a manifest, relative to its directory.
+fn manifest(file: &str) -> Result<Vec<(String, String)>, Error> {
+    let directory = Path::new(file).parent().unwrap_or(Path::new(""));
+    read_to_string(file)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .enumerate()
+        .filter(|(index, line)| {
+            !(*index == 0 && line.split(',').next().map(str::trim) == Some("input"))
+        })
+        .map(|(_, line)| {
+            let mut fields = line.split(',').map(str::trim);
+            let input = fields.next().unwrap_or("");
+            if input.is_empty() {
+                return Err(Error::InvalidData(format!(
+                    "Manifest {file} has a line without an input"
+                )));
+            }
+            let input = resolve(directory, input);
+            let name = match fields.next() {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => name(&input),
+            };
+            Ok((input, name))
+        })
+        .collect()
+}
@@ -0,0 +1,1109 @@
+use crate::metrics::write_metrics_named;
+use crate::{
+    ADAPTIVE_DEFAULT_GRADATION, ADAPTIVE_DEFAULT_TOLERANCE, Batch, ConvertOptions,
+    DefeatureOptions, DiffOptions, Element, Error, ExtractOptions, FeatureOptions,
+    HC_DEFAULT_ALPHA, HC_DEFAULT_BETA, IMPROVE_DEFAULT_ITERS, IMPROVE_DEFAULT_TARGET,
+    ImproveOptions, Label, METRICS_DEFAULT_COLUMNS, MeshOptions, OCTREE_DEFAULT_LEVELS,
+    OCTREE_DEFAULT_SCALE, REMESH_DEFAULT_ITERS, REPORT_DEFAULT_BINS, REPORT_DEFAULT_WORST, Recipe,
+    RemeshOptions, ReportOptions, SegmentOptions, SetNames, SetOptions, Sizing, SmoothOptions,
+    TAUBIN_DEFAULT_BAND, TAUBIN_DEFAULT_ITERS, TAUBIN_DEFAULT_SCALE, Tie, extension,
+    is_segmentation, read_mesh, tetrahedra_to_hexahedra, write_fractions, write_mesh,
+    write_mesh_named, write_metric_columns, write_metrics, write_segmentation, write_summary,
+};
+use clap::{Parser, Subcommand};
+use conspire::geometry::mesh::Mesh;
+use std::{
+    env::consts::{ARCH, OS},
+    ffi::OsString,
+    fmt::{self, Debug, Formatter},
+    io::Error as ErrorIO,
+    time::Instant,
+};
+
+/// Wraps errors from the library for display on the terminal.
+pub struct ErrorWrapper {
+    message: String,
+}
+
+impl Debug for ErrorWrapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[1;91m{}.\x1b[0m", self.message)
+    }
+}
+
+impl From<Error> for ErrorWrapper {
+    fn from(error: Error) -> ErrorWrapper {
+        ErrorWrapper {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<ErrorIO> for ErrorWrapper {
+    fn from(error: ErrorIO) -> ErrorWrapper {
+        ErrorWrapper {
+            message: error.to_string(),
+        }
+    }
+}
+
+macro_rules! about {
+    () => {
+        format!(
+            "
+
+     @@@@@@@@@@@@@@@@
+      @@@@  @@@@@@@@@@
+     @@@@  @@@@@@@@@@@    \x1b[1;4m{}: Automatic mesh generation\x1b[0m
+    @@@@  @@@@@@@@@@@@
+      @@    @@    @@      {}
+      @@    @@    @@      {}
+    @@@@@@@@@@@@  @@@     {}
+    @@@@@@@@@@@  @@@@     {}
+    @@@@@@@@@@ @@@@@ @
+     @@@@@@@@@@@@@@@@",
+            env!("CARGO_PKG_NAME"),
+            format!("v{} {} {}", env!("CARGO_PKG_VERSION"), OS, ARCH),
+            format!(
+                "build {} {}",
+                option_env!("GIT_COMMIT_HASH").unwrap_or(""),
+                env!("BUILD_TIME"),
+            ),
+            env!("CARGO_PKG_AUTHORS").split(':').next().unwrap_or(""),
+            env!("CARGO_PKG_AUTHORS").split(':').nth(1).unwrap_or(""),
+        )
+    };
+}
+
+#[derive(Parser)]
+#[command(about = about!(), arg_required_else_help = true, version)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Mirror terminal output to a log file
+    #[arg(global = true, long, value_name = "FILE")]
+    log: Option<String>,
+
+    /// Pass to quiet the terminal output
+    #[arg(action, global = true, long, short)]
+    quiet: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Runs a recipe on many inputs in parallel
+    Batch {
+        /// Recipe input file (toml)
+        #[arg(value_name = "FILE")]
+        recipe: String,
+
+        /// Number of worker threads [default: available parallelism]
+        #[arg(long, short, value_name = "NUM")]
+        jobs: Option<usize>,
+
+        /// Summary output file (csv)
+        #[arg(long, short, value_name = "FILE")]
+        summary: Option<String>,
+    },
+
+    /// Converts between mesh or segmentation file types
+    Convert {
+        #[command(subcommand)]
+        subcommand: ConvertSubcommand,
+    },
+
+    /// Defeatures and creates a new segmentation
+    Defeature {
+        /// Segmentation input file (nii | npy | spn | tif)
+        #[arg(long, short, value_name = "FILE")]
+        input: String,
+
+        /// Defeatured segmentation output file (nii | npy | spn | vti)
+        #[arg(long, short, value_name = "FILE")]
+        output: String,
+
+        /// Defeature clusters with less than MIN voxels
+        #[arg(long, short, value_name = "MIN")]
+        min: usize,
+
+        /// Number of voxels in the x-direction (spn)
+        #[arg(long, short = 'x', value_name = "NEL")]
+        nelx: Option<usize>,
+
+        /// Number of voxels in the y-direction (spn)
+        #[arg(long, short = 'y', value_name = "NEL")]
+        nely: Option<usize>,
+
+        /// Number of voxels in the z-direction (spn)
+        #[arg(long, short = 'z', value_name = "NEL")]
+        nelz: Option<usize>,
+    },
+
+    /// Show the difference between two segmentations or two meshes
+    Diff {
+        /// Segmentation (nii | npy | spn | tif) or mesh (exo | inp | mesh | stl | vtu) input files
+        #[arg(long, num_args = 2, short, value_delimiter = ' ', value_name = "FILE")]
+        input: Vec<String>,
+
+        /// Segmentation difference output file (nii | npy | spn | vti), or second mesh with its distances from the first (vtu)
+        #[arg(long, short, value_name = "FILE")]
+        output: String,
+
+        /// Number of voxels in the x-direction (spn)
+        #[arg(long, short = 'x', value_name = "NEL")]
+        nelx: Option<usize>,
+
+        /// Number of voxels in the y-direction (spn)
+        #[arg(long, short = 'y', value_name = "NEL")]
+        nely: Option<usize>,
+
+        /// Number of voxels in the z-direction (spn)
+        #[arg(long, short = 'z', value_name = "NEL")]
+        nelz: Option<usize>,
+    },
+
+    /// Extracts a specified range of voxels from a segmentation
+    Extract {
+        /// Segmentation input file (nii | npy | spn | tif)
+        #[arg(long, short, value_name = "FILE")]
+        input: String,
+
+        /// Extracted segmentation output file (nii | npy | spn | vti)
+        #[arg(long, short, value_name = "FILE")]
+        output: String,
+
+        /// Number of voxels in the x-direction (spn)
+        #[arg(long, short = 'x', value_name = "NEL")]
+        nelx: Option<usize>,
+
+        /// Number of voxels in the y-direction (spn)
+        #[arg(long, short = 'y', value_name = "NEL")]
+        nely: Option<usize>,
+
+        /// Number of voxels in the z-direction (spn)
+        #[arg(long, short = 'z', value_name = "NEL")]
+        nelz: Option<usize>,
+
+        /// Minimum voxel in the x-direction
+        #[arg(long, value_name = "MIN")]
+        xmin: usize,
+
+        /// Maximum voxel in the x-direction
+        #[arg(long, value_name = "MAX")]
+        xmax: usize,
+
+        /// Minimum voxel in the y-direction
+        #[arg(long, value_name = "MIN")]
+        ymin: usize,
+
+        /// Maximum voxel in the y-direction
+        #[arg(long, value_name = "MAX")]
+        ymax: usize,
+
+        /// Minimum voxel in the z-direction
+        #[arg(long, value_name = "MIN")]
+        zmin: usize,
+
+        /// Maximum voxel in the z-direction
+        #[arg(long, value_name = "MAX")]
+        zmax: usize,
+    },
+
+    /// Untangles and improves the quality of an existing hex or tet mesh
+    Improve(ImproveArgs),
+
+    /// Creates a finite element mesh from a segmentation
+    Mesh {
+        #[command(subcommand)]
+        subcommand: Box<MeshSubcommand>,
+    },
+
+    /// Quality metrics for an existing finite element mesh
+    Metrics(MetricsArgs),
+
+    /// Applies remeshing to an existing mesh [default mode: uniform]
+    Remesh {
+        /// Mesh input file (exo | inp | stl | vtu)
+        #[arg(long, short, value_name = "FILE")]
+        input: String,
+
+        /// Mesh output file (exo | inp | mesh | stl | vtu)
+        #[arg(long, short, value_name = "FILE")]
+        output: String,
+
+        #[command(flatten)]
+        features: FeatureArgs,
+
+        /// Sizing mode [default: uniform]
+        #[command(subcommand)]
+        mode: Option<MeshRemeshCommands>,
+    },
+
+    /// Runs a recipe of meshing, smoothing, and remeshing stages
+    Run {
+        /// Recipe input file (toml)
+        #[arg(value_name = "FILE")]
+        recipe: String,
+    },
+
+    /// Creates a segmentation or voxelized mesh from an existing mesh
+    Segment(SegmentArgs),
+
+    /// Applies smoothing to an existing mesh
+    Smooth(Box<SmoothArgs>),
+}
+
+/// Runs the command line interface on the given arguments, program name first.
+pub fn run<I, T>(args: I) -> Result<(), ErrorWrapper>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let time = Instant::now();
+    let args = Args::parse_from(args);
+    let quiet = args.quiet;
+    if let Some(path) = &args.log {
+        let logfile = crate::log::set_logfile(path)?;
+        if !quiet {
+            println!("     \x1b[1;92mLogging\x1b[0m to {logfile}");
+        }
+        crate::log::write_log(&about!());
+        crate::log::write_log("");
+    }
+    let result = match args.command {
+        Some(Commands::Batch {
+            recipe,
+            jobs,
+            summary,
+        }) => batch(recipe, jobs, summary, quiet),
+        Some(Commands::Convert { subcommand }) => match subcommand {
+            ConvertSubcommand::Mesh(args) => convert_mesh(args, quiet),
+            ConvertSubcommand::Segmentation(args) => ConvertOptions::default()
+                .nel(nel(args.nelx, args.nely, args.nelz))
+                .convert(&args.input, &args.output, quiet),
+        },
+        Some(Commands::Defeature {
+            input,
+            output,
+            min,
+            nelx,
+            nely,
+            nelz,
+        }) => DefeatureOptions::new(min)
+            .nel(nel(nelx, nely, nelz))
+            .defeature(&input, &output, quiet),
+        Some(Commands::Diff {
+            input,
+            output,
+            nelx,
+            nely,
+            nelz,
+        }) => DiffOptions::default()
+            .nel(nel(nelx, nely, nelz))
+            .diff(&input[0], &input[1], &output, quiet),
+        Some(Commands::Extract {
+            input,
+            output,
+            nelx,
+            nely,
+            nelz,
+            xmin,
+            xmax,
+            ymin,
+            ymax,
+            zmin,
+            zmax,
+        }) => ExtractOptions::new([xmin, ymin, zmin], [xmax, ymax, zmax])
+            .nel(nel(nelx, nely, nelz))
+            .extract(&input, &output, quiet),
+        Some(Commands::Improve(args)) => improve(args, quiet),
+        Some(Commands::Mesh { subcommand }) => match *subcommand {
+            MeshSubcommand::Hex(args) => mesh(Element::Hexahedra, args, quiet),
+            MeshSubcommand::Hexdom(args) => mesh(Element::HexDominant, args, quiet),
+            MeshSubcommand::Poly(args) => mesh(Element::Polyhedra, args, quiet),
+            MeshSubcommand::Tet(args) => mesh(Element::Tetrahedra, args, quiet),
+            MeshSubcommand::Tri(args) => mesh(Element::Triangles, args, quiet),
+        },
+        Some(Commands::Metrics(args)) => metrics(args, quiet),
+        Some(Commands::Remesh {
+            input,
+            output,
+            features,
+            mode,
+        }) => remesh(input, output, features, mode, quiet),
+        Some(Commands::Run { recipe }) => {
+            Recipe::read(&recipe).and_then(|recipe| recipe.run(quiet).map(|_| ()))
+        }
+        Some(Commands::Segment(args)) => segment(args, quiet),
+        Some(Commands::Smooth(args)) => smooth(*args, quiet),
+        None => return Ok(()),
+    };
+    crate::echo!(quiet, "       \x1b[1;98mTotal\x1b[0m {:?}", time.elapsed());
+    Ok(result?)
+}
+
+#[derive(Subcommand)]
+pub enum MeshSubcommand {
+    /// Creates an all-hexahedral mesh from a segmentation or tessellation
+    Hex(MeshArgs),
+    /// Creates a hex-dominant mesh from a tessellation, polyhedral at the boundary
+    Hexdom(MeshArgs),
+    /// Creates a polyhedral mesh from a tessellation
+    Poly(MeshArgs),
+    /// Creates an all-tetrahedral mesh from a segmentation or tessellation
+    Tet(MeshArgs),
+    /// Creates all-triangular isosurface(s) from a segmentation
+    Tri(MeshArgs),
+}
+
+#[derive(clap::Args)]
+pub struct MeshArgs {
+    #[command(subcommand)]
+    pub smoothing: Option<MeshSmoothCommands>,
+
+    /// Segmentation (nii | npy | spn | tif) or tessellation (stl) input file
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Mesh output file (exo | inp | mesh | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    /// Defeature clusters with less than NUM voxels
+    #[arg(long, short, value_name = "NUM")]
+    pub defeature: Option<usize>,
+
+    /// Number of voxels in the x-direction (spn)
+    #[arg(long, short = 'x', value_name = "NEL")]
+    pub nelx: Option<usize>,
+
+    /// Number of voxels in the y-direction (spn)
+    #[arg(long, short = 'y', value_name = "NEL")]
+    pub nely: Option<usize>,
+
+    /// Number of voxels in the z-direction (spn)
+    #[arg(long, short = 'z', value_name = "NEL")]
+    pub nelz: Option<usize>,
+
+    /// Voxel IDs to remove from the mesh (nii | npy | spn | tif)
+    #[arg(long, num_args = 1.., short, value_delimiter = ' ', value_name = "ID")]
+    pub remove: Option<Vec<Label>>,
+
+    /// Scaling (> 0.0) in the x-direction, applied before translation [default: 1.0 or nii header]
+    #[arg(long, value_name = "SCALE")]
+    pub xscale: Option<f64>,
+
+    /// Scaling (> 0.0) in the y-direction, applied before translation [default: 1.0 or nii header]
+    #[arg(long, value_name = "SCALE")]
+    pub yscale: Option<f64>,
+
+    /// Scaling (> 0.0) in the z-direction, applied before translation [default: 1.0 or nii header]
+    #[arg(long, value_name = "SCALE")]
+    pub zscale: Option<f64>,
+
+    /// Translation in the x-direction [default: 0.0 or nii header]
+    #[arg(long, allow_negative_numbers = true, value_name = "VAL")]
+    pub xtranslate: Option<f64>,
+
+    /// Translation in the y-direction [default: 0.0 or nii header]
+    #[arg(long, allow_negative_numbers = true, value_name = "VAL")]
+    pub ytranslate: Option<f64>,
+
+    /// Translation in the z-direction [default: 0.0 or nii header]
+    #[arg(long, allow_negative_numbers = true, value_name = "VAL")]
+    pub ztranslate: Option<f64>,
+
+    /// Octree refinement scale for dualizing a tessellation (stl) input
+    #[arg(long, default_value_t = OCTREE_DEFAULT_SCALE, short = 's', value_name = "SCALE")]
+    pub scale: f64,
+
+    /// Uniform lattice of the given cell size instead of an octree (stl)
+    #[arg(long, short = 'u', value_name = "SPACING")]
+    pub uniform: Option<f64>,
+
+    /// Chord-error tolerance for curvature-driven refinement [default: disabled]
+    #[arg(long, short = 't', value_name = "TOL")]
+    pub tolerance: Option<f64>,
+
+    /// Uses strong balancing instead of the default weak balancing
+    #[arg(action, long)]
+    pub strong: bool,
+
+    /// Snaps the buffer layer onto the surface instead of a soft fit
+    #[arg(action, long)]
+    pub snap: bool,
+
+    /// Level difference allowed between neighboring octree cells (poly)
+    #[arg(long, default_value_t = OCTREE_DEFAULT_LEVELS, short = 'l', value_name = "NUM")]
+    pub levels: usize,
+
+    /// Quality metrics output file (csv | exo | npy | vtu)
+    #[arg(long, value_name = "FILE")]
+    pub metrics: Option<String>,
+
+    /// Writes the quality metrics as element fields of the output (exo | vtu)
+    #[arg(action, long)]
+    pub metric_fields: bool,
+
+    /// Side set of exterior faces, as NAME:EXPR (e.g. bottom:z<0.01), repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub sideset: Vec<String>,
+
+    /// Node set of the nodes on exterior faces, as NAME:EXPR, repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub nodeset: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MeshSmoothCommands {
+    /// Applies smoothing to the mesh before output
+    Smooth {
+        #[command(subcommand)]
+        remeshing: Option<MeshRemeshSubcommand>,
+
+        #[command(flatten)]
+        smoothing: SmoothingArgs,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SmoothingArgs {
+    /// Number of smoothing iterations
+    #[arg(default_value_t = TAUBIN_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+    pub iterations: usize,
+
+    /// Smoothing method (HC | Laplace | Taubin) [default: Taubin]
+    #[arg(long, short, value_name = "NAME")]
+    pub method: Option<String>,
+
+    /// Pass-band frequency (for Taubin only)
+    #[arg(default_value_t = TAUBIN_DEFAULT_BAND, long, short = 'k', value_name = "FREQ")]
+    pub pass_band: f64,
+
+    /// Scaling parameter for all smoothing methods
+    #[arg(default_value_t = TAUBIN_DEFAULT_SCALE, long, short, value_name = "SCALE")]
+    pub scale: f64,
+
+    /// Weighting of neighbors (cotangent | inverse-distance | uniform) [default: uniform]
+    #[arg(long, short, value_name = "NAME")]
+    pub weighting: Option<String>,
+
+    /// Weight of the original positions (for HC only)
+    #[arg(default_value_t = HC_DEFAULT_ALPHA, long, value_name = "ALPHA")]
+    pub alpha: f64,
+
+    /// Weight of each node against its neighbors (for HC only)
+    #[arg(default_value_t = HC_DEFAULT_BETA, long, value_name = "BETA")]
+    pub beta: f64,
+
+    /// Enables hierarchical smoothing, preserving the boundary and interfaces
+    #[arg(action, long, short = 'b')]
+    pub hierarchical: bool,
+
+    /// Preserves the boundary, moving its nodes only along it
+    #[arg(action, long)]
+    pub preserve_boundary: bool,
+
+    /// Preserves interfaces between blocks, moving their nodes only along them
+    #[arg(action, long)]
+    pub preserve_interfaces: bool,
+
+    /// Block IDs whose nodes are never moved, comma-separated
+    #[arg(long, value_delimiter = ',', value_name = "ID")]
+    pub freeze: Vec<usize>,
+
+    /// Keeps the minimum scaled Jacobian of every element above this value
+    #[arg(long, short = 'g', value_name = "MIN")]
+    pub guard: Option<f64>,
+
+    /// Restores the volume of each block to within this relative tolerance
+    #[arg(long, value_name = "TOL")]
+    pub preserve_volume: Option<f64>,
+
+    #[command(flatten)]
+    pub features: FeatureArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FeatureArgs {
+    /// Keeps edges sharper than this dihedral angle in degrees, and their corners
+    #[arg(long, value_name = "DEG")]
+    pub feature_angle: Option<f64>,
+
+    /// Keeps edges along the polylines in this file (csv | txt) of x,y,z points
+    #[arg(long, value_name = "FILE")]
+    pub features: Option<String>,
+}
+
+impl FeatureArgs {
+    fn options(&self) -> FeatureOptions {
+        FeatureOptions::default()
+            .angle(self.feature_angle)
+            .polylines(self.features.clone())
+    }
+}
+
+impl SmoothingArgs {
+    fn options(&self) -> Result<SmoothOptions, Error> {
+        let mut options = SmoothOptions::default()
+            .iterations(self.iterations)
+            .pass_band(self.pass_band)
+            .scale(self.scale)
+            .alpha(self.alpha)
+            .beta(self.beta)
+            .preserve_boundary(self.hierarchical || self.preserve_boundary)
+            .preserve_interfaces(self.hierarchical || self.preserve_interfaces)
+            .freeze(self.freeze.clone())
+            .guard(self.guard)
+            .preserve_volume(self.preserve_volume)
+            .features(self.features.options());
+        if let Some(method) = &self.method {
+            options = options.method(method.parse()?)
+        }
+        if let Some(weighting) = &self.weighting {
+            options = options.weighting(weighting.parse()?)
+        }
+        Ok(options)
+    }
+}
+
+#[derive(clap::Args)]
+pub struct SmoothArgs {
+    #[command(subcommand)]
+    pub remeshing: Option<MeshRemeshSubcommand>,
+
+    /// Mesh input file (exo | inp | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Smoothed mesh output file (exo | inp | mesh | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub smoothing: SmoothingArgs,
+
+    /// Quality metrics output file (csv | exo | npy | vtu)
+    #[arg(long, value_name = "FILE")]
+    pub metrics: Option<String>,
+
+    /// Writes the quality metrics as element fields of the output (exo | vtu)
+    #[arg(action, long)]
+    pub metric_fields: bool,
+
+    /// Side set of exterior faces, as NAME:EXPR (e.g. bottom:z<0.01), repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub sideset: Vec<String>,
+
+    /// Node set of the nodes on exterior faces, as NAME:EXPR, repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub nodeset: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MeshRemeshSubcommand {
+    /// Applies remeshing to the mesh before output [default mode: uniform]
+    Remesh {
+        #[command(flatten)]
+        features: FeatureArgs,
+
+        /// Sizing mode [default: uniform]
+        #[command(subcommand)]
+        mode: Option<MeshRemeshCommands>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MeshRemeshCommands {
+    /// Uniform target edge length over the whole mesh
+    ///
+    /// With --feature-angle or --features, remeshes with the remesher of the
+    /// field and anisotropic modes instead of conspire's.
+    Uniform {
+        /// Number of remeshing iterations
+        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+        iterations: usize,
+
+        /// Target edge length [default: mean edge length]
+        #[arg(long, short = 's', value_name = "SIZE")]
+        size: Option<f64>,
+    },
+
+    /// Curvature-adaptive target edge length
+    ///
+    /// With --feature-angle or --features, remeshes with the remesher of the
+    /// field and anisotropic modes instead of conspire's, whose results are
+    /// close but not identical.
+    Adaptive {
+        /// Number of remeshing iterations
+        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+        iterations: usize,
+
+        /// Minimum edge length
+        #[arg(long, value_name = "MIN")]
+        minimum: f64,
+
+        /// Maximum edge length
+        #[arg(long, value_name = "MAX")]
+        maximum: f64,
+
+        /// Curvature tolerance
+        #[arg(default_value_t = ADAPTIVE_DEFAULT_TOLERANCE, long, short = 't', value_name = "TOL")]
+        tolerance: f64,
+
+        /// Size gradation factor
+        #[arg(default_value_t = ADAPTIVE_DEFAULT_GRADATION, long, short = 'g', value_name = "GRAD")]
+        gradation: f64,
+    },
+
+    /// Target edge length from a field of sizes per node or per element
+    Field {
+        /// Number of remeshing iterations
+        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+        iterations: usize,
+
+        /// Size field file (npy | vtu)
+        #[arg(long, short = 'f', value_name = "FILE")]
+        field: String,
+
+        /// Name of the point or cell data array in a vtu field [default: first]
+        #[arg(long, short = 'a', value_name = "NAME")]
+        array: Option<String>,
+    },
+
+    /// Target edge lengths aligned with the principal curvatures
+    Anisotropic {
+        /// Number of remeshing iterations
+        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+        iterations: usize,
+
+        /// Minimum edge length
+        #[arg(long, value_name = "MIN")]
+        minimum: f64,
+
+        /// Maximum edge length
+        #[arg(long, value_name = "MAX")]
+        maximum: f64,
+
+        /// Curvature tolerance
+        #[arg(default_value_t = ADAPTIVE_DEFAULT_TOLERANCE, long, short = 't', value_name = "TOL")]
+        tolerance: f64,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct SegmentArgs {
+    /// Mesh input file (exo | inp | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Segmentation (nii | npy | spn | vti) or mesh (exo | inp | mesh | vtu) output file
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    /// Grid length for sampling within each voxel, with NUM^3 points per voxel
+    #[arg(default_value_t = 1, long, short = 'g', value_name = "NUM")]
+    pub grid: usize,
+
+    /// Element size which is the side length
+    #[arg(long, short = 's', value_name = "NUM")]
+    pub size: f64,
+
+    /// Block IDs to remove from the segmentation
+    #[arg(long, num_args = 1.., short, value_delimiter = ' ', value_name = "ID")]
+    pub remove: Option<Vec<usize>>,
+
+    /// Material kept when the vote is tied (lowest | highest) [default: lowest]
+    #[arg(long, short = 't', value_name = "TIE")]
+    pub tie: Option<String>,
+
+    /// Volume fractions output file, one channel per material (npy | vti)
+    #[arg(long, short = 'f', value_name = "FILE")]
+    pub fractions: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ImproveArgs {
+    /// Mesh input file (exo | inp | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Improved mesh output file (exo | inp | mesh | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    /// Maximum number of passes over the nodes
+    #[arg(default_value_t = IMPROVE_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
+    pub iterations: usize,
+
+    /// Element quality below which the nodes around it are moved
+    #[arg(default_value_t = IMPROVE_DEFAULT_TARGET, long, short, value_name = "QUALITY")]
+    pub target: f64,
+
+    /// Quality metrics output file (csv | exo | npy | vtu)
+    #[arg(long, value_name = "FILE")]
+    pub metrics: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct MetricsArgs {
+    /// Mesh input file (exo | inp | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Quality metrics output file (csv | npy), or mesh output file with them as element fields (exo | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Metrics to write and report, in order [default: edge_ratio jacobian skew volume]
+    /// (aspect_ratio | centroid_ratio | condition | edge_ratio | fv_skewness | jacobian |
+    /// jacobian_ratio | max_angle | max_dihedral | min_angle | min_dihedral |
+    /// non_orthogonality | planarity | skew | volume | warpage)
+    #[arg(long, num_args = 1.., short, value_delimiter = ' ', value_name = "METRIC")]
+    pub columns: Option<Vec<String>>,
+
+    /// Reports statistics and histograms of each metric
+    #[arg(action, long, short)]
+    pub report: bool,
+
+    /// Quality gate that every element must pass, as METRIC<VALUE or METRIC>=VALUE
+    /// with any of the metrics of --columns, failing with a nonzero exit code
+    #[arg(long, short, value_name = "GATE")]
+    pub gate: Vec<String>,
+
+    /// Number of the worst failing elements to list for each gate
+    #[arg(default_value_t = REPORT_DEFAULT_WORST, long, short, value_name = "NUM")]
+    pub worst: usize,
+
+    /// Number of bins of each histogram
+    #[arg(default_value_t = REPORT_DEFAULT_BINS, long, short, value_name = "NUM")]
+    pub bins: usize,
+}
+
+#[derive(Subcommand)]
+pub enum ConvertSubcommand {
+    /// Converts mesh file types (exo | inp | stl | vtu) -> (exo | inp | mesh | stl | vtu)
+    Mesh(ConvertMeshArgs),
+    /// Converts segmentation file types (nii | npy | spn | tif) -> (nii | npy | spn | vti)
+    Segmentation(ConvertSegmentationArgs),
+}
+
+#[derive(clap::Args)]
+pub struct ConvertMeshArgs {
+    /// Mesh input file (exo | inp | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Mesh output file (exo | inp | mesh | stl | vtu)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    /// Splits each tetrahedron of the input into four hexahedra
+    #[arg(action, long)]
+    pub hex: bool,
+
+    /// Side set of exterior faces, as NAME:EXPR (e.g. bottom:z<0.01), repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub sideset: Vec<String>,
+
+    /// Node set of the nodes on exterior faces, as NAME:EXPR, repeatable
+    #[arg(long, value_name = "NAME:EXPR")]
+    pub nodeset: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ConvertSegmentationArgs {
+    /// Segmentation input file (nii | npy | spn | tif)
+    #[arg(long, short, value_name = "FILE")]
+    pub input: String,
+
+    /// Segmentation output file (nii | npy | spn | vti)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: String,
+
+    /// Number of voxels in the x-direction (spn)
+    #[arg(long, short = 'x', value_name = "NEL")]
+    pub nelx: Option<usize>,
+
+    /// Number of voxels in the y-direction (spn)
+    #[arg(long, short = 'y', value_name = "NEL")]
+    pub nely: Option<usize>,
+
+    /// Number of voxels in the z-direction (spn)
+    #[arg(long, short = 'z', value_name = "NEL")]
+    pub nelz: Option<usize>,
+}
+
+/// Number of voxels in each direction, if given in all of them.
+fn nel(nelx: Option<usize>, nely: Option<usize>, nelz: Option<usize>) -> Option<[usize; 3]> {
+    match (nelx, nely, nelz) {
+        (Some(nelx), Some(nely), Some(nelz)) => Some([nelx, nely, nelz]),
+        _ => None,
+    }
+}
+
+impl MeshArgs {
+    fn options(&self, element: Element) -> MeshOptions {
+        MeshOptions::new(element)
+            .defeature(self.defeature)
+            .nel(nel(self.nelx, self.nely, self.nelz))
+            .remove(self.remove.clone())
+            .xscale(self.xscale)
+            .yscale(self.yscale)
+            .zscale(self.zscale)
+            .xtranslate(self.xtranslate)
+            .ytranslate(self.ytranslate)
+            .ztranslate(self.ztranslate)
+            .octree_scale(self.scale)
+            .uniform(self.uniform)
+            .tolerance(self.tolerance)
+            .strong(self.strong)
+            .snap(self.snap)
+            .levels(self.levels)
+            .sets(matches!(extension(&self.output), Some("exo") | Some("inp")))
+    }
+}
+
+fn remesh_options(features: FeatureArgs, mode: Option<MeshRemeshCommands>) -> RemeshOptions {
+    let options = match mode {
+        None => RemeshOptions::default(),
+        Some(MeshRemeshCommands::Uniform { iterations, size }) => RemeshOptions::default()
+            .iterations(iterations)
+            .sizing(Sizing::Uniform { size }),
+        Some(MeshRemeshCommands::Adaptive {
+            iterations,
+            minimum,
+            maximum,
+            tolerance,
+            gradation,
+        }) => RemeshOptions::default()
+            .iterations(iterations)
+            .sizing(Sizing::Adaptive {
+                minimum,
+                maximum,
+                tolerance,
+                gradation,
+            }),
+        Some(MeshRemeshCommands::Field {
+            iterations,
+            field,
+            array,
+        }) => RemeshOptions::default()
+            .iterations(iterations)
+            .sizing(Sizing::Field { file: field, array }),
+        Some(MeshRemeshCommands::Anisotropic {
+            iterations,
+            minimum,
+            maximum,
+            tolerance,
+        }) => RemeshOptions::default()
+            .iterations(iterations)
+            .sizing(Sizing::Anisotropic {
+                minimum,
+                maximum,
+                tolerance,
+            }),
+    };
+    options.features(features.options())
+}
+
+fn set_options(sidesets: &[String], nodesets: &[String]) -> SetOptions {
+    let options = sidesets
+        .iter()
+        .fold(SetOptions::default(), |options, spec| options.sideset(spec));
+    nodesets
+        .iter()
+        .fold(options, |options, spec| options.nodeset(spec))
+}
+
+pub fn batch(
+    recipe: String,
+    jobs: Option<usize>,
+    summary: Option<String>,
+    quiet: bool,
+) -> Result<(), Error> {
+    let cases = Batch::new(Recipe::read(&recipe)?).jobs(jobs).run(quiet)?;
+    if let Some(file) = summary {
+        write_summary(&cases, &file, quiet)?;
+    }
+    match cases.iter().filter(|case| case.error.is_some()).count() {
+        0 => Ok(()),
+        failed => Err(Error::Mesh(format!(
+            "{failed} of {} cases failed",
+            cases.len()
+        ))),
+    }
+}
+
+pub fn mesh(element: Element, args: MeshArgs, quiet: bool) -> Result<(), Error> {
+    check_metric_fields(&args.output, args.metric_fields)?;
+    let (mut mesh, mut names) = args.options(element).mesh_named(&args.input, quiet)?;
+    if let Some(MeshSmoothCommands::Smooth {
+        remeshing,
+        smoothing,
+    }) = args.smoothing
+    {
+        smoothing.options()?.smooth(&mut mesh, quiet)?;
+        if let Some(MeshRemeshSubcommand::Remesh { features, mode }) = remeshing {
+            mesh = remesh_options(features, mode).remesh(mesh, quiet)?;
+        }
+    }
+    set_options(&args.sideset, &args.nodeset).tag(&mut mesh, &mut names, quiet)?;
+    if let Some(file) = &args.metrics {
+        write_metrics(&mesh, file, quiet)?;
+    }
+    write_output(&args.output, mesh, &names, args.metric_fields, quiet)
+}
+
+pub fn smooth(args: SmoothArgs, quiet: bool) -> Result<(), Error> {
+    check_metric_fields(&args.output, args.metric_fields)?;
+    let mut mesh = read_mesh(&args.input, quiet, true)?;
+    let mut names = SetNames::default();
+    args.smoothing.options()?.smooth(&mut mesh, quiet)?;
+    if let Some(MeshRemeshSubcommand::Remesh { features, mode }) = args.remeshing {
+        mesh = remesh_options(features, mode).remesh(mesh, quiet)?;
+    }
+    set_options(&args.sideset, &args.nodeset).tag(&mut mesh, &mut names, quiet)?;
+    if let Some(file) = args.metrics {
+        write_metrics(&mesh, &file, quiet)?;
+    }
+    write_output(&args.output, mesh, &names, args.metric_fields, quiet)
+}
+
+fn check_metric_fields(file: &str, metric_fields: bool) -> Result<(), Error> {
+    match extension(file) {
+        Some("exo" | "vtu") => Ok(()),
+        _ if metric_fields => Err(Error::InvalidOption(format!(
+            "Metric fields need an exo or vtu output, not {file}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Writes a mesh with its named sets, and with its quality metrics as element
+/// fields if requested.
+fn write_output(
+    file: &str,
+    mesh: Mesh<3>,
+    names: &SetNames,
+    metric_fields: bool,
+    quiet: bool,
+) -> Result<(), Error> {
+    if metric_fields {
+        write_metrics_named(&mesh, file, names, quiet)
+    } else {
+        write_mesh_named(file, mesh, names, quiet)
+    }
+}
+
+pub fn remesh(
+    input: String,
+    output: String,
+    features: FeatureArgs,
+    mode: Option<MeshRemeshCommands>,
+    quiet: bool,
+) -> Result<(), Error> {
+    let mesh = read_mesh(&input, quiet, true)?;
+    let mesh = remesh_options(features, mode).remesh(mesh, quiet)?;
+    write_mesh(&output, mesh, quiet)
+}
+
+pub fn improve(args: ImproveArgs, quiet: bool) -> Result<(), Error> {
+    let mut mesh = read_mesh(&args.input, quiet, true)?;
+    ImproveOptions::default()
+        .iterations(args.iterations)
+        .target(args.target)
+        .improve(&mut mesh, quiet)?;
+    if let Some(file) = &args.metrics {
+        write_metrics(&mesh, file, quiet)?;
+    }
+    write_mesh(&args.output, mesh, quiet)
+}
+
+pub fn metrics(args: MetricsArgs, quiet: bool) -> Result<(), Error> {
+    if args.output.is_none() && !args.report && args.gate.is_empty() {
+        return Err(Error::InvalidOption(
+            "Metrics need an output file, a report, or a gate".into(),
+        ));
+    }
+    let columns = match &args.columns {
+        Some(columns) => columns
+            .iter()
+            .map(|column| column.parse())
+            .collect::<Result<_, _>>()?,
+        None => METRICS_DEFAULT_COLUMNS.to_vec(),
+    };
+    let options = args.gate.iter().try_fold(
+        ReportOptions::default()
+            .bins(args.bins)
+            .worst(args.worst)
+            .statistics(args.report)
+            .columns(columns.clone()),
+        |options, gate| Ok::<_, Error>(options.gate(gate.parse()?)),
+    )?;
+    let mesh = read_mesh(&args.input, quiet, true)?;
+    if let Some(file) = &args.output {
+        write_metric_columns(&mesh, file, &columns, quiet)?;
+    }
+    if args.report || !args.gate.is_empty() {
+        options.report(&mesh, quiet)?;
+    }
+    Ok(())
+}
+
+pub fn segment(args: SegmentArgs, quiet: bool) -> Result<(), Error> {
+    let tie = match args.tie {
+        Some(tie) => tie.parse()?,
+        None => Tie::Lowest,
+    };
+    let mesh = read_mesh(&args.input, quiet, true)?;
+    let segmented = SegmentOptions::new(args.size)
+        .grid(args.grid)
+        .remove(args.remove.unwrap_or_default())
+        .tie(tie)
+        .segment(&mesh, quiet)?;
+    if let Some(file) = &args.fractions {
+        write_fractions(
+            file,
+            *segmented.voxels.nel(),
+            &segmented.fractions,
+            &segmented.embedding,
+            quiet,
+        )?;
+    }
+    match extension(&args.output) {
+        _ if is_segmentation(&args.output) || extension(&args.output) == Some("vti") => {
+            write_segmentation(
+                &args.output,
+                &segmented.voxels,
+                Some(&segmented.embedding),
+                quiet,
+            )
+        }
+        Some("exo") | Some("inp") | Some("mesh") | Some("vtu") => {
+            let mesh = Mesh::from_voxels(segmented.voxels, Some(&[0]));
+            write_mesh(&args.output, mesh, quiet)
+        }
+        extension => Err(Error::InvalidOutput {
+            file: args.output.clone(),
+            extension: extension.map(String::from),
+        }),
+    }
+}
+
+pub fn convert_mesh(args: ConvertMeshArgs, quiet: bool) -> Result<(), Error> {
+    let mut mesh = read_mesh(&args.input, quiet, true)?;
+    if args.hex {
+        let time = Instant::now();
+        crate::echo!(
+            quiet,
+            "   \x1b[1;96mSplitting\x1b[0m tetrahedra into hexahedra"
+        );
+        mesh = tetrahedra_to_hexahedra(mesh)?;
+        crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+    }
+    let mut names = SetNames::default();
+    set_options(&args.sideset, &args.nodeset).tag(&mut mesh, &mut names, quiet)?;
+    write_mesh_named(&args.output, mesh, &names, quiet)
+}
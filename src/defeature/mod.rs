@@ -1,26 +1,52 @@
 use super::{
-    ErrorWrapper,
+    Error, Label,
     io::{read_segmentation, write_segmentation},
 };
+use conspire::geometry::grid::Voxels;
 use std::time::Instant;
 
-#[allow(clippy::too_many_arguments)]
-pub fn defeature(
-    input: String,
-    output: String,
+/// Options for defeaturing a segmentation.
+///
+/// ```no_run
+/// use automesh::{DefeatureOptions, read_segmentation};
+///
+/// let (voxels, _) = read_segmentation("letter_f_3d.npy", None, None, None, true, false)?;
+/// let voxels = DefeatureOptions::new(2).defeature_voxels(voxels, true);
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DefeatureOptions {
     min: usize,
-    nelx: Option<usize>,
-    nely: Option<usize>,
-    nelz: Option<usize>,
-    quiet: bool,
-) -> Result<(), ErrorWrapper> {
-    let voxels = read_segmentation(&input, nelx, nely, nelz, quiet, true)?;
-    let time = Instant::now();
-    crate::echo!(
-        quiet,
-        " \x1b[1;96mDefeaturing\x1b[0m clusters of {min} voxels or less"
-    );
-    let voxels = voxels.defeature(min);
-    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-    write_segmentation(&output, &voxels, quiet)
+    nel: Option<[usize; 3]>,
+}
+
+impl DefeatureOptions {
+    /// Options for defeaturing clusters of at most the given number of voxels.
+    pub fn new(min: usize) -> Self {
+        Self { min, nel: None }
+    }
+    /// Number of voxels in each direction, needed for an spn segmentation.
+    pub fn nel(mut self, nel: impl Into<Option<[usize; 3]>>) -> Self {
+        self.nel = nel.into();
+        self
+    }
+    /// Defeatures a segmentation file, writing the result to another.
+    pub fn defeature(&self, input: &str, output: &str, quiet: bool) -> Result<(), Error> {
+        let [nelx, nely, nelz] = self.nel.map_or([None; 3], |nel| nel.map(Some));
+        let (voxels, embedding) = read_segmentation(input, nelx, nely, nelz, quiet, true)?;
+        let voxels = self.defeature_voxels(voxels, quiet);
+        write_segmentation(output, &voxels, embedding.as_ref(), quiet)
+    }
+    /// Defeatures a segmentation already in memory.
+    pub fn defeature_voxels(&self, voxels: Voxels<Label>, quiet: bool) -> Voxels<Label> {
+        let time = Instant::now();
+        crate::echo!(
+            quiet,
+            " \x1b[1;96mDefeaturing\x1b[0m clusters of {} voxels or less",
+            self.min
+        );
+        let voxels = voxels.defeature(self.min);
+        crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+        voxels
+    }
 }
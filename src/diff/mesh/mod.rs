@@ -0,0 +1,374 @@
+use crate::{
+    Error, Metric,
+    improve::closest,
+    io::{read_mesh, write_mesh_node_fields},
+    smooth::points,
+    vector::{Point, cross, distance, dot},
+};
+use conspire::geometry::mesh::{Connectivity, Mesh};
+use std::{collections::HashMap, time::Instant};
+
+/// Largest number of grid cells along the bounding box of a surface.
+const GRID_CELLS: f64 = 512.0;
+
+/// Distances between the surfaces of two meshes, sampled at their surface
+/// nodes, and the volume of each block of each mesh.
+///
+/// The surface of a mesh is its triangles, quadrilaterals, and polygons, and
+/// the faces of its cells that belong to no other cell.
+#[derive(Clone, Debug)]
+pub struct Deviation {
+    /// Largest distance from a surface node of the first mesh to the surface
+    /// of the second, and from the second to the first.
+    pub hausdorff: [f64; 2],
+    /// Mean distance from the surface nodes of the first mesh to the surface
+    /// of the second, and from the second to the first.
+    pub mean: [f64; 2],
+    /// Distance from each node of the second mesh to the surface of the
+    /// first, which is NaN off its surface.
+    pub distances: Vec<f64>,
+    /// Each block number, with its volume in the first and second mesh, or
+    /// zero in a mesh without the block.
+    pub volumes: Vec<(usize, f64, f64)>,
+}
+
+impl Deviation {
+    /// Compares the surfaces and the block volumes of two meshes.
+    pub fn new(first: &Mesh<3>, second: &Mesh<3>) -> Result<Self, Error> {
+        let surfaces = [Surface::of(first)?, Surface::of(second)?];
+        let forward = surfaces[1].distances(&surfaces[0].points, &surfaces[0].nodes);
+        let backward = surfaces[0].distances(&surfaces[1].points, &surfaces[1].nodes);
+        let mut distances = vec![f64::NAN; second.number_of_nodes()];
+        surfaces[1]
+            .nodes
+            .iter()
+            .zip(&backward)
+            .for_each(|(&node, &distance)| distances[node] = distance);
+        let hausdorff =
+            [&forward, &backward].map(|values| values.iter().copied().fold(0.0, f64::max));
+        let mean =
+            [&forward, &backward].map(|values| values.iter().sum::<f64>() / values.len() as f64);
+        let [first_volumes, second_volumes] = [first, second].map(block_volumes);
+        let mut numbers: Vec<usize> = first_volumes
+            .keys()
+            .chain(second_volumes.keys())
+            .copied()
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        let volumes = numbers
+            .into_iter()
+            .map(|number| {
+                (
+                    number,
+                    first_volumes.get(&number).copied().unwrap_or(0.0),
+                    second_volumes.get(&number).copied().unwrap_or(0.0),
+                )
+            })
+            .collect();
+        Ok(Self {
+            hausdorff,
+            mean,
+            distances,
+            volumes,
+        })
+    }
+    /// The larger of the two one-sided Hausdorff distances.
+    pub fn symmetric_hausdorff(&self) -> f64 {
+        self.hausdorff[0].max(self.hausdorff[1])
+    }
+    /// The mean of the two one-sided mean distances.
+    pub fn mean_deviation(&self) -> f64 {
+        0.5 * (self.mean[0] + self.mean[1])
+    }
+}
+
+/// Compares the surfaces and block volumes of two meshes, and writes the
+/// second mesh with the distance from each of its surface nodes to the
+/// surface of the first (vtu).
+pub(super) fn diff_meshes(
+    first: &str,
+    second: &str,
+    output: &str,
+    quiet: bool,
+) -> Result<(), Error> {
+    let meshes = [
+        read_mesh(first, quiet, true)?,
+        read_mesh(second, quiet, false)?,
+    ];
+    let time = Instant::now();
+    crate::echo!(quiet, "   \x1b[1;96mComparing\x1b[0m {first} and {second}");
+    let deviation = Deviation::new(&meshes[0], &meshes[1])?;
+    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+    crate::echo!(
+        quiet,
+        "   \x1b[1;96mHausdorff\x1b[0m {:.6e} \x1b[2m[{:.6e} from {first}, {:.6e} from {second}]\x1b[0m",
+        deviation.symmetric_hausdorff(),
+        deviation.hausdorff[0],
+        deviation.hausdorff[1]
+    );
+    crate::echo!(
+        quiet,
+        "   \x1b[1;96mDeviation\x1b[0m {:.6e} mean \x1b[2m[{:.6e} from {first}, {:.6e} from {second}]\x1b[0m",
+        deviation.mean_deviation(),
+        deviation.mean[0],
+        deviation.mean[1]
+    );
+    crate::echo!(
+        quiet,
+        "      \x1b[1;96mVolume\x1b[0m \x1b[1m{:>6}{:>14}{:>14}{:>14}{:>12}\x1b[0m",
+        "block",
+        "first",
+        "second",
+        "difference",
+        "relative"
+    );
+    deviation.volumes.iter().for_each(|&(number, a, b)| {
+        let relative = if a == 0.0 {
+            format!("{:>12}", "-")
+        } else {
+            format!("{:>11.3}%", 100.0 * (b - a) / a)
+        };
+        crate::echo!(
+            quiet,
+            "             {number:>6}{a:>14.6e}{b:>14.6e}{:>14.6e}{relative}",
+            b - a
+        )
+    });
+    crate::echo!(quiet, "     \x1b[1;96mWriting\x1b[0m {output}");
+    let time = Instant::now();
+    write_mesh_node_fields(output, &meshes[1], &[("distance", deviation.distances)])?;
+    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+    Ok(())
+}
+
+/// The volume of each block, by its number, with that of a block of surface
+/// elements being the volume it encloses.
+fn block_volumes(mesh: &Mesh<3>) -> HashMap<usize, f64> {
+    let coordinates = points(mesh.coordinates());
+    let mut volumes = HashMap::new();
+    mesh.iter()
+        .zip(Metric::Volume.evaluate(mesh))
+        .enumerate()
+        .for_each(|(block, (connectivity, cells))| {
+            let volume = match connectivity {
+                Connectivity::Polygonal(_)
+                | Connectivity::Quadrilateral(_)
+                | Connectivity::Triangular(_) => connectivity
+                    .into_iter()
+                    .map(|element| {
+                        let polygon = polygon(connectivity, element);
+                        (1..polygon.len() - 1)
+                            .map(|k| {
+                                let [a, b, c] = [polygon[0], polygon[k], polygon[k + 1]]
+                                    .map(|node| coordinates[node]);
+                                dot(&a, &cross(&b, &c)) / 6.0
+                            })
+                            .sum::<f64>()
+                    })
+                    .sum::<f64>()
+                    .abs(),
+                Connectivity::Hexahedral(_)
+                | Connectivity::Polyhedral(_)
+                | Connectivity::Pyramidal(_)
+                | Connectivity::Tetrahedral(_)
+                | Connectivity::Wedge(_) => cells.iter().sum(),
+            };
+            *volumes
+                .entry(mesh.blocks().map_or(block + 1, |numbers| numbers[block]))
+                .or_insert(0.0) += volume
+        });
+    volumes
+}
+
+/// The nodes of a surface element in order around it.
+fn polygon(connectivity: &Connectivity, element: &[usize]) -> Vec<usize> {
+    match connectivity {
+        Connectivity::Polygonal(_) => {
+            let mut edges = connectivity.element_faces(element);
+            let mut nodes = edges.swap_remove(0);
+            while let Some(next) = edges
+                .iter()
+                .position(|edge| edge.contains(nodes.last().unwrap()))
+            {
+                let edge = edges.swap_remove(next);
+                let node = if edge[0] == *nodes.last().unwrap() {
+                    edge[1]
+                } else {
+                    edge[0]
+                };
+                if node == nodes[0] {
+                    break;
+                }
+                nodes.push(node)
+            }
+            nodes
+        }
+        _ => element.to_vec(),
+    }
+}
+
+/// The triangles of the surface of a mesh, binned into a uniform grid.
+struct Surface {
+    points: Vec<Point>,
+    nodes: Vec<usize>,
+    triangles: Vec<[Point; 3]>,
+    minimum: Point,
+    size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl Surface {
+    fn of(mesh: &Mesh<3>) -> Result<Self, Error> {
+        let points = points(mesh.coordinates());
+        let mut faces: HashMap<Vec<usize>, (Vec<usize>, usize)> = HashMap::new();
+        let mut polygons = Vec::new();
+        mesh.iter().for_each(|connectivity| match connectivity {
+            Connectivity::Polygonal(_)
+            | Connectivity::Quadrilateral(_)
+            | Connectivity::Triangular(_) => connectivity
+                .into_iter()
+                .for_each(|element| polygons.push(polygon(connectivity, element))),
+            Connectivity::Hexahedral(_)
+            | Connectivity::Polyhedral(_)
+            | Connectivity::Pyramidal(_)
+            | Connectivity::Tetrahedral(_)
+            | Connectivity::Wedge(_) => connectivity.into_iter().for_each(|element| {
+                connectivity
+                    .element_faces(element)
+                    .into_iter()
+                    .for_each(|face| {
+                        let mut key = face.clone();
+                        key.sort_unstable();
+                        faces.entry(key).or_insert_with(|| (face, 0)).1 += 1
+                    })
+            }),
+        });
+        polygons.extend(
+            faces
+                .into_values()
+                .filter(|&(_, count)| count == 1)
+                .map(|(face, _)| face),
+        );
+        let mut nodes: Vec<usize> = polygons.iter().flatten().copied().collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        if nodes.is_empty() {
+            return Err(Error::Mesh("Mesh has no surface to compare".into()));
+        }
+        let triangles: Vec<[Point; 3]> = polygons
+            .iter()
+            .flat_map(|polygon| {
+                (1..polygon.len() - 1)
+                    .map(|k| [polygon[0], polygon[k], polygon[k + 1]].map(|node| points[node]))
+            })
+            .collect();
+        let minimum = [0, 1, 2].map(|axis| {
+            nodes
+                .iter()
+                .map(|&node| points[node][axis])
+                .fold(f64::INFINITY, f64::min)
+        });
+        let extent = [0, 1, 2]
+            .map(|axis| {
+                nodes
+                    .iter()
+                    .map(|&node| points[node][axis] - minimum[axis])
+                    .fold(0.0, f64::max)
+            })
+            .into_iter()
+            .fold(0.0, f64::max);
+        let edges = triangles
+            .iter()
+            .map(|[a, b, _]| distance(b, a))
+            .sum::<f64>()
+            / triangles.len().max(1) as f64;
+        let size = edges.max(extent / GRID_CELLS).max(f64::MIN_POSITIVE);
+        let mut surface = Self {
+            points,
+            nodes,
+            triangles,
+            minimum,
+            size,
+            cells: HashMap::new(),
+        };
+        (0..surface.triangles.len()).for_each(|index| {
+            let triangle = &surface.triangles[index];
+            let [low, high] = [f64::min, f64::max].map(|bound| {
+                surface.cell(&[0, 1, 2].map(|axis| {
+                    bound(
+                        bound(triangle[0][axis], triangle[1][axis]),
+                        triangle[2][axis],
+                    )
+                }))
+            });
+            (low[0]..=high[0]).for_each(|i| {
+                (low[1]..=high[1]).for_each(|j| {
+                    (low[2]..=high[2])
+                        .for_each(|k| surface.cells.entry([i, j, k]).or_default().push(index))
+                })
+            })
+        });
+        Ok(surface)
+    }
+    fn cell(&self, point: &Point) -> [i64; 3] {
+        [0, 1, 2].map(|axis| ((point[axis] - self.minimum[axis]) / self.size).floor() as i64)
+    }
+    /// Distance from each of some nodes to the surface.
+    fn distances(&self, points: &[Point], nodes: &[usize]) -> Vec<f64> {
+        let (low, high) =
+            self.cells
+                .keys()
+                .fold(([i64::MAX; 3], [i64::MIN; 3]), |(low, high), cell| {
+                    (
+                        [0, 1, 2].map(|axis| low[axis].min(cell[axis])),
+                        [0, 1, 2].map(|axis| high[axis].max(cell[axis])),
+                    )
+                });
+        nodes
+            .iter()
+            .map(|&node| self.distance(&points[node], &low, &high))
+            .collect()
+    }
+    /// Distance from a point to the surface, searching shells of grid cells
+    /// outward from the nearest one until no closer triangle can remain.
+    fn distance(&self, point: &Point, low: &[i64; 3], high: &[i64; 3]) -> f64 {
+        let unclamped = self.cell(point);
+        let center = [0, 1, 2].map(|axis| unclamped[axis].clamp(low[axis], high[axis]));
+        let reach = [0, 1, 2]
+            .map(|axis| (center[axis] - low[axis]).max(high[axis] - center[axis]))
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+        let mut best = f64::INFINITY;
+        for radius in 0..=reach {
+            let range = |axis: usize| {
+                (center[axis] - radius).max(low[axis])..=(center[axis] + radius).min(high[axis])
+            };
+            for i in range(0) {
+                for j in range(1) {
+                    let layers =
+                        if (i - center[0]).abs() == radius || (j - center[1]).abs() == radius {
+                            range(2).collect()
+                        } else {
+                            vec![center[2] - radius, center[2] + radius]
+                        };
+                    layers.into_iter().for_each(|k| {
+                        self.cells
+                            .get(&[i, j, k])
+                            .into_iter()
+                            .flatten()
+                            .for_each(|&index| {
+                                best = best
+                                    .min(distance(&closest(point, &self.triangles[index]), point))
+                            })
+                    })
+                }
+            }
+            if best <= radius as f64 * self.size {
+                break;
+            }
+        }
+        best
+    }
+}
@@ -1,17 +1,78 @@
+mod mesh;
+
 use super::{
-    ErrorWrapper,
-    io::{read_segmentation, write_segmentation},
+    Error, Label,
+    io::{is_segmentation, read_segmentation, relabel, write_segmentation},
 };
 
-pub fn diff(
-    input: Vec<String>,
-    output: String,
-    nelx: Option<usize>,
-    nely: Option<usize>,
-    nelz: Option<usize>,
-    quiet: bool,
-) -> Result<(), ErrorWrapper> {
-    let voxels_1 = read_segmentation(&input[0], nelx, nely, nelz, quiet, true)?;
-    let voxels_2 = read_segmentation(&input[1], nelx, nely, nelz, quiet, false)?;
-    write_segmentation(&output, &voxels_1.diff(&voxels_2), quiet)
+pub use mesh::Deviation;
+
+use conspire::geometry::{grid::Voxels, mesh::Mesh};
+use mesh::diff_meshes;
+
+/// Options for showing the difference between two segmentations or two meshes.
+///
+/// ```no_run
+/// use automesh::{DiffOptions, read_mesh};
+///
+/// let first = read_mesh("first.stl", true, false)?;
+/// let second = read_mesh("second.stl", true, false)?;
+/// let deviation = DiffOptions::default().diff_meshes(&first, &second)?;
+/// println!("{}", deviation.symmetric_hausdorff());
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    nel: Option<[usize; 3]>,
+}
+
+impl DiffOptions {
+    /// Number of voxels in each direction, needed for spn segmentations.
+    pub fn nel(mut self, nel: impl Into<Option<[usize; 3]>>) -> Self {
+        self.nel = nel.into();
+        self
+    }
+    /// Writes the difference between two segmentation files, or the second of
+    /// two mesh files with the distance from each of its surface nodes to the
+    /// surface of the first (vtu).
+    pub fn diff(&self, first: &str, second: &str, output: &str, quiet: bool) -> Result<(), Error> {
+        match (is_segmentation(first), is_segmentation(second)) {
+            (true, true) => {
+                let [nelx, nely, nelz] = self.nel.map_or([None; 3], |nel| nel.map(Some));
+                let (voxels_1, embedding) =
+                    read_segmentation(first, nelx, nely, nelz, quiet, true)?;
+                let (voxels_2, _) = read_segmentation(second, nelx, nely, nelz, quiet, false)?;
+                write_segmentation(
+                    output,
+                    &self.diff_voxels(&voxels_1, &voxels_2)?,
+                    embedding.as_ref(),
+                    quiet,
+                )
+            }
+            (false, false) => diff_meshes(first, second, output, quiet),
+            _ => Err(Error::InvalidOption(
+                "Diff needs two segmentations or two meshes".into(),
+            )),
+        }
+    }
+    /// The voxels of two segmentations in memory that differ, which are one
+    /// where the labels differ and zero elsewhere.
+    pub fn diff_voxels(
+        &self,
+        first: &Voxels<Label>,
+        second: &Voxels<Label>,
+    ) -> Result<Voxels<Label>, Error> {
+        if first.nel() != second.nel() {
+            return Err(Error::InvalidData(format!(
+                "Segmentations of {:?} and {:?} voxels cannot be compared",
+                first.nel(),
+                second.nel()
+            )));
+        }
+        relabel(&first.diff(second), |voxel| Ok(Label::from(voxel)))
+    }
+    /// Compares the surfaces and block volumes of two meshes in memory.
+    pub fn diff_meshes(&self, first: &Mesh<3>, second: &Mesh<3>) -> Result<Deviation, Error> {
+        Deviation::new(first, second)
+    }
 }
@@ -1,36 +1,71 @@
 use std::{
-    fmt::{self, Debug, Formatter},
+    error,
+    fmt::{self, Display, Formatter},
     io::Error as ErrorIO,
 };
 
-pub struct ErrorWrapper {
-    message: String,
+/// Errors from reading, meshing, and writing.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io(ErrorIO),
+    /// An input file has an unsupported extension.
+    InvalidInput {
+        file: String,
+        extension: Option<String>,
+    },
+    /// An output file has an unsupported extension.
+    InvalidOutput {
+        file: String,
+        extension: Option<String>,
+    },
+    /// An option is invalid, or does not apply to the given inputs.
+    InvalidOption(String),
+    /// The contents of a file are invalid or unsupported.
+    InvalidData(String),
+    /// A mesh does not suit an operation, or an operation on it failed.
+    Mesh(String),
 }
 
-impl Debug for ErrorWrapper {
+impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "\x1b[1;91m{}.\x1b[0m", self.message)
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::InvalidInput { file, extension } => write!(
+                f,
+                "Invalid extension .{} from input file {file}",
+                extension.as_deref().unwrap_or("UNDEFINED")
+            ),
+            Self::InvalidOutput { file, extension } => write!(
+                f,
+                "Invalid extension .{} from output file {file}",
+                extension.as_deref().unwrap_or("UNDEFINED")
+            ),
+            Self::InvalidOption(message) | Self::InvalidData(message) | Self::Mesh(message) => {
+                write!(f, "{message}")
+            }
+        }
     }
 }
 
-impl From<ErrorIO> for ErrorWrapper {
-    fn from(error: ErrorIO) -> ErrorWrapper {
-        ErrorWrapper {
-            message: error.to_string(),
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
         }
     }
 }
 
-impl From<String> for ErrorWrapper {
-    fn from(message: String) -> ErrorWrapper {
-        ErrorWrapper { message }
+impl From<ErrorIO> for Error {
+    fn from(error: ErrorIO) -> Error {
+        Error::Io(error)
     }
 }
 
-impl From<&str> for ErrorWrapper {
-    fn from(error: &str) -> ErrorWrapper {
-        ErrorWrapper {
-            message: error.to_string(),
-        }
+/// Failed mesh operations report their reasons as messages.
+impl From<&str> for Error {
+    fn from(error: &str) -> Error {
+        Error::Mesh(error.to_string())
     }
 }
@@ -1,24 +1,68 @@
 use super::{
-    ErrorWrapper,
-    io::{read_segmentation, write_segmentation},
+    Error, Label,
+    io::{Embedding, read_segmentation, write_segmentation},
 };
+use conspire::geometry::grid::Voxels;
+use std::array::from_fn;
 
-#[allow(clippy::too_many_arguments)]
-pub fn extract(
-    input: String,
-    output: String,
-    nelx: Option<usize>,
-    nely: Option<usize>,
-    nelz: Option<usize>,
-    xmin: usize,
-    xmax: usize,
-    ymin: usize,
-    ymax: usize,
-    zmin: usize,
-    zmax: usize,
-    quiet: bool,
-) -> Result<(), ErrorWrapper> {
-    let voxels = read_segmentation(&input, nelx, nely, nelz, quiet, true)?;
-    let extracted = voxels.extract([xmin..xmax + 1, ymin..ymax + 1, zmin..zmax + 1]);
-    write_segmentation(&output, &extracted, quiet)
+/// Options for extracting a range of voxels from a segmentation.
+///
+/// ```no_run
+/// use automesh::{ExtractOptions, read_segmentation};
+///
+/// let (voxels, embedding) = read_segmentation("letter_f_3d.npy", None, None, None, true, false)?;
+/// let (voxels, embedding) =
+///     ExtractOptions::new([0, 0, 0], [1, 2, 3]).extract_voxels(&voxels, embedding)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    min: [usize; 3],
+    max: [usize; 3],
+    nel: Option<[usize; 3]>,
+}
+
+impl ExtractOptions {
+    /// Options for extracting the voxels from the minimum to the maximum
+    /// index in each direction, inclusive.
+    pub fn new(min: [usize; 3], max: [usize; 3]) -> Self {
+        Self {
+            min,
+            max,
+            nel: None,
+        }
+    }
+    /// Number of voxels in each direction, needed for an spn segmentation.
+    pub fn nel(mut self, nel: impl Into<Option<[usize; 3]>>) -> Self {
+        self.nel = nel.into();
+        self
+    }
+    /// Extracts from a segmentation file, writing the result to another.
+    pub fn extract(&self, input: &str, output: &str, quiet: bool) -> Result<(), Error> {
+        let [nelx, nely, nelz] = self.nel.map_or([None; 3], |nel| nel.map(Some));
+        let (voxels, embedding) = read_segmentation(input, nelx, nely, nelz, quiet, true)?;
+        let (voxels, embedding) = self.extract_voxels(&voxels, embedding)?;
+        write_segmentation(output, &voxels, embedding.as_ref(), quiet)
+    }
+    /// Extracts from a segmentation already in memory, moving its embedding,
+    /// if any, to the first extracted voxel.
+    pub fn extract_voxels(
+        &self,
+        voxels: &Voxels<Label>,
+        embedding: Option<Embedding>,
+    ) -> Result<(Voxels<Label>, Option<Embedding>), Error> {
+        let nel = voxels.nel();
+        if (0..3).any(|axis| self.min[axis] > self.max[axis] || self.max[axis] >= nel[axis]) {
+            return Err(Error::InvalidOption(format!(
+                "Extracting voxels {:?} to {:?} needs 0 <= minimum <= maximum < {nel:?}",
+                self.min, self.max
+            )));
+        }
+        let extracted = voxels.extract(from_fn(|axis| self.min[axis]..self.max[axis] + 1));
+        let embedding = embedding.map(|Embedding { scale, translate }| Embedding {
+            scale,
+            translate: from_fn(|axis| translate[axis] + self.min[axis] as f64 * scale[axis]),
+        });
+        Ok((extracted, embedding))
+    }
 }
@@ -0,0 +1,217 @@
+use super::{
+    Error,
+    vector::{Point, add, distance, dot, scale, sub},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+};
+
+/// Fraction of the length of an edge within which its nodes and midpoint
+/// must lie of a feature polyline for the edge to follow it.
+const POLYLINE_TOLERANCE: f64 = 0.1;
+
+/// Sharp edges and corners of a surface to keep when remeshing or smoothing.
+///
+/// Features are the edges between faces meeting at more than a dihedral
+/// angle, and the edges along any given polylines.  Nodes on a feature move
+/// only along it, and corners, where features end, meet, or turn sharply, do
+/// not move at all.  Giving any features to `RemeshOptions` switches uniform
+/// and adaptive sizing from conspire's remesher to the in-crate one.
+///
+/// ```no_run
+/// use automesh::{FeatureOptions, RemeshOptions, read_mesh};
+///
+/// let mesh = read_mesh("bracket.stl", true, false)?;
+/// let mesh = RemeshOptions::default()
+///     .features(FeatureOptions::default().angle(Some(30.0)))
+///     .remesh(mesh, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FeatureOptions {
+    angle: Option<f64>,
+    polylines: Option<String>,
+}
+
+impl FeatureOptions {
+    /// Dihedral angle in degrees above which an edge is a feature, and above
+    /// which a turn along a feature is a corner.
+    pub fn angle(mut self, angle: Option<f64>) -> Self {
+        self.angle = angle;
+        self
+    }
+    /// Feature polylines file (csv | txt) of one point `x,y,z` per line, with
+    /// blank lines between polylines.
+    pub fn polylines(mut self, file: Option<String>) -> Self {
+        self.polylines = file;
+        self
+    }
+    /// Whether no features are to be kept.
+    pub fn is_empty(&self) -> bool {
+        self.angle.is_none() && self.polylines.is_none()
+    }
+    /// Feature edges of a surface of consistently oriented faces.
+    pub(crate) fn edges(
+        &self,
+        faces: &[Vec<usize>],
+        points: &[Point],
+    ) -> Result<HashSet<(usize, usize)>, Error> {
+        let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        faces.iter().enumerate().for_each(|(index, face)| {
+            (0..face.len()).for_each(|corner| {
+                let edge = edge(face[corner], face[(corner + 1) % face.len()]);
+                edge_faces.entry(edge).or_default().push(index)
+            })
+        });
+        let mut edges = HashSet::new();
+        if let Some(angle) = self.threshold()? {
+            let normals: Vec<Point> = faces.iter().map(|face| normal(face, points)).collect();
+            edges.extend(
+                edge_faces
+                    .iter()
+                    .filter(|(_, faces)| {
+                        faces.len() == 2 && between(&normals[faces[0]], &normals[faces[1]]) > angle
+                    })
+                    .map(|(&edge, _)| edge),
+            );
+        }
+        if let Some(file) = &self.polylines {
+            let segments = read_polylines(file)?;
+            if segments.is_empty() {
+                return Err(Error::InvalidData(format!(
+                    "Feature polylines file {file} has no segments"
+                )));
+            }
+            let before = edges.len();
+            edges.extend(edge_faces.keys().copied().filter(|&(u, v)| {
+                let tolerance = POLYLINE_TOLERANCE * distance(&points[u], &points[v]);
+                let midpoint = [0, 1, 2].map(|i| 0.5 * (points[u][i] + points[v][i]));
+                [&points[u], &points[v], &midpoint]
+                    .into_iter()
+                    .all(|point| {
+                        segments
+                            .iter()
+                            .any(|segment| distance(point, &closest(point, segment)) <= tolerance)
+                    })
+            }));
+            if edges.len() == before {
+                return Err(Error::InvalidData(format!(
+                    "Feature polylines in {file} follow no edges of the mesh"
+                )));
+            }
+        }
+        Ok(edges)
+    }
+    /// Whether each node is a corner of the given feature edges, where they
+    /// end, meet, or turn by more than the angle.
+    pub(crate) fn corners(&self, edges: &HashSet<(usize, usize)>, points: &[Point]) -> Vec<bool> {
+        let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+        edges.iter().for_each(|&(u, v)| {
+            neighbors[u].push(v);
+            neighbors[v].push(u);
+        });
+        let angle = self.angle.map(f64::to_radians);
+        neighbors
+            .iter()
+            .enumerate()
+            .map(|(node, neighbors)| match neighbors[..] {
+                [] => false,
+                [before, after] => angle.is_some_and(|angle| {
+                    between(
+                        &sub(&points[node], &points[before]),
+                        &sub(&points[after], &points[node]),
+                    ) > angle
+                }),
+                _ => true,
+            })
+            .collect()
+    }
+    fn threshold(&self) -> Result<Option<f64>, Error> {
+        match self.angle {
+            Some(angle) if !(angle > 0.0 && angle < 180.0) => Err(Error::InvalidOption(format!(
+                "Feature angle {angle} must be between 0 and 180 degrees"
+            ))),
+            angle => Ok(angle.map(f64::to_radians)),
+        }
+    }
+}
+
+/// Segments of the polylines in a file of one point per line.
+fn read_polylines(file: &str) -> Result<Vec<[Point; 2]>, Error> {
+    let mut segments = Vec::new();
+    let mut previous: Option<Point> = None;
+    for (number, line) in read_to_string(file)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            previous = None;
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let values: Vec<f64> = line
+            .split(|character: char| character == ',' || character.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| {
+                Error::InvalidData(format!(
+                    "Feature polylines file {file} has an invalid point on line {}",
+                    number + 1
+                ))
+            })?;
+        let &[x, y, z] = values.as_slice() else {
+            return Err(Error::InvalidData(format!(
+                "Feature polylines file {file} needs three coordinates on line {}",
+                number + 1
+            )));
+        };
+        if let Some(start) = previous {
+            segments.push([start, [x, y, z]]);
+        }
+        previous = Some([x, y, z]);
+    }
+    Ok(segments)
+}
+
+pub(crate) fn edge(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Closest point to a point on a segment.
+pub(crate) fn closest(point: &Point, [start, end]: &[Point; 2]) -> Point {
+    let along = sub(end, start);
+    let length = dot(&along, &along);
+    let fraction = if length > 0.0 {
+        (dot(&sub(point, start), &along) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    add(start, &scale(&along, fraction))
+}
+
+/// Area-weighted normal of a polygon.
+fn normal(face: &[usize], points: &[Point]) -> Point {
+    (0..face.len()).fold([0.0; 3], |normal, corner| {
+        let (a, b) = (
+            &points[face[corner]],
+            &points[face[(corner + 1) % face.len()]],
+        );
+        [
+            normal[0] + (a[1] - b[1]) * (a[2] + b[2]),
+            normal[1] + (a[2] - b[2]) * (a[0] + b[0]),
+            normal[2] + (a[0] - b[0]) * (a[1] + b[1]),
+        ]
+    })
+}
+
+/// Angle between two vectors, or zero if either vanishes.
+fn between(a: &Point, b: &Point) -> f64 {
+    let lengths = (dot(a, a) * dot(b, b)).sqrt();
+    if lengths > 0.0 {
+        (dot(a, b) / lengths).clamp(-1.0, 1.0).acos()
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,472 @@
+use super::{
+    Error, Metrics,
+    smooth::replace_coordinates,
+    vector::{Point, add, cross, distance, dot, norm, normalized, scale, sub},
+};
+use conspire::{
+    geometry::{
+        Coordinate,
+        mesh::{Connectivity, Mesh},
+    },
+    math::Tensor,
+};
+use std::{
+    array::from_fn,
+    collections::HashMap,
+    f64::consts::{FRAC_PI_3, SQRT_2},
+    time::Instant,
+};
+
+pub const IMPROVE_DEFAULT_ITERS: usize = 10;
+pub const IMPROVE_DEFAULT_TARGET: f64 = 0.2;
+
+/// Rounds of probing each node, halving the step after a round without gain.
+const PROBES: usize = 8;
+
+/// Gain in quality below which a move is not taken.
+const TOLERANCE: f64 = 1e-12;
+
+/// Options for untangling and improving the quality of a hexahedral or
+/// tetrahedral mesh by local optimization of the node positions.
+///
+/// Each node in turn is moved to maximize the worst quality of the elements
+/// around it, the quality of an element being the least of its minimum scaled
+/// Jacobian, the reciprocal of its maximum edge ratio, and one less its
+/// maximum skew, so inverted elements are untangled first. Only nodes with an
+/// element below the target quality are moved, and nodes on the exterior or on
+/// an interface between blocks stay on the original surface.
+///
+/// ```no_run
+/// use automesh::{ImproveOptions, read_mesh};
+///
+/// let mut mesh = read_mesh("smoothed.inp", true, false)?;
+/// ImproveOptions::default().iterations(20).improve(&mut mesh, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImproveOptions {
+    iterations: usize,
+    target: f64,
+}
+
+impl Default for ImproveOptions {
+    fn default() -> Self {
+        Self {
+            iterations: IMPROVE_DEFAULT_ITERS,
+            target: IMPROVE_DEFAULT_TARGET,
+        }
+    }
+}
+
+impl ImproveOptions {
+    /// Maximum number of passes over the nodes, stopping once nothing moves.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    /// Quality of an element below which the nodes around it are moved.
+    pub fn target(mut self, target: f64) -> Self {
+        self.target = target;
+        self
+    }
+    /// Improves a mesh in place.
+    pub fn improve(&self, mesh: &mut Mesh<3>, quiet: bool) -> Result<(), Error> {
+        let time = Instant::now();
+        let cells = cells(mesh)?;
+        crate::echo!(
+            quiet,
+            "   \x1b[1;96mImproving\x1b[0m with {} iterations \x1b[2m[{}]\x1b[0m",
+            self.iterations,
+            summary(&Metrics::from(&*mesh))
+        );
+        let surface = Surface::of(mesh);
+        let stars = mesh.node_element_connectivity().to_vec();
+        let neighbors = mesh.node_node_connectivity().to_vec();
+        let mut points: Vec<[f64; 3]> = mesh
+            .coordinates()
+            .iter()
+            .map(|coordinate| from_fn(|axis| coordinate[axis]))
+            .collect();
+        let mut iterations = 0;
+        while iterations < self.iterations {
+            iterations += 1;
+            let mut moved = false;
+            for node in 0..points.len() {
+                if !stars[node].is_empty() && !neighbors[node].is_empty() {
+                    moved |= optimize(
+                        node,
+                        &mut points,
+                        &stars[node],
+                        &neighbors[node],
+                        &cells,
+                        &surface,
+                        self.target,
+                    );
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+        replace_coordinates(mesh, points.into_iter().map(Coordinate::from).collect());
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{iterations} iterations, {}]\x1b[0m",
+            time.elapsed(),
+            summary(&Metrics::from(&*mesh))
+        );
+        Ok(())
+    }
+}
+
+/// An element of a mesh being improved.
+#[derive(Clone, Copy)]
+enum Cell {
+    Hexahedron([usize; 8]),
+    Tetrahedron([usize; 4]),
+}
+
+/// Lists the elements over all blocks in order.
+fn cells(mesh: &Mesh<3>) -> Result<Vec<Cell>, Error> {
+    let mut cells = Vec::with_capacity(mesh.number_of_elements());
+    for connectivity in mesh.connectivities() {
+        match connectivity {
+            Connectivity::Hexahedral(elements) => {
+                cells.extend(elements.iter().map(|&nodes| Cell::Hexahedron(nodes)))
+            }
+            Connectivity::Tetrahedral(elements) => {
+                cells.extend(elements.iter().map(|&nodes| Cell::Tetrahedron(nodes)))
+            }
+            _ => {
+                return Err(Error::InvalidData(
+                    "Improvement requires a hexahedral or tetrahedral mesh".into(),
+                ));
+            }
+        }
+    }
+    Ok(cells)
+}
+
+fn summary(metrics: &Metrics) -> String {
+    let inverted = metrics
+        .minimum_scaled_jacobians
+        .iter()
+        .filter(|&&jacobian| jacobian <= 0.0)
+        .count();
+    let minimum = metrics
+        .minimum_scaled_jacobians
+        .iter()
+        .copied()
+        .fold(f64::INFINITY, f64::min);
+    format!("{inverted} inverted, minimum scaled Jacobian {minimum:.4}")
+}
+
+/// Moves a node with an element below the target quality to improve the
+/// elements around it, returning whether it moved.
+fn optimize(
+    node: usize,
+    points: &mut [[f64; 3]],
+    star: &[usize],
+    neighbors: &[usize],
+    cells: &[Cell],
+    surface: &Surface,
+    target: f64,
+) -> bool {
+    let origin = points[node];
+    let mut best = quality(star, cells, points);
+    if best.0 >= target {
+        return false;
+    }
+    let mut attempt = |points: &mut [[f64; 3]], candidate: [f64; 3]| {
+        let candidate = surface.project(node, candidate);
+        if candidate.iter().any(|value| !value.is_finite()) {
+            return false;
+        }
+        let previous = points[node];
+        points[node] = candidate;
+        let trial = quality(star, cells, points);
+        if trial.0 > best.0 + TOLERANCE
+            || (trial.0 > best.0 - TOLERANCE && trial.1 > best.1 + TOLERANCE)
+        {
+            best = trial;
+            true
+        } else {
+            points[node] = previous;
+            false
+        }
+    };
+    let count = neighbors.len() as f64;
+    let centroid: [f64; 3] = from_fn(|axis| {
+        neighbors
+            .iter()
+            .map(|&other| points[other][axis])
+            .sum::<f64>()
+            / count
+    });
+    attempt(points, centroid);
+    let mut step = 0.25
+        * neighbors
+            .iter()
+            .map(|&other| distance(&points[other], &origin))
+            .sum::<f64>()
+        / count;
+    for _ in 0..PROBES {
+        let mut improved = false;
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut candidate = points[node];
+                candidate[axis] += sign * step;
+                improved |= attempt(points, candidate);
+            }
+        }
+        if !improved {
+            step *= 0.5;
+        }
+    }
+    points[node] != origin
+}
+
+/// Worst and mean quality of the elements of a star.
+fn quality(star: &[usize], cells: &[Cell], points: &[[f64; 3]]) -> (f64, f64) {
+    let (worst, total) = star
+        .iter()
+        .fold((f64::INFINITY, 0.0), |(worst, total), &element| {
+            let quality = cells[element].quality(points);
+            (worst.min(quality), total + quality)
+        });
+    (worst, total / star.len() as f64)
+}
+
+impl Cell {
+    /// Least of the minimum scaled Jacobian, the reciprocal of the maximum edge
+    /// ratio, and one less the maximum skew, defined as conspire's metrics.
+    fn quality(self, points: &[[f64; 3]]) -> f64 {
+        let (jacobian, ratio, skew) = match self {
+            Self::Hexahedron(nodes) => {
+                let p = nodes.map(|node| points[node]);
+                let axis = |pairs: [[usize; 2]; 4]| {
+                    pairs
+                        .iter()
+                        .fold([0.0; 3], |axis, &[a, b]| add(&axis, &sub(&p[a], &p[b])))
+                };
+                let axes = [
+                    axis([[1, 0], [2, 3], [5, 4], [6, 7]]),
+                    axis([[3, 0], [2, 1], [7, 4], [6, 5]]),
+                    axis([[4, 0], [5, 1], [6, 2], [7, 3]]),
+                ];
+                let skew = [(0, 1), (0, 2), (1, 2)]
+                    .map(|(u, v)| {
+                        let (nu, nv) = (norm(&axes[u]), norm(&axes[v]));
+                        if nu > 0.0 && nv > 0.0 {
+                            (dot(&axes[u], &axes[v]) / (nu * nv)).abs()
+                        } else {
+                            0.0
+                        }
+                    })
+                    .into_iter()
+                    .fold(f64::NEG_INFINITY, f64::max);
+                (
+                    scaled_jacobian(&p, &HEXAHEDRON_CORNERS, 1.0),
+                    edge_ratio(&p, &HEXAHEDRON_EDGES),
+                    skew,
+                )
+            }
+            Self::Tetrahedron(nodes) => {
+                let p = nodes.map(|node| points[node]);
+                let skew = [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]]
+                    .map(|face| triangle_skew(face.map(|node| p[node])))
+                    .into_iter()
+                    .fold(f64::NEG_INFINITY, f64::max);
+                (
+                    scaled_jacobian(&p, &TETRAHEDRON_CORNERS, SQRT_2),
+                    edge_ratio(&p, &TETRAHEDRON_EDGES),
+                    skew,
+                )
+            }
+        };
+        jacobian.min(1.0 / ratio).min(1.0 - skew)
+    }
+}
+
+/// Nodes at the ends of the three edges from each corner of a hexahedron.
+const HEXAHEDRON_CORNERS: [[usize; 3]; 8] = [
+    [1, 3, 4],
+    [2, 0, 5],
+    [3, 1, 6],
+    [0, 2, 7],
+    [7, 5, 0],
+    [4, 6, 1],
+    [5, 7, 2],
+    [6, 4, 3],
+];
+
+const HEXAHEDRON_EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [1, 2],
+    [2, 3],
+    [3, 0],
+    [4, 5],
+    [5, 6],
+    [6, 7],
+    [7, 4],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+/// Nodes at the ends of the three edges from each corner of a tetrahedron.
+const TETRAHEDRON_CORNERS: [[usize; 3]; 4] = [[1, 2, 3], [0, 3, 2], [0, 1, 3], [0, 2, 1]];
+
+const TETRAHEDRON_EDGES: [[usize; 2]; 6] = [[0, 1], [1, 2], [2, 0], [0, 3], [1, 3], [2, 3]];
+
+/// Least over the corners of the Jacobian scaled by the lengths of its edges.
+fn scaled_jacobian(p: &[Point], corners: &[[usize; 3]], factor: f64) -> f64 {
+    corners
+        .iter()
+        .enumerate()
+        .map(|(corner, ends)| {
+            let [a, b, c] = ends.map(|end| sub(&p[end], &p[corner]));
+            let lengths = norm(&a) * norm(&b) * norm(&c);
+            if lengths > 0.0 {
+                (factor * dot(&a, &cross(&b, &c)) / lengths).clamp(-1.0, 1.0)
+            } else {
+                0.0
+            }
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Longest over shortest edge, which is infinite for a collapsed edge.
+fn edge_ratio(p: &[Point], edges: &[[usize; 2]]) -> f64 {
+    let lengths = edges.iter().map(|&[a, b]| distance(&p[a], &p[b]));
+    let shortest = lengths.clone().fold(f64::INFINITY, f64::min);
+    let longest = lengths.fold(0.0, f64::max);
+    if shortest > 0.0 {
+        longest / shortest
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// Shortfall of the smallest angle of a triangle from sixty degrees, relative
+/// to sixty degrees.
+fn triangle_skew([a, b, c]: [Point; 3]) -> f64 {
+    let l0 = normalized(&sub(&c, &b));
+    let l1 = normalized(&sub(&a, &c));
+    let l2 = normalized(&sub(&b, &a));
+    let smallest = [(l0, l1), (l1, l2), (l2, l0)]
+        .map(|(u, v)| (-dot(&u, &v)).acos())
+        .into_iter()
+        .fold(f64::INFINITY, f64::min);
+    (FRAC_PI_3 - smallest) / FRAC_PI_3
+}
+
+/// The original exterior and interface faces of a mesh, as triangles.
+struct Surface {
+    triangles: Vec<[[f64; 3]; 3]>,
+    patches: Vec<Vec<usize>>,
+}
+
+impl Surface {
+    /// Finds the faces of only one element, or of two elements in different
+    /// blocks, and for each node on them the nearby triangles to project onto.
+    fn of(mesh: &Mesh<3>) -> Self {
+        let coordinates = mesh.coordinates();
+        let point = |node: usize| -> [f64; 3] { from_fn(|axis| coordinates[node][axis]) };
+        let mut faces: HashMap<Vec<usize>, (Vec<usize>, Vec<usize>)> = HashMap::new();
+        for (block, connectivity) in mesh.connectivities().iter().enumerate() {
+            for nodes in connectivity.iter() {
+                for face in connectivity.element_faces(nodes) {
+                    let mut key = face.clone();
+                    key.sort_unstable();
+                    faces
+                        .entry(key)
+                        .or_insert_with(|| (face, Vec::new()))
+                        .1
+                        .push(block);
+                }
+            }
+        }
+        let mut triangles = Vec::new();
+        let mut node_triangles = vec![Vec::new(); mesh.number_of_nodes()];
+        faces
+            .into_values()
+            .filter(|(_, blocks)| match blocks[..] {
+                [_] => true,
+                [a, b] => a != b,
+                _ => false,
+            })
+            .for_each(|(face, _)| {
+                (1..face.len() - 1).for_each(|corner| {
+                    let triangle = [face[0], face[corner], face[corner + 1]];
+                    triangle
+                        .iter()
+                        .for_each(|&node| node_triangles[node].push(triangles.len()));
+                    triangles.push(triangle.map(point));
+                })
+            });
+        let patches = node_triangles
+            .iter()
+            .enumerate()
+            .map(|(node, own)| {
+                if own.is_empty() {
+                    return Vec::new();
+                }
+                let mut patch: Vec<usize> = mesh.node_node_connectivity()[node]
+                    .iter()
+                    .flat_map(|&other| node_triangles[other].iter().copied())
+                    .chain(own.iter().copied())
+                    .collect();
+                patch.sort_unstable();
+                patch.dedup();
+                patch
+            })
+            .collect();
+        Self { triangles, patches }
+    }
+    /// Projects a point onto the original surface near a node, if it is on it.
+    fn project(&self, node: usize, point: [f64; 3]) -> [f64; 3] {
+        self.patches[node]
+            .iter()
+            .map(|&triangle| closest(&point, &self.triangles[triangle]))
+            .min_by(|a, b| distance(a, &point).total_cmp(&distance(b, &point)))
+            .unwrap_or(point)
+    }
+}
+
+/// Closest point on a triangle, by its Voronoi regions.
+pub(crate) fn closest(point: &Point, [a, b, c]: &[Point; 3]) -> Point {
+    let along = |origin: &Point, direction: &Point, t: f64| add(origin, &scale(direction, t));
+    let ab = sub(b, a);
+    let ac = sub(c, a);
+    let ap = sub(point, a);
+    let (d1, d2) = (dot(&ab, &ap), dot(&ac, &ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return *a;
+    }
+    let bp = sub(point, b);
+    let (d3, d4) = (dot(&ab, &bp), dot(&ac, &bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return *b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return along(a, &ab, d1 / (d1 - d3));
+    }
+    let cp = sub(point, c);
+    let (d5, d6) = (dot(&ab, &cp), dot(&ac, &cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return *c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return along(a, &ac, d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return along(b, &sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = va + vb + vc;
+    along(&along(a, &ab, vb / denominator), &ac, vc / denominator)
+}
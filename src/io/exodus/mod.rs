@@ -0,0 +1,623 @@
+use super::Error;
+use std::fs::{read as read_file, write as write_file};
+
+const MAGIC: [u8; 3] = *b"CDF";
+
+const DIMENSION: u32 = 10;
+const VARIABLE: u32 = 11;
+const ATTRIBUTE: u32 = 12;
+
+const CHAR: u32 = 2;
+const INT: u32 = 4;
+const DOUBLE: u32 = 6;
+
+/// Length of an Exodus name, including its terminating null.
+const NAME_LENGTH: usize = 33;
+
+/// Appends element variables to an Exodus file, as a single time step.
+///
+/// An Exodus file is a classic netCDF file (CDF-1, CDF-2, or CDF-5), so the
+/// header is rewritten with the dimensions and variables of the fields, the
+/// existing data is shifted past it, and the values are appended as a record.
+/// Each field has one value per element, in the order of the blocks, which
+/// have the given numbers of elements.
+pub fn append_element_variables(
+    file: &str,
+    fields: &[(&str, Vec<f64>)],
+    blocks: &[usize],
+) -> Result<(), Error> {
+    let invalid = |problem: &str| Error::InvalidData(format!("Exodus file {file} {problem}"));
+    let bytes = read_file(file)?;
+    let (mut header, start) = Header::read(&bytes).map_err(|problem| invalid(&problem))?;
+    if header.numrecs > 0
+        || header
+            .variables
+            .iter()
+            .any(|variable| header.is_record(variable))
+    {
+        return Err(invalid("already has time steps"));
+    }
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let elements: usize = blocks.iter().sum();
+    if let Some((name, _)) = fields.iter().find(|(_, values)| values.len() != elements) {
+        return Err(invalid(&format!(
+            "has {elements} elements, but not field {name}"
+        )));
+    }
+    let dimension = |header: &Header, name: &str| {
+        header
+            .dimensions
+            .iter()
+            .position(|(dimension, _)| dimension == name)
+    };
+    let number_of_blocks =
+        dimension(&header, "num_el_blk").ok_or_else(|| invalid("has no blocks"))?;
+    let in_block = (1..=blocks.len())
+        .map(|block| {
+            dimension(&header, &format!("num_el_in_blk{block}"))
+                .ok_or_else(|| invalid(&format!("has no block {block}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let time = dimension(&header, "time_step").unwrap_or_else(|| header.dimension("time_step", 0));
+    let variables = header.dimension("num_elem_var", fields.len() as u64);
+    let length = name_length(&mut header);
+    let names = padded_names(
+        fields.iter().map(|(name, _)| *name),
+        header.dimensions[length].1,
+    );
+    let table: Vec<u8> = (0..blocks.len() * fields.len())
+        .flat_map(|_| 1_i32.to_be_bytes())
+        .collect();
+    let fixed = [
+        header.variable("name_elem_var", CHAR, vec![variables, length], names),
+        header.variable(
+            "elem_var_tab",
+            INT,
+            vec![number_of_blocks, variables],
+            table,
+        ),
+    ];
+    let mut record = vec![header.variable(
+        "time_whole",
+        DOUBLE,
+        vec![time],
+        0.0_f64.to_be_bytes().to_vec(),
+    )];
+    fields.iter().enumerate().for_each(|(index, (_, values))| {
+        let mut elements = values.iter();
+        blocks
+            .iter()
+            .zip(&in_block)
+            .enumerate()
+            .for_each(|(block, (&count, &dimension))| {
+                let data = elements
+                    .by_ref()
+                    .take(count)
+                    .flat_map(|value| value.to_be_bytes())
+                    .collect();
+                record.push(header.variable(
+                    &format!("vals_elem_var{}eb{}", index + 1, block + 1),
+                    DOUBLE,
+                    vec![time, dimension],
+                    data,
+                ))
+            })
+    });
+    header.numrecs = 1;
+    Ok(write_file(
+        file,
+        extend(&bytes, header, start, fixed.to_vec(), record),
+    )?)
+}
+
+/// Appends the names of the node sets and side sets to an Exodus file, as
+/// `ns_names` and `ss_names`, leaving out those with no names.
+///
+/// Each name is of a set in the order of the sets the file has, with an empty
+/// name for an unnamed set.
+pub fn append_set_names(
+    file: &str,
+    node_sets: &[String],
+    side_sets: &[String],
+) -> Result<(), Error> {
+    let invalid = |problem: &str| Error::InvalidData(format!("Exodus file {file} {problem}"));
+    let bytes = read_file(file)?;
+    let (mut header, start) = Header::read(&bytes).map_err(|problem| invalid(&problem))?;
+    if header
+        .variables
+        .iter()
+        .any(|variable| header.is_record(variable))
+    {
+        return Err(invalid("already has time steps"));
+    }
+    let mut fixed = Vec::new();
+    for (names, count, variable) in [
+        (node_sets, "num_node_sets", "ns_names"),
+        (side_sets, "num_side_sets", "ss_names"),
+    ] {
+        if names.iter().all(String::is_empty) {
+            continue;
+        }
+        let sets = header
+            .dimensions
+            .iter()
+            .position(|(dimension, length)| dimension == count && *length == names.len() as u64)
+            .ok_or_else(|| invalid(&format!("has no {count} of {}", names.len())))?;
+        if header.variables.iter().any(|old| old.name == variable) {
+            return Err(invalid(&format!("already has {variable}")));
+        }
+        let length = name_length(&mut header);
+        let data = padded_names(
+            names.iter().map(String::as_str),
+            header.dimensions[length].1,
+        );
+        fixed.push(header.variable(variable, CHAR, vec![sets, length], data));
+    }
+    if fixed.is_empty() {
+        return Ok(());
+    }
+    Ok(write_file(
+        file,
+        extend(&bytes, header, start, fixed, Vec::new()),
+    )?)
+}
+
+/// The `len_name` dimension of a header, added if it has none.
+fn name_length(header: &mut Header) -> usize {
+    header
+        .dimensions
+        .iter()
+        .position(|(dimension, _)| dimension == "len_name")
+        .unwrap_or_else(|| header.dimension("len_name", NAME_LENGTH as u64))
+}
+
+/// Names as null-padded characters of a length, truncated to fit.
+fn padded_names<'a>(names: impl ExactSizeIterator<Item = &'a str>, length: u64) -> Vec<u8> {
+    let length = length as usize;
+    let mut bytes = vec![0_u8; names.len() * length];
+    names.enumerate().for_each(|(index, name)| {
+        let name = &name.as_bytes()[..name.len().min(length - 1)];
+        bytes[index * length..][..name.len()].copy_from_slice(name)
+    });
+    bytes
+}
+
+/// The bytes of a file with variables added to its header, its data shifted
+/// past the header, and the data of the new fixed-size and record variables
+/// appended after its own.
+fn extend(
+    bytes: &[u8],
+    mut header: Header,
+    start: usize,
+    mut fixed: Vec<(Variable, Vec<u8>)>,
+    mut record: Vec<(Variable, Vec<u8>)>,
+) -> Vec<u8> {
+    let end = header
+        .variables
+        .iter()
+        .map(|variable| variable.begin + variable.size)
+        .max()
+        .unwrap_or(start as u64);
+    let begin = header
+        .variables
+        .iter()
+        .map(|variable| variable.begin)
+        .min()
+        .unwrap_or(start as u64);
+    let old = header.variables.len();
+    header.variables.extend(
+        fixed
+            .iter()
+            .chain(&record)
+            .map(|(variable, _)| variable.clone()),
+    );
+    let size = header.write().len() as u64;
+    let shift = size.saturating_sub(begin).next_multiple_of(8);
+    header.variables[..old]
+        .iter_mut()
+        .for_each(|variable| variable.begin += shift);
+    let mut offset = end + shift;
+    header.variables[old..]
+        .iter_mut()
+        .zip(fixed.iter_mut().chain(record.iter_mut()))
+        .for_each(|(variable, (_, data))| {
+            variable.begin = offset;
+            data.resize(variable.size as usize, 0);
+            offset += variable.size;
+        });
+    let mut output = header.write();
+    output.resize((begin + shift) as usize, 0);
+    let old_data = &bytes[(begin as usize).min(bytes.len())..];
+    output.extend_from_slice(old_data);
+    output.resize((end + shift) as usize, 0);
+    fixed
+        .iter()
+        .chain(&record)
+        .for_each(|(_, data)| output.extend_from_slice(data));
+    output
+}
+
+#[derive(Clone)]
+struct Attribute {
+    name: String,
+    kind: u32,
+    count: u64,
+    values: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Variable {
+    name: String,
+    dimensions: Vec<usize>,
+    attributes: Vec<Attribute>,
+    kind: u32,
+    size: u64,
+    begin: u64,
+}
+
+struct Header {
+    version: u8,
+    numrecs: u64,
+    dimensions: Vec<(String, u64)>,
+    attributes: Vec<Attribute>,
+    variables: Vec<Variable>,
+}
+
+impl Header {
+    /// Reads the header of a classic netCDF file, and where it ends.
+    fn read(bytes: &[u8]) -> Result<(Self, usize), String> {
+        if bytes.len() < 4 || bytes[..3] != MAGIC || ![1, 2, 5].contains(&bytes[3]) {
+            return Err("is not a classic netCDF file".into());
+        }
+        let mut reader = Reader {
+            bytes,
+            at: 4,
+            version: bytes[3],
+        };
+        let numrecs = match reader.version {
+            5 => reader.u64()?,
+            _ => reader.u32()? as u64,
+        };
+        let dimensions = reader.list(DIMENSION, |reader| Ok((reader.name()?, reader.count()?)))?;
+        let attributes = reader.attributes()?;
+        let variables = reader.list(VARIABLE, |reader| {
+            let name = reader.name()?;
+            let rank = reader.count()?;
+            let dimensions = (0..rank)
+                .map(|_| reader.count().map(|dimension| dimension as usize))
+                .collect::<Result<_, _>>()?;
+            let attributes = reader.attributes()?;
+            let kind = reader.u32()?;
+            let size = reader.count()?;
+            let begin = match reader.version {
+                1 => reader.u32()? as u64,
+                _ => reader.u64()?,
+            };
+            Ok(Variable {
+                name,
+                dimensions,
+                attributes,
+                kind,
+                size,
+                begin,
+            })
+        })?;
+        Ok((
+            Self {
+                version: reader.version,
+                numrecs,
+                dimensions,
+                attributes,
+                variables,
+            },
+            reader.at,
+        ))
+    }
+    fn write(&self) -> Vec<u8> {
+        let mut writer = Writer {
+            bytes: MAGIC.to_vec(),
+            version: self.version,
+        };
+        writer.bytes.push(self.version);
+        match self.version {
+            5 => writer.u64(self.numrecs),
+            _ => writer.u32(self.numrecs as u32),
+        }
+        writer.list(DIMENSION, &self.dimensions, |writer, (name, length)| {
+            writer.name(name);
+            writer.count(*length)
+        });
+        writer.attributes(&self.attributes);
+        writer.list(VARIABLE, &self.variables, |writer, variable| {
+            writer.name(&variable.name);
+            writer.count(variable.dimensions.len() as u64);
+            variable
+                .dimensions
+                .iter()
+                .for_each(|&dimension| writer.count(dimension as u64));
+            writer.attributes(&variable.attributes);
+            writer.u32(variable.kind);
+            writer.count(variable.size);
+            match self.version {
+                1 => writer.u32(variable.begin as u32),
+                _ => writer.u64(variable.begin),
+            }
+        });
+        writer.bytes
+    }
+    /// Adds a dimension, returning its index.
+    fn dimension(&mut self, name: &str, length: u64) -> usize {
+        self.dimensions.push((name.to_string(), length));
+        self.dimensions.len() - 1
+    }
+    /// A new variable over dimensions, with its data, yet to be placed.
+    fn variable(
+        &self,
+        name: &str,
+        kind: u32,
+        dimensions: Vec<usize>,
+        data: Vec<u8>,
+    ) -> (Variable, Vec<u8>) {
+        let size = dimensions
+            .iter()
+            .enumerate()
+            .filter(|&(index, &dimension)| index > 0 || self.dimensions[dimension].1 > 0)
+            .map(|(_, &dimension)| self.dimensions[dimension].1)
+            .product::<u64>()
+            * size_of_kind(kind);
+        (
+            Variable {
+                name: name.to_string(),
+                dimensions,
+                attributes: Vec::new(),
+                kind,
+                size: size.next_multiple_of(4),
+                begin: 0,
+            },
+            data,
+        )
+    }
+    fn is_record(&self, variable: &Variable) -> bool {
+        variable
+            .dimensions
+            .first()
+            .is_some_and(|&dimension| self.dimensions[dimension].1 == 0)
+    }
+}
+
+fn size_of_kind(kind: u32) -> u64 {
+    match kind {
+        3 | 8 => 2,
+        4 | 5 | 9 => 4,
+        6 | 10 | 11 => 8,
+        _ => 1,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    version: u8,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.at..self.at + length)
+            .ok_or("has a truncated header")?;
+        self.at += length;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn count(&mut self) -> Result<u64, String> {
+        match self.version {
+            5 => self.u64(),
+            _ => self.u32().map(u64::from),
+        }
+    }
+    fn padded(&mut self, length: usize) -> Result<Vec<u8>, String> {
+        let bytes = self.take(length)?.to_vec();
+        self.take(length.next_multiple_of(4) - length)?;
+        Ok(bytes)
+    }
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.count()? as usize;
+        String::from_utf8(self.padded(length)?).map_err(|_| "has an invalid name".into())
+    }
+    fn list<T>(
+        &mut self,
+        tag: u32,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let found = self.u32()?;
+        let count = self.count()?;
+        if found != tag && !(found == 0 && count == 0) {
+            return Err("has an invalid header".into());
+        }
+        (0..count).map(|_| item(self)).collect()
+    }
+    fn attributes(&mut self) -> Result<Vec<Attribute>, String> {
+        self.list(ATTRIBUTE, |reader| {
+            let name = reader.name()?;
+            let kind = reader.u32()?;
+            let count = reader.count()?;
+            let values = reader.padded((count * size_of_kind(kind)) as usize)?;
+            Ok(Attribute {
+                name,
+                kind,
+                count,
+                values,
+            })
+        })
+    }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    version: u8,
+}
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_be_bytes())
+    }
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_be_bytes())
+    }
+    fn count(&mut self, value: u64) {
+        match self.version {
+            5 => self.u64(value),
+            _ => self.u32(value as u32),
+        }
+    }
+    fn padded(&mut self, bytes: &[u8]) {
+        self.bytes.extend(bytes);
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0)
+    }
+    fn name(&mut self, name: &str) {
+        self.count(name.len() as u64);
+        self.padded(name.as_bytes())
+    }
+    fn list<T>(&mut self, tag: u32, items: &[T], mut item: impl FnMut(&mut Self, &T)) {
+        self.u32(if items.is_empty() { 0 } else { tag });
+        self.count(items.len() as u64);
+        items.iter().for_each(|entry| item(self, entry))
+    }
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        self.list(ATTRIBUTE, attributes, |writer, attribute| {
+            writer.name(&attribute.name);
+            writer.u32(attribute.kind);
+            writer.count(attribute.count);
+            writer.padded(&attribute.values)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CDF-1 file with one block of two elements and its block ID, written
+    /// byte by byte from the netCDF specification.
+    fn minimal() -> Vec<u8> {
+        let mut bytes = b"CDF\x01".to_vec();
+        let words: [&[u8]; 21] = [
+            &[0; 4],
+            &DIMENSION.to_be_bytes(),
+            &3_u32.to_be_bytes(),
+            &9_u32.to_be_bytes(),
+            b"time_step\0\0\0",
+            &0_u32.to_be_bytes(),
+            &10_u32.to_be_bytes(),
+            b"num_el_blk\0\0",
+            &1_u32.to_be_bytes(),
+            &14_u32.to_be_bytes(),
+            b"num_el_in_blk1\0\0",
+            &2_u32.to_be_bytes(),
+            &[0; 8],
+            &VARIABLE.to_be_bytes(),
+            &1_u32.to_be_bytes(),
+            &8_u32.to_be_bytes(),
+            b"eb_prop1",
+            &[0, 0, 0, 1, 0, 0, 0, 1],
+            &[0; 8],
+            &INT.to_be_bytes(),
+            &4_u32.to_be_bytes(),
+        ];
+        words.iter().for_each(|word| bytes.extend(*word));
+        let begin = bytes.len() as u32 + 4;
+        bytes.extend(begin.to_be_bytes());
+        bytes.extend(7_i32.to_be_bytes());
+        bytes
+    }
+
+    fn values(bytes: &[u8], header: &Header, name: &str) -> Vec<u8> {
+        let variable = header
+            .variables
+            .iter()
+            .find(|variable| variable.name == name)
+            .unwrap();
+        bytes[variable.begin as usize..][..variable.size as usize].to_vec()
+    }
+
+    #[test]
+    fn append_element_variables_keeps_data_and_adds_a_record() {
+        let file = std::env::temp_dir().join(format!("automesh_exodus_{}.exo", std::process::id()));
+        let file = file.to_str().unwrap();
+        write_file(file, minimal()).unwrap();
+        append_element_variables(file, &[("volume", vec![1.5, 2.5])], &[2]).unwrap();
+        let bytes = read_file(file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        let (header, end) = Header::read(&bytes).unwrap();
+        assert_eq!(header.numrecs, 1);
+        assert_eq!(values(&bytes, &header, "eb_prop1"), 7_i32.to_be_bytes());
+        assert_eq!(&values(&bytes, &header, "name_elem_var")[..7], b"volume\0");
+        assert_eq!(values(&bytes, &header, "elem_var_tab"), 1_i32.to_be_bytes());
+        assert_eq!(values(&bytes, &header, "time_whole"), 0.0_f64.to_be_bytes());
+        assert_eq!(
+            values(&bytes, &header, "vals_elem_var1eb1"),
+            [1.5_f64.to_be_bytes(), 2.5_f64.to_be_bytes()].concat()
+        );
+        let time = &header.variables[header.variables.len() - 2];
+        let last = &header.variables[header.variables.len() - 1];
+        assert!(header.is_record(time) && header.is_record(last));
+        assert_eq!(time.begin + time.size, last.begin);
+        assert_eq!(last.begin + last.size, bytes.len() as u64);
+        assert!(
+            header
+                .variables
+                .iter()
+                .all(|variable| variable.begin >= end as u64)
+        );
+    }
+
+    #[test]
+    fn append_set_names_before_element_variables() {
+        let (mut header, start) = Header::read(&minimal()).unwrap();
+        header.dimension("num_side_sets", 2);
+        let bytes = extend(&minimal(), header, start, Vec::new(), Vec::new());
+        let file = std::env::temp_dir().join(format!("automesh_sets_{}.exo", std::process::id()));
+        let file = file.to_str().unwrap();
+        write_file(file, bytes).unwrap();
+        let names = ["bottom".to_string(), String::new()];
+        assert!(append_set_names(file, &[], &names[..1]).is_err());
+        append_set_names(file, &[], &names).unwrap();
+        append_element_variables(file, &[("volume", vec![1.5, 2.5])], &[2]).unwrap();
+        let bytes = read_file(file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        let (header, _) = Header::read(&bytes).unwrap();
+        assert!(
+            header
+                .variables
+                .iter()
+                .all(|variable| variable.name != "ns_names")
+        );
+        assert_eq!(values(&bytes, &header, "eb_prop1"), 7_i32.to_be_bytes());
+        let ss_names = values(&bytes, &header, "ss_names");
+        assert_eq!(ss_names.len(), (2 * NAME_LENGTH).next_multiple_of(4));
+        assert_eq!(&ss_names[..7], b"bottom\0");
+        assert!(ss_names[NAME_LENGTH..].iter().all(|&byte| byte == 0));
+        assert_eq!(&values(&bytes, &header, "name_elem_var")[..7], b"volume\0");
+    }
+
+    #[test]
+    fn header_round_trips_as_cdf5() {
+        let (mut header, _) = Header::read(&minimal()).unwrap();
+        header.version = 5;
+        let bytes = header.write();
+        let (read, end) = Header::read(&bytes).unwrap();
+        assert_eq!(end, bytes.len());
+        assert_eq!(read.dimensions, header.dimensions);
+        assert_eq!(read.variables[0].name, "eb_prop1");
+        assert_eq!(read.variables[0].dimensions, [1]);
+        assert_eq!(read.variables[0].begin, header.variables[0].begin);
+    }
+}
@@ -1,31 +1,110 @@
-use super::ErrorWrapper;
+mod exodus;
+mod nifti;
+mod tiff;
+
+use super::{Error, SetNames};
 use conspire::{
     geometry::{
         grid::{Input as GridInput, Output as GridOutput, Voxels},
         mesh::{Input as MeshInput, Mesh, Output as MeshOutput, Stl, Tessellation, Vtk},
     },
-    io::{Write, write::Compression},
+    io::{
+        Npy, NpyType, Write,
+        read::{data_arrays, encoding, find_data_array, floats, tag},
+        write::{Compression, data_array},
+    },
+};
+use std::{
+    fmt::Display,
+    fs::{File, read_dir},
+    io::{BufWriter, Read, Write as _},
+    iter::empty,
+    mem::replace,
+    path::{Path, PathBuf},
+    time::Instant,
 };
-use std::{path::Path, time::Instant};
+
+/// Integer type of segmentation labels (material IDs).
+///
+/// Labels are 16-bit, so a segmentation has at most 65536 materials. Wider
+/// labels (from a 32- or 64-bit npy, an spn, a 32-bit tif, or a nii) are read
+/// as such, but one above 65535 is an error rather than wrapping around, and
+/// such a segmentation needs relabeling before it can be read.
+pub type Label = u16;
 
 pub fn extension(file: &str) -> Option<&str> {
     Path::new(file).extension().and_then(|ext| ext.to_str())
 }
 
-pub fn invalid_input(file: &str, extension: Option<&str>) -> ErrorWrapper {
-    ErrorWrapper::from(format!(
-        "Invalid extension .{} from input file {}",
-        extension.unwrap_or("UNDEFINED"),
-        file
-    ))
+/// Whether the file name of a path is a pattern using `*` or `?`.
+pub(crate) fn is_pattern(file: &str) -> bool {
+    Path::new(file)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains(['*', '?']))
+}
+
+/// Lists the files matching a pattern in their file name, in natural order.
+pub(crate) fn glob(pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut files: Vec<PathBuf> = read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+        .filter(|file| {
+            file.to_str()
+                .is_some_and(|file| tiff::matches(name.as_bytes(), file.as_bytes()))
+        })
+        .map(|file| path.with_file_name(file))
+        .collect();
+    files.sort_by(|a, b| tiff::natural(&a.to_string_lossy(), &b.to_string_lossy()));
+    Ok(files)
+}
+
+/// Voxel spacing and origin that place a segmentation in space.
+#[derive(Clone, Copy, Debug)]
+pub struct Embedding {
+    pub scale: [f64; 3],
+    pub translate: [f64; 3],
+}
+
+impl Default for Embedding {
+    fn default() -> Self {
+        Self {
+            scale: [1.0; 3],
+            translate: [0.0; 3],
+        }
+    }
+}
+
+/// Whether an input names a segmentation (nii | npy | spn | tif) or a directory of tif slices.
+pub fn is_segmentation(file: &str) -> bool {
+    Path::new(file).is_dir()
+        || nifti::is_nifti(file)
+        || matches!(
+            extension(file),
+            Some("npy") | Some("spn") | Some("tif") | Some("tiff")
+        )
 }
 
-pub fn invalid_output(file: &str, extension: Option<&str>) -> ErrorWrapper {
-    ErrorWrapper::from(format!(
-        "Invalid extension .{} from output file {}",
-        extension.unwrap_or("UNDEFINED"),
-        file
-    ))
+pub fn invalid_input(file: &str, extension: Option<&str>) -> Error {
+    Error::InvalidInput {
+        file: file.to_string(),
+        extension: extension.map(String::from),
+    }
+}
+
+pub fn invalid_output(file: &str, extension: Option<&str>) -> Error {
+    Error::InvalidOutput {
+        file: file.to_string(),
+        extension: extension.map(String::from),
+    }
 }
 
 pub fn title(quiet: bool) {
@@ -48,7 +127,7 @@ fn done(time: Instant, quiet: bool) {
 }
 
 /// Reads a finite element mesh (exo | inp | mesh | stl | vtu) into a conspire mesh.
-pub fn read_mesh(file: &str, quiet: bool, show_title: bool) -> Result<Mesh<3>, ErrorWrapper> {
+pub fn read_mesh(file: &str, quiet: bool, show_title: bool) -> Result<Mesh<3>, Error> {
     if show_title {
         title(quiet);
     }
@@ -67,39 +146,216 @@ pub fn read_mesh(file: &str, quiet: bool, show_title: bool) -> Result<Mesh<3>, E
 }
 
 /// Writes a conspire mesh to a finite element file (exo | inp | mesh | vtu | stl).
-pub fn write_mesh(file: &str, mesh: Mesh<3>, quiet: bool) -> Result<(), ErrorWrapper> {
+pub fn write_mesh(file: &str, mut mesh: Mesh<3>, quiet: bool) -> Result<(), Error> {
+    write_mesh_borrowed(file, &mut mesh, &SetNames::default(), quiet)
+}
+
+/// Writes a mesh like [`write_mesh`], giving its sets names (exo | inp).
+pub fn write_mesh_named(
+    file: &str,
+    mut mesh: Mesh<3>,
+    names: &SetNames,
+    quiet: bool,
+) -> Result<(), Error> {
+    write_mesh_borrowed(file, &mut mesh, names, quiet)
+}
+
+/// Writes a mesh like [`write_mesh`], but leaves it with the caller.
+///
+/// An stl output needs the mesh by value, so it is moved out for the write and
+/// then moved back, whether or not the write succeeded.
+pub(crate) fn write_mesh_borrowed(
+    file: &str,
+    mesh: &mut Mesh<3>,
+    names: &SetNames,
+    quiet: bool,
+) -> Result<(), Error> {
     crate::echo!(quiet, "     \x1b[1;96mWriting\x1b[0m {file}");
     let time = Instant::now();
     let extension = extension(file);
     match extension {
-        Some("inp") => mesh.write(MeshOutput::Abaqus(file))?,
-        Some("exo") => mesh.write(MeshOutput::Exodus(file))?,
+        Some("inp") => {
+            mesh.write(MeshOutput::Abaqus(file))?;
+            name_sets(file, mesh, names)?
+        }
+        Some("exo") => {
+            mesh.write(MeshOutput::Exodus(file))?;
+            name_sets(file, mesh, names)?
+        }
         Some("mesh") => mesh.write(MeshOutput::Medit(file))?,
         Some("vtu") => mesh.write(MeshOutput::Vtk(Vtk::UnstructuredGrid(Compression::Off(
             file,
         ))))?,
-        Some("stl") => Tessellation::from(mesh).write(Stl::Binary(file))?,
+        Some("stl") => {
+            let empty = Mesh::from((Vec::new(), empty().collect()));
+            let tessellation = Tessellation::from(replace(mesh, empty));
+            let written = tessellation.write(Stl::Binary(file));
+            *mesh = Mesh::from(tessellation);
+            written?
+        }
         _ => return Err(invalid_output(file, extension)),
-    } // Output::Vtk(Vtk::UnstructuredGrid(Compression::Off(path)))
+    }
     crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
     Ok(())
 }
 
+/// Gives the sets of a written mesh their names, in place of the numbered
+/// `NSET1`, `SURF1`, and so on (inp), or as `ns_names` and `ss_names` (exo).
+fn name_sets(file: &str, mesh: &Mesh<3>, names: &SetNames) -> Result<(), Error> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let mut names = names.clone();
+    names.fit(mesh);
+    match extension(file) {
+        Some("exo") => exodus::append_set_names(file, names.node_sets(), names.side_sets()),
+        _ => {
+            let named = |names: &[String], number: &str| {
+                number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| names.get(number.wrapping_sub(1)))
+                    .filter(|name| !name.is_empty())
+                    .cloned()
+            };
+            let inp = std::fs::read_to_string(file)?;
+            let mut output = String::with_capacity(inp.len());
+            inp.lines().for_each(|line| {
+                let renamed = if let Some(number) = line.strip_prefix("*Nset, nset=NSET") {
+                    named(names.node_sets(), number).map(|name| format!("*Nset, nset={name}"))
+                } else if let Some(number) = line.strip_prefix("*Surface, type=ELEMENT, name=SURF")
+                {
+                    named(names.side_sets(), number)
+                        .map(|name| format!("*Surface, type=ELEMENT, name={name}"))
+                } else {
+                    None
+                };
+                output.push_str(renamed.as_deref().unwrap_or(line));
+                output.push('\n')
+            });
+            Ok(std::fs::write(file, output)?)
+        }
+    }
+}
+
+/// Writes a mesh with fields of one value per element, in element order, as
+/// cell data alongside the block ID (vtu) or as element variables (exo).
+pub(crate) fn write_mesh_fields(
+    file: &str,
+    mesh: &Mesh<3>,
+    names: &SetNames,
+    fields: &[(&str, Vec<f64>)],
+) -> Result<(), Error> {
+    let extension = extension(file);
+    match extension {
+        Some("exo") => {
+            mesh.write(MeshOutput::Exodus(file))?;
+            name_sets(file, mesh, names)?;
+            let blocks: Vec<usize> = mesh
+                .iter()
+                .map(|block| block.number_of_elements())
+                .collect();
+            exodus::append_element_variables(file, fields, &blocks)
+        }
+        Some("vtu") => {
+            mesh.write(MeshOutput::Vtk(Vtk::UnstructuredGrid(Compression::Off(
+                file,
+            ))))?;
+            let mut blocks = Vec::with_capacity(4 * mesh.number_of_elements());
+            mesh.iter().enumerate().for_each(|(block, connectivity)| {
+                let number = mesh.blocks().map_or(block + 1, |numbers| numbers[block]) as i32;
+                (0..connectivity.number_of_elements())
+                    .for_each(|_| blocks.extend(number.to_le_bytes()))
+            });
+            let mut cell_data = String::from("      <CellData>\n");
+            cell_data.push_str(&format!(
+                "        <DataArray type=\"Int32\" Name=\"block_id\" format=\"binary\">{}</DataArray>\n",
+                data_array(&blocks)
+            ));
+            cell_data.push_str(&float_arrays(fields));
+            cell_data.push_str("      </CellData>\n");
+            insert_before_points(file, &cell_data)
+        }
+        _ => Err(invalid_output(file, extension)),
+    }
+}
+
+/// Writes a mesh with fields of its nodes (vtu), alongside the node sets the
+/// writer already gives as point data.
+pub(crate) fn write_mesh_node_fields(
+    file: &str,
+    mesh: &Mesh<3>,
+    fields: &[(&str, Vec<f64>)],
+) -> Result<(), Error> {
+    let extension = extension(file);
+    match extension {
+        Some("vtu") => {
+            mesh.write(MeshOutput::Vtk(Vtk::UnstructuredGrid(Compression::Off(
+                file,
+            ))))?;
+            let arrays = float_arrays(fields);
+            let mut vtu = std::fs::read_to_string(file)?;
+            match vtu.find("      </PointData>") {
+                Some(end) => vtu.insert_str(end, &arrays),
+                None => {
+                    let points = vtu.find("      <Points>").ok_or_else(|| {
+                        Error::InvalidData(format!("VTU file {file} has no points"))
+                    })?;
+                    vtu.insert_str(
+                        points,
+                        &format!("      <PointData>\n{arrays}      </PointData>\n"),
+                    )
+                }
+            }
+            Ok(std::fs::write(file, vtu)?)
+        }
+        _ => Err(invalid_output(file, extension)),
+    }
+}
+
+fn float_arrays(fields: &[(&str, Vec<f64>)]) -> String {
+    fields
+        .iter()
+        .map(|(name, values)| {
+            let data: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+            format!(
+                "        <DataArray type=\"Float64\" Name=\"{name}\" format=\"binary\">{}</DataArray>\n",
+                data_array(&data)
+            )
+        })
+        .collect()
+}
+
+fn insert_before_points(file: &str, data: &str) -> Result<(), Error> {
+    let mut vtu = std::fs::read_to_string(file)?;
+    let points = vtu
+        .find("      <Points>")
+        .ok_or_else(|| Error::InvalidData(format!("VTU file {file} has no points")))?;
+    vtu.insert_str(points, data);
+    Ok(std::fs::write(file, vtu)?)
+}
+
 /// Resolves the voxels-per-direction needed to read an spn segmentation.
 pub fn nel(
     nelx: Option<usize>,
     nely: Option<usize>,
     nelz: Option<usize>,
-) -> Result<Vec<usize>, ErrorWrapper> {
+) -> Result<Vec<usize>, Error> {
     match (nelx, nely, nelz) {
         (Some(x), Some(y), Some(z)) => Ok(vec![x, y, z]),
-        _ => Err(ErrorWrapper::from(
-            "Need to specify nelx, nely, and nelz for an spn segmentation",
+        _ => Err(Error::InvalidOption(
+            "Need to specify nelx, nely, and nelz for an spn segmentation".into(),
         )),
     }
 }
 
-/// Reads a segmentation (npy | spn) into voxels.
+/// Reads a segmentation (nii | npy | spn | tif) into voxels.
+///
+/// A tif input may be a single (multi-page) file, a pattern such as
+/// `slice_*.tif`, or a directory, and is read as a stack of slices along z.
+/// A nii (or nii.gz) input also returns the embedding from its header.
+/// Labels of any integer width are read, but one outside of [`Label`] is an
+/// error rather than wrapping around.
 #[allow(clippy::too_many_arguments)]
 pub fn read_segmentation(
     file: &str,
@@ -108,18 +364,29 @@ pub fn read_segmentation(
     nelz: Option<usize>,
     quiet: bool,
     show_title: bool,
-) -> Result<Voxels<u8>, ErrorWrapper> {
+) -> Result<(Voxels<Label>, Option<Embedding>), Error> {
     if show_title {
         title(quiet);
     }
     let time = begin("Reading", file, quiet);
     let extension = extension(file);
+    let mut embedding = None;
     let voxels = match extension {
-        Some("npy") => Voxels::<u8>::try_from(GridInput::Npy(file))?,
-        Some("spn") => Voxels::<u8>::try_from(GridInput::Spn(file, nel(nelx, nely, nelz)?))?,
+        _ if Path::new(file).is_dir() => tiff::read_stack(file)?,
+        _ if nifti::is_nifti(file) => {
+            let (voxels, header) = nifti::read(file)?;
+            embedding = Some(header);
+            voxels
+        }
+        Some("npy") => read_npy(file)?,
+        Some("spn") => narrow(
+            &Voxels::<u32>::try_from(GridInput::Spn(file, nel(nelx, nely, nelz)?))?,
+            file,
+        )?,
+        Some("tif") | Some("tiff") => tiff::read_stack(file)?,
         _ => return Err(invalid_input(file, extension)),
     };
-    let mut materials = [false; u8::MAX as usize + 1];
+    let mut materials = vec![false; Label::MAX as usize + 1];
     voxels
         .data()
         .iter()
@@ -131,24 +398,227 @@ pub fn read_segmentation(
         "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{num_materials} materials, {num_voxels} voxels]\x1b[0m",
         time.elapsed()
     );
-    Ok(voxels)
+    Ok((voxels, embedding))
 }
 
-/// Writes a segmentation (npy | spn | vti).
+/// Writes a segmentation (nii | npy | spn | vti).
+///
+/// Only a nii (or nii.gz) output records the embedding, unit spacing at the
+/// origin if there is none. Labels are written 8-bit when they all fit, and
+/// 16-bit otherwise.
 pub fn write_segmentation(
     file: &str,
-    voxels: &Voxels<u8>,
+    voxels: &Voxels<Label>,
+    embedding: Option<&Embedding>,
     quiet: bool,
-) -> Result<(), ErrorWrapper> {
+) -> Result<(), Error> {
     crate::echo!(quiet, "     \x1b[1;96mWriting\x1b[0m {file}");
     let time = Instant::now();
     let extension = extension(file);
     match extension {
-        Some("npy") => voxels.write(GridOutput::Npy(file))?,
+        _ if nifti::is_nifti(file) => {
+            nifti::write(file, voxels, embedding.unwrap_or(&Embedding::default()))?
+        }
+        Some("npy") | Some("vti") if is_8_bit(voxels) => {
+            write_grid(file, &narrow::<Label, u8>(voxels, file)?)?
+        }
+        Some("npy") | Some("vti") => write_grid(file, voxels)?,
         Some("spn") => voxels.write(GridOutput::Spn(file))?,
-        Some("vti") => voxels.write(GridOutput::Vti(Compression::Off(file)))?,
         _ => return Err(invalid_output(file, extension)),
     }
     crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
     Ok(())
 }
+
+/// Writes per-voxel volume fractions, one channel per material (npy | vti).
+///
+/// An npy output has shape (nelx, nely, nelz, materials) with the channels in
+/// the given material order, and a vti output has one cell-data array per
+/// material, named `material_<ID>`, placed by the embedding.
+pub fn write_fractions(
+    file: &str,
+    nel: [usize; 3],
+    fractions: &[(usize, Vec<f64>)],
+    embedding: &Embedding,
+    quiet: bool,
+) -> Result<(), Error> {
+    let materials = fractions
+        .iter()
+        .map(|(material, _)| material.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    crate::echo!(
+        quiet,
+        "     \x1b[1;96mWriting\x1b[0m {file} \x1b[2m[materials {materials}]\x1b[0m"
+    );
+    let time = Instant::now();
+    let extension = extension(file);
+    match extension {
+        Some("npy") => Npy {
+            data: fractions
+                .iter()
+                .flat_map(|(_, fraction)| fraction.iter().copied())
+                .collect(),
+            shape: vec![nel[0], nel[1], nel[2], fractions.len()],
+            fortran_order: true,
+        }
+        .write(file)?,
+        Some("vti") => {
+            let extent = format!("0 {} 0 {} 0 {}", nel[0], nel[1], nel[2]);
+            let [x, y, z] = embedding.translate;
+            let [dx, dy, dz] = embedding.scale;
+            let mut vti = BufWriter::new(File::create(file)?);
+            writeln!(vti, "<?xml version=\"1.0\"?>")?;
+            writeln!(
+                vti,
+                "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt64\">"
+            )?;
+            writeln!(
+                vti,
+                "  <ImageData WholeExtent=\"{extent}\" Origin=\"{x} {y} {z}\" Spacing=\"{dx} {dy} {dz}\">"
+            )?;
+            writeln!(vti, "    <Piece Extent=\"{extent}\">")?;
+            writeln!(vti, "      <CellData>")?;
+            for (material, fraction) in fractions {
+                let mut data = Vec::with_capacity(8 * fraction.len());
+                fraction
+                    .iter()
+                    .for_each(|&value| data.extend(value.to_le_bytes()));
+                writeln!(
+                    vti,
+                    "        <DataArray type=\"Float64\" Name=\"material_{material}\" NumberOfComponents=\"1\" format=\"binary\">{}</DataArray>",
+                    data_array(&data)
+                )?;
+            }
+            writeln!(vti, "      </CellData>")?;
+            writeln!(vti, "    </Piece>")?;
+            writeln!(vti, "  </ImageData>")?;
+            writeln!(vti, "</VTKFile>")?;
+        }
+        _ => return Err(invalid_output(file, extension)),
+    }
+    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+    Ok(())
+}
+
+fn write_grid<T>(file: &str, voxels: &Voxels<T>) -> Result<(), Error>
+where
+    T: NpyType + Display,
+{
+    match extension(file) {
+        Some("npy") => voxels.write(GridOutput::Npy(file))?,
+        _ => voxels.write(GridOutput::Vti(Compression::Off(file)))?,
+    }
+    Ok(())
+}
+
+/// Whether every label of a segmentation fits in 8 bits.
+pub fn is_8_bit(voxels: &Voxels<Label>) -> bool {
+    voxels.data().iter().all(|&label| label <= u8::MAX as Label)
+}
+
+/// Maps each label of a segmentation, keeping its shape and memory order.
+pub fn relabel<T, U, F>(voxels: &Voxels<T>, map: F) -> Result<Voxels<U>, Error>
+where
+    T: Copy,
+    F: FnMut(T) -> Result<U, Error>,
+{
+    let data = voxels
+        .data()
+        .iter()
+        .copied()
+        .map(map)
+        .collect::<Result<Vec<U>, Error>>()?;
+    Ok(if voxels.is_col_major() {
+        Voxels::new(data, *voxels.nel())
+    } else {
+        Voxels::new_row_major(data, *voxels.nel())
+    })
+}
+
+/// Converts labels to a narrower (or wider) integer type, erroring on overflow.
+pub fn narrow<T, U>(voxels: &Voxels<T>, file: &str) -> Result<Voxels<U>, Error>
+where
+    T: Copy + Display + TryInto<U>,
+{
+    relabel(voxels, |label| {
+        label.try_into().map_err(|_| {
+            Error::InvalidData(format!(
+                "Label {label} from {file} is out of range for {}-bit labels",
+                8 * size_of::<U>()
+            ))
+        })
+    })
+}
+
+/// Reads an npy segmentation of any integer dtype.
+fn read_npy(file: &str) -> Result<Voxels<Label>, Error> {
+    let mut preamble = [0; 12];
+    File::open(file)?.read_exact(&mut preamble)?;
+    let end = match preamble[6] {
+        1 => 10 + u16::from_le_bytes([preamble[8], preamble[9]]) as usize,
+        _ => 12 + u32::from_le_bytes(preamble[8..12].try_into().unwrap()) as usize,
+    };
+    let mut header = vec![0; end];
+    File::open(file)?.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+    let descr = header
+        .split("'descr':")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').nth(1))
+        .unwrap_or("UNDEFINED");
+    match descr.get(1..) {
+        Some("u1") => narrow(&Voxels::<u8>::try_from(GridInput::Npy(file))?, file),
+        Some("i1") => narrow(&Voxels::<i8>::try_from(GridInput::Npy(file))?, file),
+        Some("u2") => narrow(&Voxels::<u16>::try_from(GridInput::Npy(file))?, file),
+        Some("i2") => narrow(&Voxels::<i16>::try_from(GridInput::Npy(file))?, file),
+        Some("u4") => narrow(&Voxels::<u32>::try_from(GridInput::Npy(file))?, file),
+        Some("i4") => narrow(&Voxels::<i32>::try_from(GridInput::Npy(file))?, file),
+        Some("u8") => narrow(&Voxels::<u64>::try_from(GridInput::Npy(file))?, file),
+        Some("i8") => narrow(&Voxels::<i64>::try_from(GridInput::Npy(file))?, file),
+        _ => Err(Error::InvalidData(format!(
+            "Segmentation {file} has dtype {descr}, but labels must be integers"
+        ))),
+    }
+}
+
+/// Reads a scalar field (npy | vtu) of one value per node or per element.
+///
+/// An npy file holds a float array of one column, and a vtu file holds the
+/// named array, or else the first one, in its point or cell data.
+pub(crate) fn read_field(file: &str, array: Option<&str>) -> Result<Vec<f64>, Error> {
+    let values = match extension(file) {
+        Some("npy") => match Npy::<f64>::read(file) {
+            Ok(npy) => (npy.shape.iter().skip(1).all(|&size| size == 1)).then_some(npy.data),
+            Err(_) => {
+                let npy = Npy::<f32>::read(file)?;
+                (npy.shape.iter().skip(1).all(|&size| size == 1))
+                    .then(|| npy.data.into_iter().map(f64::from).collect())
+            }
+        }
+        .ok_or_else(|| Error::InvalidData(format!("Field {file} must have a single column")))?,
+        Some("vtu") => {
+            let text = std::fs::read_to_string(file)?;
+            let encoding = encoding(tag(&text, "<VTKFile")?)?;
+            let arrays = ["PointData", "CellData"]
+                .into_iter()
+                .filter_map(|name| {
+                    let start = text.find(&format!("<{name}"))?;
+                    let end = text[start..].find(&format!("</{name}>"))? + start;
+                    Some(&text[start..end])
+                })
+                .map(data_arrays)
+                .collect::<Result<Vec<_>, _>>()?
+                .concat();
+            let array = find_data_array(&arrays, array).map_err(|_| {
+                Error::InvalidData(format!(
+                    "Field {file} has no point or cell data{}",
+                    array.map_or(String::new(), |name| format!(" named {name}"))
+                ))
+            })?;
+            floats(&array, &encoding)?
+        }
+        extension => return Err(invalid_input(file, extension)),
+    };
+    Ok(values)
+}
@@ -0,0 +1,301 @@
+use super::{Embedding, Error, Label, is_8_bit};
+use conspire::{
+    geometry::grid::Voxels,
+    io::{deflate, inflate},
+};
+use std::fs::{read as read_file, write as write_file};
+
+const HEADER_SIZE: usize = 348;
+const VOX_OFFSET: usize = 352;
+
+const UINT8: i16 = 2;
+const INT16: i16 = 4;
+const INT32: i16 = 8;
+const FLOAT32: i16 = 16;
+const FLOAT64: i16 = 64;
+const INT8: i16 = 256;
+const UINT16: i16 = 512;
+const UINT32: i16 = 768;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether a file name is a NIfTI image (nii | nii.gz).
+pub fn is_nifti(file: &str) -> bool {
+    file.ends_with(".nii") || file.ends_with(".nii.gz")
+}
+
+/// Reads a single-file NIfTI-1 label image into voxels and its embedding.
+///
+/// The voxel spacing and origin come from the sform, or the qform and
+/// `pixdim` when there is no sform. NIfTI places voxel centers on the grid,
+/// so the translation is offset by half a voxel to the first voxel's corner.
+/// An affine with a rotation or flip, which voxels aligned with the axes
+/// cannot be embedded by, is an error rather than being ignored. Stored values
+/// are scaled by `scl_slope` and `scl_inter`, unless the slope is zero as for
+/// unscaled data, and must then be whole labels.
+pub fn read(file: &str) -> Result<(Voxels<Label>, Embedding), Error> {
+    let invalid = |problem: &str| Error::InvalidData(format!("NIfTI file {file} {problem}"));
+    let mut bytes = read_file(file)?;
+    if bytes.starts_with(&GZIP_MAGIC) {
+        bytes = gunzip(&bytes).map_err(|problem| invalid(&problem))?;
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(invalid("is truncated"));
+    }
+    let little = match &bytes[0..4] {
+        [92, 1, 0, 0] => true,
+        [0, 0, 1, 92] => false,
+        [28, 2, 0, 0] | [0, 0, 2, 28] => {
+            return Err(invalid("is NIfTI-2, which is unsupported"));
+        }
+        _ => return Err(invalid("has an invalid header size")),
+    };
+    if &bytes[344..348] != b"n+1\0" {
+        return Err(invalid(
+            "is not a single-file NIfTI-1 image (.hdr/.img pairs are unsupported)",
+        ));
+    }
+    let header = Header {
+        bytes: &bytes,
+        little,
+    };
+    let ndim = header.i16(40);
+    if !(1..=7).contains(&ndim) {
+        return Err(invalid("has an invalid number of dimensions"));
+    }
+    let dim: Vec<usize> = (1..=ndim as usize)
+        .map(|index| header.i16(40 + 2 * index))
+        .map(|size| usize::try_from(size).ok().filter(|&size| size > 0))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("has a dimension that is not positive"))?;
+    if dim.iter().skip(3).any(|&size| size > 1) {
+        return Err(invalid("has more than three dimensions"));
+    }
+    let nel = [0, 1, 2].map(|axis| dim.get(axis).copied().unwrap_or(1));
+    let count = nel.iter().product::<usize>();
+    let datatype = header.i16(70);
+    let size = match datatype {
+        UINT8 | INT8 => 1,
+        INT16 | UINT16 => 2,
+        INT32 | UINT32 | FLOAT32 => 4,
+        FLOAT64 => 8,
+        _ => return Err(invalid("has an unsupported datatype")),
+    };
+    let (slope, intercept) = match (header.f32(112) as f64, header.f32(116) as f64) {
+        (0.0, _) => (1.0, 0.0),
+        (slope, intercept) if slope.is_finite() && intercept.is_finite() => (slope, intercept),
+        _ => return Err(invalid("has a non-finite scl_slope or scl_inter")),
+    };
+    let offset = (header.f32(108) as usize).max(VOX_OFFSET);
+    let raw = bytes
+        .get(offset..offset + count * size)
+        .ok_or_else(|| invalid("has fewer voxels than its dimensions"))?;
+    let data = raw
+        .chunks_exact(size)
+        .map(|chunk| {
+            let label = match datatype {
+                UINT8 => chunk[0] as f64,
+                INT8 => chunk[0] as i8 as f64,
+                INT16 => header.i16_from(chunk) as f64,
+                UINT16 => header.i16_from(chunk) as u16 as f64,
+                INT32 => header.i32_from(chunk) as f64,
+                UINT32 => header.i32_from(chunk) as u32 as f64,
+                FLOAT32 => header.f32_from(chunk) as f64,
+                _ => header.f64_from(chunk),
+            } * slope
+                + intercept;
+            if label.fract() == 0.0 && (0.0..=Label::MAX as f64).contains(&label) {
+                Ok(label as Label)
+            } else {
+                Err(invalid(&format!(
+                    "has label {label}, which is not in 0..={}",
+                    Label::MAX
+                )))
+            }
+        })
+        .collect::<Result<Vec<Label>, Error>>()?;
+    let pixdim = [1, 2, 3].map(|axis| header.f32(76 + 4 * axis) as f64);
+    if header.i16(254) <= 0 && pixdim.iter().any(|&spacing| spacing < 0.0) {
+        return Err(invalid("has a negative pixdim, which flips an axis"));
+    }
+    let mut scale = pixdim.map(|spacing| if spacing > 0.0 { spacing } else { 1.0 });
+    let origin = if header.i16(254) > 0 {
+        let rows = [280, 296, 312].map(|row| [0, 4, 8, 12].map(|column| header.f32(row + column)));
+        for (axis, row) in rows.iter().enumerate() {
+            if (0..3).any(|column| column != axis && row[column] != 0.0) {
+                return Err(invalid("has an sform with a rotation or shear"));
+            }
+            if row[axis] <= 0.0 {
+                return Err(invalid("has an sform that flips or collapses an axis"));
+            }
+            scale[axis] = row[axis] as f64
+        }
+        rows.map(|row| row[3] as f64)
+    } else if header.i16(252) > 0 {
+        if [256, 260, 264]
+            .iter()
+            .any(|&offset| header.f32(offset) != 0.0)
+        {
+            return Err(invalid("has a qform with a rotation"));
+        }
+        if header.f32(76) < 0.0 {
+            return Err(invalid("has a qform that flips the z axis"));
+        }
+        [268, 272, 276].map(|offset| header.f32(offset) as f64)
+    } else {
+        [0.0; 3]
+    };
+    let translate = [0, 1, 2].map(|axis| origin[axis] - 0.5 * scale[axis]);
+    Ok((Voxels::new(data, nel), Embedding { scale, translate }))
+}
+
+/// Writes voxels and their embedding as an 8- or 16-bit NIfTI-1 image (nii | nii.gz).
+pub fn write(file: &str, voxels: &Voxels<Label>, embedding: &Embedding) -> Result<(), Error> {
+    let nel = voxels
+        .nel()
+        .iter()
+        .map(|&nel| {
+            i16::try_from(nel).map_err(|_| {
+                Error::InvalidData(format!(
+                    "NIfTI file {file} cannot have {nel} voxels along an axis, more than {}",
+                    i16::MAX
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let bits: i16 = if is_8_bit(voxels) { 8 } else { 16 };
+    let mut bytes = vec![0; VOX_OFFSET];
+    let mut put =
+        |offset: usize, value: &[u8]| bytes[offset..offset + value.len()].copy_from_slice(value);
+    put(0, &(HEADER_SIZE as i32).to_le_bytes());
+    put(40, &3_i16.to_le_bytes());
+    nel.iter()
+        .enumerate()
+        .for_each(|(axis, nel)| put(42 + 2 * axis, &nel.to_le_bytes()));
+    put(70, &if bits == 8 { UINT8 } else { UINT16 }.to_le_bytes());
+    put(72, &bits.to_le_bytes());
+    put(76, &1_f32.to_le_bytes());
+    let origin: [f64; 3] =
+        [0, 1, 2].map(|axis| embedding.translate[axis] + 0.5 * embedding.scale[axis]);
+    for axis in 0..3 {
+        put(80 + 4 * axis, &(embedding.scale[axis] as f32).to_le_bytes());
+        let mut row = [0.0_f32; 4];
+        row[axis] = embedding.scale[axis] as f32;
+        row[3] = origin[axis] as f32;
+        row.iter()
+            .enumerate()
+            .for_each(|(column, value)| put(280 + 16 * axis + 4 * column, &value.to_le_bytes()));
+        put(268 + 4 * axis, &(origin[axis] as f32).to_le_bytes());
+    }
+    put(108, &(VOX_OFFSET as f32).to_le_bytes());
+    put(252, &1_i16.to_le_bytes());
+    put(254, &1_i16.to_le_bytes());
+    put(344, b"n+1\0");
+    voxels.data_col_major().iter().for_each(|&label| {
+        if bits == 8 {
+            bytes.push(label as u8)
+        } else {
+            bytes.extend(label.to_le_bytes())
+        }
+    });
+    if file.ends_with(".gz") {
+        bytes = gzip(&bytes);
+    }
+    Ok(write_file(file, bytes)?)
+}
+
+/// Little- or big-endian reader over a NIfTI-1 header and its voxels.
+struct Header<'a> {
+    bytes: &'a [u8],
+    little: bool,
+}
+
+impl Header<'_> {
+    fn i16(&self, offset: usize) -> i16 {
+        self.i16_from(&self.bytes[offset..offset + 2])
+    }
+    fn f32(&self, offset: usize) -> f32 {
+        self.f32_from(&self.bytes[offset..offset + 4])
+    }
+    fn i16_from(&self, chunk: &[u8]) -> i16 {
+        let bytes = chunk.try_into().unwrap();
+        if self.little {
+            i16::from_le_bytes(bytes)
+        } else {
+            i16::from_be_bytes(bytes)
+        }
+    }
+    fn i32_from(&self, chunk: &[u8]) -> i32 {
+        let bytes = chunk.try_into().unwrap();
+        if self.little {
+            i32::from_le_bytes(bytes)
+        } else {
+            i32::from_be_bytes(bytes)
+        }
+    }
+    fn f32_from(&self, chunk: &[u8]) -> f32 {
+        let bytes = chunk.try_into().unwrap();
+        if self.little {
+            f32::from_le_bytes(bytes)
+        } else {
+            f32::from_be_bytes(bytes)
+        }
+    }
+    fn f64_from(&self, chunk: &[u8]) -> f64 {
+        let bytes = chunk.try_into().unwrap();
+        if self.little {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        }
+    }
+}
+
+/// Decompresses a gzip member, skipping the optional header fields.
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let truncated = || String::from("is a truncated gzip stream");
+    if bytes.len() < 18 || bytes[2] != 8 {
+        return Err(String::from("is not a deflate gzip stream"));
+    }
+    let flags = bytes[3];
+    let mut offset = 10;
+    if flags & 0x04 != 0 {
+        let extra = bytes.get(offset..offset + 2).ok_or_else(truncated)?;
+        offset += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            offset += 1 + bytes
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or_else(truncated)?;
+        }
+    }
+    if flags & 0x02 != 0 {
+        offset += 2;
+    }
+    let stream = bytes.get(offset..).ok_or_else(truncated)?;
+    let data = inflate(stream).map_err(|error| error.to_string())?;
+    let trailer = &bytes[bytes.len() - 8..];
+    if crc32(&data).to_le_bytes() != trailer[0..4] {
+        return Err(String::from("fails its gzip CRC-32 check"));
+    }
+    Ok(data)
+}
+
+/// Compresses data into a single gzip member.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    bytes.extend(deflate(data));
+    bytes.extend(crc32(data).to_le_bytes());
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
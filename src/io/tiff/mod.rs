@@ -0,0 +1,335 @@
+use super::{Error, Label, glob, is_pattern};
+use conspire::geometry::grid::Voxels;
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs::{read, read_dir},
+    iter::repeat_n,
+    path::{Path, PathBuf},
+};
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const TILE_WIDTH: u16 = 322;
+
+const UNCOMPRESSED: u32 = 1;
+const PACK_BITS: u32 = 32773;
+
+/// Reads a stack of 8-, 16-, or 32-bit grayscale TIFF slices into voxels.
+///
+/// A 32-bit pixel is read as an unsigned integer, and one outside of
+/// [`Label`] is an error rather than wrapping around.
+///
+/// The input is a directory of slices, a file name pattern using `*` or `?`,
+/// or a single (possibly multi-page) TIFF. Slices are stacked along z in
+/// natural order, so `slice_10.tif` follows `slice_9.tif`. Image rows run top
+/// to bottom while y runs upward, so the pixel at column x and row r of slice
+/// z becomes the voxel at (x, height - 1 - r, z).
+pub fn read_stack(input: &str) -> Result<Voxels<Label>, Error> {
+    let files = slices(input)?;
+    let mut nel: Option<[usize; 2]> = None;
+    let mut data = Vec::new();
+    let mut nelz = 0;
+    for file in &files {
+        let bytes = read(file)?;
+        for page in pages(&bytes, file)? {
+            match nel {
+                Some(shape) if shape != [page.width, page.height] => {
+                    return Err(Error::InvalidData(format!(
+                        "Slice {} is {}x{} but preceding slices are {}x{}",
+                        file.display(),
+                        page.width,
+                        page.height,
+                        shape[0],
+                        shape[1]
+                    )));
+                }
+                _ => nel = Some([page.width, page.height]),
+            }
+            page.pixels
+                .chunks_exact(page.width)
+                .rev()
+                .for_each(|row| data.extend_from_slice(row));
+            nelz += 1;
+        }
+    }
+    let [nelx, nely] = nel.ok_or_else(|| Error::InvalidData("TIFF stack has no slices".into()))?;
+    Ok(Voxels::new(data, [nelx, nely, nelz]))
+}
+
+/// Resolves the slice files of a TIFF stack in natural order.
+fn slices(input: &str) -> Result<Vec<PathBuf>, Error> {
+    let path = Path::new(input);
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| is_tiff(file))
+            .collect()
+    } else if is_pattern(input) {
+        glob(input)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    if files.is_empty() {
+        return Err(Error::InvalidData(format!(
+            "No TIFF slices found for {input}"
+        )));
+    }
+    files.sort_by(|a, b| natural(&a.to_string_lossy(), &b.to_string_lossy()));
+    Ok(files)
+}
+
+fn is_tiff(file: &Path) -> bool {
+    matches!(
+        file.extension().and_then(|ext| ext.to_str()),
+        Some("tif") | Some("tiff")
+    )
+}
+
+/// Matches a file name against a pattern of literals, `*`, and `?`.
+pub(super) fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Orders strings with embedded numbers by value, so `a_2` precedes `a_10`.
+pub(super) fn natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let split = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (i, j) = (split(a), split(b));
+                let trim = |s: &[u8]| {
+                    let zeros = s.iter().take_while(|&&c| c == b'0').count();
+                    s[zeros..].to_vec()
+                };
+                let (x, y) = (trim(&a[..i]), trim(&b[..j]));
+                match x.len().cmp(&y.len()).then_with(|| x.cmp(&y)) {
+                    Ordering::Equal => (a, b) = (&a[i..], &b[j..]),
+                    ordering => return ordering,
+                }
+            }
+            (Some(x), Some(y)) => match x.cmp(y) {
+                Ordering::Equal => (a, b) = (&a[1..], &b[1..]),
+                ordering => return ordering,
+            },
+        }
+    }
+}
+
+struct Page {
+    width: usize,
+    height: usize,
+    pixels: Vec<Label>,
+}
+
+/// Byte-order aware reader over the raw bytes of a TIFF file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little: bool,
+    file: &'a Path,
+}
+
+impl Reader<'_> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&[u8], Error> {
+        self.bytes
+            .get(offset..offset + length)
+            .ok_or_else(|| self.invalid("is truncated"))
+    }
+    fn u16(&self, offset: usize) -> Result<u16, Error> {
+        let bytes = self.slice(offset, 2)?.try_into().unwrap();
+        Ok(if self.little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        let bytes = self.slice(offset, 4)?.try_into().unwrap();
+        Ok(if self.little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+    /// Reads the values of an IFD entry as integers, inline or at an offset.
+    fn values(&self, entry: usize) -> Result<Vec<u32>, Error> {
+        let kind = self.u16(entry + 2)?;
+        let count = self.u32(entry + 4)? as usize;
+        let size = match kind {
+            1 => 1,
+            3 => 2,
+            4 => 4,
+            _ => return Err(self.invalid("has an unsupported tag type")),
+        };
+        let start = if size * count <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        (0..count)
+            .map(|index| {
+                let offset = start + index * size;
+                match size {
+                    1 => self.slice(offset, 1).map(|byte| byte[0] as u32),
+                    2 => self.u16(offset).map(u32::from),
+                    _ => self.u32(offset),
+                }
+            })
+            .collect()
+    }
+    fn invalid(&self, problem: &str) -> Error {
+        Error::InvalidData(format!("TIFF file {} {problem}", self.file.display()))
+    }
+}
+
+/// Decodes every page (image file directory) of a TIFF file.
+fn pages(bytes: &[u8], file: &Path) -> Result<Vec<Page>, Error> {
+    let little = match bytes.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => {
+            return Err(Error::InvalidData(format!(
+                "File {} is not a TIFF",
+                file.display()
+            )));
+        }
+    };
+    let reader = Reader {
+        bytes,
+        little,
+        file,
+    };
+    if reader.u16(2)? != 42 {
+        return Err(reader.invalid("is not a classic TIFF (BigTIFF is unsupported)"));
+    }
+    let mut pages = Vec::new();
+    let mut ifd = reader.u32(4)? as usize;
+    let mut visited = HashSet::new();
+    while ifd != 0 {
+        if !visited.insert(ifd) {
+            return Err(reader.invalid("has a loop of image file directories"));
+        }
+        let entries = reader.u16(ifd)? as usize;
+        let mut width = None;
+        let mut height = None;
+        let mut bits = vec![1];
+        let mut samples = 1;
+        let mut compression = UNCOMPRESSED;
+        let mut offsets = Vec::new();
+        let mut counts = Vec::new();
+        for index in 0..entries {
+            let entry = ifd + 2 + 12 * index;
+            let first = || -> Result<u32, Error> {
+                reader
+                    .values(entry)?
+                    .first()
+                    .copied()
+                    .ok_or_else(|| reader.invalid("has an empty tag"))
+            };
+            match reader.u16(entry)? {
+                IMAGE_WIDTH => width = Some(first()? as usize),
+                IMAGE_LENGTH => height = Some(first()? as usize),
+                BITS_PER_SAMPLE => bits = reader.values(entry)?,
+                COMPRESSION => compression = first()?,
+                STRIP_OFFSETS => offsets = reader.values(entry)?,
+                SAMPLES_PER_PIXEL => samples = first()?,
+                STRIP_BYTE_COUNTS => counts = reader.values(entry)?,
+                TILE_WIDTH => return Err(reader.invalid("is tiled (only strips are supported)")),
+                _ => {}
+            }
+        }
+        let (Some(width @ 1..), Some(height @ 1..)) = (width, height) else {
+            return Err(reader.invalid("is missing its image dimensions"));
+        };
+        let size: usize = match (samples, bits.as_slice()) {
+            (1, [8]) => 1,
+            (1, [16]) => 2,
+            (1, [32]) => 4,
+            _ => return Err(reader.invalid("is not 8-, 16-, or 32-bit grayscale")),
+        };
+        if offsets.len() != counts.len() {
+            return Err(reader.invalid("has mismatched strip offsets and byte counts"));
+        }
+        let length = size
+            .checked_mul(width)
+            .and_then(|length| length.checked_mul(height))
+            .ok_or_else(|| reader.invalid("has image dimensions too large to read"))?;
+        let stored = counts.iter().fold(0_usize, |total, &count| {
+            total.saturating_add(count as usize)
+        });
+        let mut raw = Vec::with_capacity(length.min(stored));
+        for (&offset, &count) in offsets.iter().zip(counts.iter()) {
+            let strip = reader.slice(offset as usize, count as usize)?;
+            match compression {
+                UNCOMPRESSED => raw.extend_from_slice(strip),
+                PACK_BITS => unpack_bits(strip, &mut raw),
+                _ => return Err(reader.invalid("uses an unsupported compression")),
+            }
+        }
+        if raw.len() < length {
+            return Err(reader.invalid("has fewer pixels than its dimensions"));
+        }
+        let pixels = raw
+            .chunks_exact(size)
+            .take(width * height)
+            .map(|pixel| {
+                let value = match *pixel {
+                    [byte] => byte as u32,
+                    [first, second] if little => u16::from_le_bytes([first, second]) as u32,
+                    [first, second] => u16::from_be_bytes([first, second]) as u32,
+                    [a, b, c, d] if little => u32::from_le_bytes([a, b, c, d]),
+                    [a, b, c, d] => u32::from_be_bytes([a, b, c, d]),
+                    _ => unreachable!("pixels are one, two, or four bytes"),
+                };
+                Label::try_from(value).map_err(|_| {
+                    reader.invalid(&format!(
+                        "has label {value}, which is out of range for {}-bit labels",
+                        Label::BITS
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        pages.push(Page {
+            width,
+            height,
+            pixels,
+        });
+        ifd = reader.u32(ifd + 2 + 12 * entries)? as usize;
+    }
+    Ok(pages)
+}
+
+/// Decodes PackBits run-length encoded data.
+fn unpack_bits(mut strip: &[u8], pixels: &mut Vec<u8>) {
+    while let Some((&header, rest)) = strip.split_first() {
+        let header = header as i8;
+        strip = rest;
+        if header >= 0 {
+            let length = (header as usize + 1).min(strip.len());
+            pixels.extend_from_slice(&strip[..length]);
+            strip = &strip[length..];
+        } else if header != -128
+            && let Some((&byte, rest)) = strip.split_first()
+        {
+            pixels.extend(repeat_n(byte, (1 - header as isize) as usize));
+            strip = rest;
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Automatic mesh generation.
+//!
+//! Each stage of the `automesh` pipeline is configured with a builder-style
+//! options type and run on a [`conspire`] mesh in memory, so the stages can be
+//! composed in-process the same way the command line composes them.
+//!
+//! ```no_run
+//! use automesh::{Element, MeshOptions, SmoothOptions, write_mesh, write_metrics};
+//!
+//! let quiet = true;
+//! let mut mesh = MeshOptions::new(Element::Hexahedra)
+//!     .remove(vec![0])
+//!     .mesh("letter_f_3d.npy", quiet)?;
+//! SmoothOptions::default()
+//!     .hierarchical(true)
+//!     .smooth(&mut mesh, quiet)?;
+//! write_metrics(&mesh, "metrics.csv", quiet)?;
+//! write_mesh("letter_f_3d.inp", mesh, quiet)?;
+//! # Ok::<(), automesh::Error>(())
+//! ```
+//!
+//! Every function taking `quiet` reports its progress to the terminal unless
+//! `quiet` is `true`.
+//!
+//! The command line, and with it `clap`, is behind the default `cli` feature,
+//! which library users can leave out with `default-features = false`.
+
+mod batch;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli;
+mod convert;
+mod defeature;
+mod diff;
+mod error;
+mod extract;
+mod feature;
+mod improve;
+mod io;
+#[doc(hidden)]
+pub mod log;
+mod mesh;
+mod metrics;
+#[cfg(feature = "python")]
+mod py;
+mod recipe;
+mod remesh;
+mod segment;
+mod sets;
+mod smooth;
+mod split;
+mod vector;
+
+pub use batch::{Batch, Case, write_summary};
+pub use conspire;
+pub use convert::ConvertOptions;
+pub use defeature::DefeatureOptions;
+pub use diff::{Deviation, DiffOptions};
+pub use error::Error;
+pub use extract::ExtractOptions;
+pub use feature::FeatureOptions;
+pub use improve::{IMPROVE_DEFAULT_ITERS, IMPROVE_DEFAULT_TARGET, ImproveOptions};
+pub use io::{
+    Embedding, Label, extension, is_segmentation, read_mesh, read_segmentation, write_fractions,
+    write_mesh, write_mesh_named, write_segmentation,
+};
+pub use mesh::{Element, MeshOptions, OCTREE_DEFAULT_LEVELS, OCTREE_DEFAULT_SCALE};
+pub use metrics::{
+    Gate, METRICS_DEFAULT_COLUMNS, Metric, Metrics, REPORT_DEFAULT_BINS, REPORT_DEFAULT_WORST,
+    ReportOptions, write_metric_columns, write_metrics,
+};
+pub use recipe::{MeshStage, Recipe, Stage};
+pub use remesh::{
+    ADAPTIVE_DEFAULT_GRADATION, ADAPTIVE_DEFAULT_TOLERANCE, REMESH_DEFAULT_ITERS, RemeshOptions,
+    Sizing,
+};
+pub use segment::{SegmentOptions, Segmented, Tie};
+pub use sets::{SetNames, SetOptions};
+pub use smooth::{
+    HC_DEFAULT_ALPHA, HC_DEFAULT_BETA, SmoothOptions, SmoothingMethod, SmoothingWeighting,
+    TAUBIN_DEFAULT_BAND, TAUBIN_DEFAULT_ITERS, TAUBIN_DEFAULT_SCALE,
+};
+pub use split::{hexahedra_to_tetrahedra, lattice_to_tetrahedra, tetrahedra_to_hexahedra};
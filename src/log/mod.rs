@@ -1,19 +1,27 @@
 use std::{
+    cell::RefCell,
     fs::File,
     io::{self, Write},
     path::Path,
     process::Command,
-    sync::Mutex,
 };
 
-static LOGFILE: Mutex<Option<File>> = Mutex::new(None);
+thread_local! {
+    /// Each thread logs to its own file, so that parallel runs keep separate logs.
+    static LOGFILE: RefCell<Option<File>> = const { RefCell::new(None) };
+}
 
 pub fn set_logfile(path: &str) -> io::Result<String> {
     let stamped = stamped_path(path);
-    *LOGFILE.lock().unwrap() = Some(File::create(&stamped)?);
+    let file = File::create(&stamped)?;
+    LOGFILE.with_borrow_mut(|logfile| *logfile = Some(file));
     Ok(stamped)
 }
 
+pub fn unset_logfile() {
+    LOGFILE.with_borrow_mut(|logfile| *logfile = None);
+}
+
 fn stamped_path(path: &str) -> String {
     insert_stamp(path, &timestamp())
 }
@@ -45,9 +53,11 @@ fn timestamp() -> String {
 }
 
 pub fn write_log(line: &str) {
-    if let Some(file) = LOGFILE.lock().unwrap().as_mut() {
-        let _ = writeln!(file, "{}", strip_ansi(line));
-    }
+    LOGFILE.with_borrow_mut(|logfile| {
+        if let Some(file) = logfile.as_mut() {
+            let _ = writeln!(file, "{}", strip_ansi(line));
+        }
+    });
 }
 
 fn strip_ansi(input: &str) -> String {
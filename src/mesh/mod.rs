@@ -1,11 +1,9 @@
 use super::{
-    ErrorWrapper,
-    io::{extension, invalid_input, read_segmentation, write_mesh},
-    metrics::write_metrics,
-    remesh::apply_remesh_subcommand,
-    smooth::{MeshSmoothCommands, apply_smoothing_method},
+    Error,
+    io::{Embedding, Label, extension, invalid_input, is_segmentation, read_segmentation, relabel},
+    sets::{SetNames, attach, classify},
+    split::{hexahedra_to_tetrahedra, lattice_to_tetrahedra},
 };
-use clap::Subcommand;
 use conspire::{
     geometry::{
         Coordinate, Coordinates,
@@ -16,385 +14,460 @@ use conspire::{
     },
     math::Tensor,
 };
-use std::{collections::HashSet, path::Path, time::Instant};
+use serde::{Deserialize, Serialize};
+use std::{array::from_fn, collections::HashSet, path::Path, time::Instant};
 
-#[derive(Subcommand)]
-pub enum MeshSubcommand {
-    /// Creates an all-hexahedral mesh from a segmentation or tessellation
-    Hex(MeshArgs),
-    /// Creates a hex-dominant mesh from a tessellation, polyhedral at the boundary
-    Hexdom(MeshArgs),
-    /// Creates a polyhedral mesh from a tessellation
-    Poly(MeshArgs),
-    /// Creates all-triangular isosurface(s) from a segmentation
-    Tri(MeshArgs),
-}
-
-#[derive(clap::Args)]
-pub struct MeshArgs {
-    #[command(subcommand)]
-    pub smoothing: Option<MeshSmoothCommands>,
-
-    /// Segmentation (npy | spn) or tessellation (stl) input file
-    #[arg(long, short, value_name = "FILE")]
-    pub input: String,
-
-    /// Mesh output file (exo | inp | mesh | stl | vtu)
-    #[arg(long, short, value_name = "FILE")]
-    pub output: String,
-
-    /// Defeature clusters with less than NUM voxels
-    #[arg(long, short, value_name = "NUM")]
-    pub defeature: Option<usize>,
-
-    /// Number of voxels in the x-direction (spn)
-    #[arg(long, short = 'x', value_name = "NEL")]
-    pub nelx: Option<usize>,
-
-    /// Number of voxels in the y-direction (spn)
-    #[arg(long, short = 'y', value_name = "NEL")]
-    pub nely: Option<usize>,
-
-    /// Number of voxels in the z-direction (spn)
-    #[arg(long, short = 'z', value_name = "NEL")]
-    pub nelz: Option<usize>,
-
-    /// Voxel IDs to remove from the mesh (npy | spn)
-    #[arg(long, num_args = 1.., short, value_delimiter = ' ', value_name = "ID")]
-    pub remove: Option<Vec<usize>>,
-
-    /// Scaling (> 0.0) in the x-direction, applied before translation
-    #[arg(default_value_t = 1.0, long, value_name = "SCALE")]
-    pub xscale: f64,
-
-    /// Scaling (> 0.0) in the y-direction, applied before translation
-    #[arg(default_value_t = 1.0, long, value_name = "SCALE")]
-    pub yscale: f64,
-
-    /// Scaling (> 0.0) in the z-direction, applied before translation
-    #[arg(default_value_t = 1.0, long, value_name = "SCALE")]
-    pub zscale: f64,
-
-    /// Translation in the x-direction
-    #[arg(
-        long,
-        default_value_t = 0.0,
-        allow_negative_numbers = true,
-        value_name = "VAL"
-    )]
-    pub xtranslate: f64,
-
-    /// Translation in the y-direction
-    #[arg(
-        long,
-        default_value_t = 0.0,
-        allow_negative_numbers = true,
-        value_name = "VAL"
-    )]
-    pub ytranslate: f64,
-
-    /// Translation in the z-direction
-    #[arg(
-        long,
-        default_value_t = 0.0,
-        allow_negative_numbers = true,
-        value_name = "VAL"
-    )]
-    pub ztranslate: f64,
-
-    /// Octree refinement scale for dualizing a tessellation (stl) input
-    #[arg(long, default_value_t = 5.0, short = 's', value_name = "SCALE")]
-    pub scale: f64,
-
-    /// Uniform lattice of the given cell size instead of an octree (stl)
-    #[arg(long, short = 'u', value_name = "SPACING")]
-    pub uniform: Option<f64>,
-
-    /// Chord-error tolerance for curvature-driven refinement [default: disabled]
-    #[arg(long, short = 't', value_name = "TOL")]
-    pub tolerance: Option<f64>,
-
-    /// Uses strong balancing instead of the default weak balancing
-    #[arg(action, long)]
-    pub strong: bool,
-
-    /// Snaps the buffer layer onto the surface instead of a soft fit
-    #[arg(action, long)]
-    pub snap: bool,
-
-    /// Level difference allowed between neighboring octree cells (poly)
-    #[arg(long, default_value_t = 1, short = 'l', value_name = "NUM")]
-    pub levels: usize,
-
-    /// Quality metrics output file (csv | npy)
-    #[arg(long, value_name = "FILE")]
-    pub metrics: Option<String>,
-}
+pub const OCTREE_DEFAULT_SCALE: f64 = 5.0;
+pub const OCTREE_DEFAULT_LEVELS: usize = 1;
 
+/// Type of elements to mesh into.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum Element {
+    #[serde(rename = "hex")]
     Hexahedra,
+    #[serde(rename = "hexdom")]
     HexDominant,
+    #[serde(rename = "poly")]
     Polyhedra,
+    #[serde(rename = "tet")]
+    Tetrahedra,
+    #[serde(rename = "tri")]
     Triangles,
 }
 
-fn read_voxels(args: &MeshArgs, quiet: bool) -> Result<Voxels<u8>, ErrorWrapper> {
-    match extension(&args.input) {
-        Some("npy") | Some("spn") => {
-            let mut voxels =
-                read_segmentation(&args.input, args.nelx, args.nely, args.nelz, quiet, true)?;
-            if let Some(min) = args.defeature {
-                let time = Instant::now();
-                crate::echo!(
-                    quiet,
-                    " \x1b[1;96mDefeaturing\x1b[0m clusters of {min} voxels or less"
-                );
-                voxels = voxels.defeature(min);
-                crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-            }
-            Ok(voxels)
-        }
-        extension => Err(invalid_input(&args.input, extension)),
-    }
+/// Options for meshing a segmentation or tessellation.
+///
+/// ```no_run
+/// use automesh::{Element, MeshOptions};
+///
+/// let mesh = MeshOptions::new(Element::Hexahedra)
+///     .remove(vec![0])
+///     .xscale(0.5)
+///     .mesh("letter_f_3d.npy", true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct MeshOptions {
+    element: Element,
+    defeature: Option<usize>,
+    nel: Option<[usize; 3]>,
+    remove: Option<Vec<Label>>,
+    scale: [Option<f64>; 3],
+    translate: [Option<f64>; 3],
+    octree_scale: f64,
+    uniform: Option<f64>,
+    tolerance: Option<f64>,
+    strong: bool,
+    snap: bool,
+    levels: usize,
+    sets: bool,
 }
 
-fn finish(mut mesh: Mesh<3>, args: MeshArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    if let Some(MeshSmoothCommands::Smooth {
-        remeshing,
-        iterations,
-        method,
-        pass_band,
-        scale,
-        hierarchical,
-    }) = args.smoothing
-    {
-        apply_smoothing_method(
-            &mut mesh,
-            iterations,
-            method,
-            pass_band,
-            scale,
-            hierarchical,
-            quiet,
-        )?;
-        if let Some(subcommand) = remeshing {
-            mesh = apply_remesh_subcommand(mesh, subcommand, quiet)?;
+impl MeshOptions {
+    pub fn new(element: Element) -> Self {
+        Self {
+            element,
+            defeature: None,
+            nel: None,
+            remove: None,
+            scale: [None; 3],
+            translate: [None; 3],
+            octree_scale: OCTREE_DEFAULT_SCALE,
+            uniform: None,
+            tolerance: None,
+            strong: false,
+            snap: false,
+            levels: OCTREE_DEFAULT_LEVELS,
+            sets: false,
         }
     }
-    if let Some(file) = &args.metrics {
-        write_metrics(&mesh, file, quiet)?;
+    /// Defeatures clusters with less than this many voxels.
+    pub fn defeature(mut self, min: impl Into<Option<usize>>) -> Self {
+        self.defeature = min.into();
+        self
+    }
+    /// Number of voxels in each direction, needed for an spn segmentation.
+    pub fn nel(mut self, nel: impl Into<Option<[usize; 3]>>) -> Self {
+        self.nel = nel.into();
+        self
+    }
+    /// Voxel IDs to remove from the mesh.
+    pub fn remove(mut self, remove: impl Into<Option<Vec<Label>>>) -> Self {
+        self.remove = remove.into();
+        self
+    }
+    /// Scaling in the x-direction, applied before translation [default: 1.0 or nii header].
+    pub fn xscale(mut self, scale: impl Into<Option<f64>>) -> Self {
+        self.scale[0] = scale.into();
+        self
+    }
+    /// Scaling in the y-direction, applied before translation [default: 1.0 or nii header].
+    pub fn yscale(mut self, scale: impl Into<Option<f64>>) -> Self {
+        self.scale[1] = scale.into();
+        self
+    }
+    /// Scaling in the z-direction, applied before translation [default: 1.0 or nii header].
+    pub fn zscale(mut self, scale: impl Into<Option<f64>>) -> Self {
+        self.scale[2] = scale.into();
+        self
+    }
+    /// Translation in the x-direction [default: 0.0 or nii header].
+    pub fn xtranslate(mut self, translate: impl Into<Option<f64>>) -> Self {
+        self.translate[0] = translate.into();
+        self
+    }
+    /// Translation in the y-direction [default: 0.0 or nii header].
+    pub fn ytranslate(mut self, translate: impl Into<Option<f64>>) -> Self {
+        self.translate[1] = translate.into();
+        self
+    }
+    /// Translation in the z-direction [default: 0.0 or nii header].
+    pub fn ztranslate(mut self, translate: impl Into<Option<f64>>) -> Self {
+        self.translate[2] = translate.into();
+        self
+    }
+    /// Octree refinement scale for dualizing a tessellation (stl) input.
+    pub fn octree_scale(mut self, scale: f64) -> Self {
+        self.octree_scale = scale;
+        self
+    }
+    /// Uniform lattice of the given cell size instead of an octree (stl).
+    pub fn uniform(mut self, spacing: impl Into<Option<f64>>) -> Self {
+        self.uniform = spacing.into();
+        self
+    }
+    /// Chord-error tolerance for curvature-driven refinement.
+    pub fn tolerance(mut self, tolerance: impl Into<Option<f64>>) -> Self {
+        self.tolerance = tolerance.into();
+        self
+    }
+    /// Uses strong balancing instead of weak balancing.
+    pub fn strong(mut self, strong: bool) -> Self {
+        self.strong = strong;
+        self
+    }
+    /// Snaps the buffer layer onto the surface instead of a soft fit.
+    pub fn snap(mut self, snap: bool) -> Self {
+        self.snap = snap;
+        self
+    }
+    /// Level difference allowed between neighboring octree cells (poly).
+    pub fn levels(mut self, levels: usize) -> Self {
+        self.levels = levels;
+        self
+    }
+    /// Classifies boundary, void, and interface sets of a segmentation's mesh.
+    pub fn sets(mut self, sets: bool) -> Self {
+        self.sets = sets;
+        self
+    }
+    /// Scaling and translation, from the options given or else the segmentation header.
+    fn embedding(&self, header: Option<Embedding>) -> Embedding {
+        let header = header.unwrap_or_default();
+        let pick = |options: [Option<f64>; 3], defaults: [f64; 3]| {
+            [0, 1, 2].map(|axis| options[axis].unwrap_or(defaults[axis]))
+        };
+        Embedding {
+            scale: pick(self.scale, header.scale),
+            translate: pick(self.translate, header.translate),
+        }
+    }
+    /// Meshes a segmentation (nii | npy | spn | tif) or tessellation (stl) file.
+    pub fn mesh(&self, input: &str, quiet: bool) -> Result<Mesh<3>, Error> {
+        Ok(self.mesh_named(input, quiet)?.0)
     }
-    write_mesh(&args.output, mesh, quiet)
-}
 
-pub fn mesh(element: Element, args: MeshArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    match (&element, extension(&args.input)) {
-        (Element::Hexahedra, Some("stl")) => return hexahedralize(args, quiet),
-        (element @ (Element::HexDominant | Element::Polyhedra), Some("stl")) => {
-            return cut(args, element, quiet);
+    /// Meshes a file like [`MeshOptions::mesh`], along with the names of the
+    /// sets classified under [`MeshOptions::sets`].
+    pub fn mesh_named(&self, input: &str, quiet: bool) -> Result<(Mesh<3>, SetNames), Error> {
+        let element = self.element;
+        match (element, extension(input)) {
+            (Element::Hexahedra | Element::Tetrahedra, Some("stl")) => {
+                return Ok((self.hexahedralize(input, quiet)?, SetNames::default()));
+            }
+            (Element::HexDominant | Element::Polyhedra, Some("stl")) => {
+                return Ok((self.cut(input, quiet)?, SetNames::default()));
+            }
+            (Element::HexDominant | Element::Polyhedra, extension) => {
+                return Err(invalid_input(input, extension));
+            }
+            _ => {}
         }
-        (Element::HexDominant | Element::Polyhedra, extension) => {
-            return Err(invalid_input(&args.input, extension));
+        if !is_segmentation(input) {
+            return Err(invalid_input(input, extension(input)));
         }
-        _ => {}
+        let [nelx, nely, nelz] = self.nel.map_or([None; 3], |nel| nel.map(Some));
+        let (voxels, header) = read_segmentation(input, nelx, nely, nelz, quiet, true)?;
+        self.segmentation(voxels, header, quiet)
     }
-    if args.uniform.is_some() {
-        return Err(ErrorWrapper::from(
-            "Uniform lattice meshing applies to tessellation (stl) inputs only",
-        ));
+
+    /// Meshes a segmentation already in memory, placed by the scaling and
+    /// translation options alone.
+    pub fn mesh_voxels(&self, voxels: Voxels<Label>, quiet: bool) -> Result<Mesh<3>, Error> {
+        if matches!(self.element, Element::HexDominant | Element::Polyhedra) {
+            return Err(Error::InvalidOption(
+                "Cutting requires a tessellation (stl) input".into(),
+            ));
+        }
+        Ok(self.segmentation(voxels, None, quiet)?.0)
     }
-    let voxels = read_voxels(&args, quiet)?;
-    let time = Instant::now();
-    let mesh = match element {
-        Element::Hexahedra => {
-            crate::echo!(quiet, "     \x1b[1;96mMeshing\x1b[0m voxels into hexahedra");
-            let remove: Option<Vec<u8>> = args
-                .remove
-                .as_ref()
-                .map(|ids| ids.iter().map(|&id| id as u8).collect());
-            let scale = Coordinate::from([args.xscale, args.yscale, args.zscale]);
-            let translate = Coordinate::from([args.xtranslate, args.ytranslate, args.ztranslate]);
-            let segmentation = Segmentation::new(voxels, scale, translate);
-            Mesh::from_segmentation(segmentation, remove.as_deref())
+
+    fn segmentation(
+        &self,
+        mut voxels: Voxels<Label>,
+        header: Option<Embedding>,
+        quiet: bool,
+    ) -> Result<(Mesh<3>, SetNames), Error> {
+        if self.uniform.is_some() {
+            return Err(Error::InvalidOption(
+                "Uniform lattice meshing applies to tessellation (stl) inputs only".into(),
+            ));
         }
-        Element::HexDominant | Element::Polyhedra => {
-            unreachable!("cutting requires a tessellation input")
+        if let Some(min) = self.defeature {
+            let time = Instant::now();
+            crate::echo!(
+                quiet,
+                " \x1b[1;96mDefeaturing\x1b[0m clusters of {min} voxels or less"
+            );
+            voxels = voxels.defeature(min);
+            crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
         }
-        Element::Triangles => {
-            crate::echo!(quiet, "     \x1b[1;96mMeshing\x1b[0m voxels into triangles");
-            let voxels = remove_materials(voxels, args.remove.as_deref());
-            let mesh = Mesh::from(Tessellation::from(voxels));
-            scaled(
-                mesh,
-                [args.xscale, args.yscale, args.zscale],
-                [args.xtranslate, args.ytranslate, args.ztranslate],
-            )
+        let element = self.element;
+        let embedding = self.embedding(header);
+        let nel = *voxels.nel();
+        let time = Instant::now();
+        let mut mesh = match element {
+            Element::Hexahedra | Element::Tetrahedra => {
+                let tetrahedral = matches!(element, Element::Tetrahedra);
+                crate::echo!(
+                    quiet,
+                    "     \x1b[1;96mMeshing\x1b[0m voxels into {}",
+                    if tetrahedral {
+                        "tetrahedra"
+                    } else {
+                        "hexahedra"
+                    }
+                );
+                let scale = Coordinate::from(embedding.scale);
+                let translate = Coordinate::from(embedding.translate);
+                let segmentation = Segmentation::new(voxels, scale, translate);
+                let mesh = Mesh::from_segmentation(segmentation, self.remove.as_deref());
+                if tetrahedral {
+                    lattice_to_tetrahedra(mesh)?
+                } else {
+                    mesh
+                }
+            }
+            Element::HexDominant | Element::Polyhedra => {
+                unreachable!("cutting requires a tessellation input")
+            }
+            Element::Triangles => {
+                crate::echo!(quiet, "     \x1b[1;96mMeshing\x1b[0m voxels into triangles");
+                let voxels = remove_materials(voxels, self.remove.as_deref())?;
+                let mesh = Mesh::from(Tessellation::from(voxels));
+                scaled(mesh, embedding.scale, embedding.translate)
+            }
+        };
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
+            time.elapsed(),
+            mesh.number_of_elements(),
+            mesh.number_of_nodes()
+        );
+        if self.sets && matches!(element, Element::Hexahedra | Element::Tetrahedra) {
+            let time = Instant::now();
+            crate::echo!(
+                quiet,
+                " \x1b[1;96mClassifying\x1b[0m boundaries and interfaces"
+            );
+            let corner = from_fn(|axis| {
+                embedding.translate[axis] + nel[axis] as f64 * embedding.scale[axis]
+            });
+            let sets = classify(&mesh, embedding.translate, corner);
+            crate::echo!(
+                quiet,
+                "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{}]\x1b[0m",
+                time.elapsed(),
+                sets.iter()
+                    .enumerate()
+                    .map(|(index, set)| format!("{} {}", index + 1, set.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let names = attach(&mut mesh, sets);
+            return Ok((mesh, names));
         }
-    };
-    crate::echo!(
-        quiet,
-        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
-        time.elapsed(),
-        mesh.number_of_elements(),
-        mesh.number_of_nodes()
-    );
-    finish(mesh, args, quiet)
-}
-
-/// Meshes a tessellation (stl) input into an all-hexahedral mesh.
-///
-/// A background mesh of the enclosed volume is built first — the dual of an
-/// octree fitted to the surface, or a uniform lattice under `--uniform` — and
-/// trimmed to the surface. A buffer layer is then fitted onto the surface.
-/// Buffering is timed on its own because it dominates the total by far, while
-/// the steps building the background are lumped together as one.
-fn hexahedralize(args: MeshArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    crate::echo!(quiet, "     \x1b[1;96mReading\x1b[0m {}", args.input);
-    let mut time = Instant::now();
-    let tessellation = Tessellation::try_from(Path::new(&args.input))?;
-    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-    let fitting = if args.snap {
-        Fitting::Snap
-    } else {
-        Fitting::Soft
-    };
+        Ok((mesh, SetNames::default()))
+    }
 
-    crate::echo!(
-        quiet,
-        "     \x1b[1;96mMeshing\x1b[0m hexahedra {}",
-        if args.uniform.is_some() {
-            "uniformly"
+    /// Meshes a tessellation (stl) input into an all-hexahedral mesh.
+    ///
+    /// A background mesh of the enclosed volume is built first — the dual of an
+    /// octree fitted to the surface, or a uniform lattice under `--uniform` — and
+    /// trimmed to the surface. A buffer layer is then fitted onto the surface.
+    /// Buffering is timed on its own because it dominates the total by far, while
+    /// the steps building the background are lumped together as one. For
+    /// [`Element::Tetrahedra`], the hexahedra are then split into tetrahedra.
+    fn hexahedralize(&self, input: &str, quiet: bool) -> Result<Mesh<3>, Error> {
+        crate::echo!(quiet, "     \x1b[1;96mReading\x1b[0m {input}");
+        let mut time = Instant::now();
+        let tessellation = Tessellation::try_from(Path::new(input))?;
+        crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+        let fitting = if self.snap {
+            Fitting::Snap
         } else {
-            "adaptively"
-        }
-    );
-    time = Instant::now();
-    let mut mesh = if let Some(spacing) = args.uniform {
-        tessellation.lattice_background(spacing)?.0
-    } else {
-        let balancing = if args.strong {
-            Balancing::Strong(1)
+            Fitting::Soft
+        };
+
+        crate::echo!(
+            quiet,
+            "     \x1b[1;96mMeshing\x1b[0m hexahedra {}",
+            if self.uniform.is_some() {
+                "uniformly"
+            } else {
+                "adaptively"
+            }
+        );
+        time = Instant::now();
+        let mut mesh = if let Some(spacing) = self.uniform {
+            tessellation.lattice_background(spacing)?.0
         } else {
-            Balancing::Weak(1)
+            let balancing = if self.strong {
+                Balancing::Strong(1)
+            } else {
+                Balancing::Weak(1)
+            };
+            let mut octree = Octree::<u16, usize>::from_features(
+                &tessellation,
+                self.octree_scale,
+                CurvatureSizing {
+                    tolerance: self.tolerance,
+                    ..Default::default()
+                },
+                0,
+            );
+            octree.equilibrate(balancing, Pairing::Regular)?;
+            octree.dualize()
         };
-        let mut octree = Octree::<u16, usize>::from_features(
-            &tessellation,
-            args.scale,
-            CurvatureSizing {
-                tolerance: args.tolerance,
-                ..Default::default()
-            },
-            0,
+        tessellation.trim(&mut mesh)?;
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
+            time.elapsed(),
+            mesh.number_of_elements(),
+            mesh.number_of_nodes()
         );
-        octree.equilibrate(balancing, Pairing::Regular)?;
-        octree.dualize()
-    };
-    tessellation.trim(&mut mesh)?;
-    crate::echo!(
-        quiet,
-        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
-        time.elapsed(),
-        mesh.number_of_elements(),
-        mesh.number_of_nodes()
-    );
 
-    crate::echo!(
-        quiet,
-        "   \x1b[1;96mBuffering\x1b[0m hexahedra onto geometry"
-    );
-    time = Instant::now();
-    let mesh = mesh.buffer(&tessellation, fitting)?;
-    let mesh = scaled(
-        mesh,
-        [args.xscale, args.yscale, args.zscale],
-        [args.xtranslate, args.ytranslate, args.ztranslate],
-    );
-    crate::echo!(
-        quiet,
-        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
-        time.elapsed(),
-        mesh.number_of_elements(),
-        mesh.number_of_nodes()
-    );
-    finish(mesh, args, quiet)
-}
-
-/// Cuts an octree fitted to a tessellation (stl) input to the surface.
-///
-/// [`Element::Polyhedra`] cuts the octree itself, while [`Element::HexDominant`]
-/// cuts its dual, leaving hexahedra everywhere but at the boundary.
-fn cut(args: MeshArgs, element: &Element, quiet: bool) -> Result<(), ErrorWrapper> {
-    crate::echo!(quiet, "     \x1b[1;96mReading\x1b[0m {}", args.input);
-    let mut time = Instant::now();
-    let tessellation = Tessellation::try_from(Path::new(&args.input))?;
-    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-    let polyhedral = matches!(element, Element::Polyhedra);
-    if polyhedral && args.uniform.is_some() {
-        return Err(ErrorWrapper::from(
-            "Uniform lattice meshing applies to mesh hex and mesh hexdom only",
-        ));
-    }
-    if !polyhedral && args.levels != 1 {
-        return Err(ErrorWrapper::from(
-            "Dualization requires 2:1 balancing, so levels applies to mesh poly only",
-        ));
+        crate::echo!(
+            quiet,
+            "   \x1b[1;96mBuffering\x1b[0m hexahedra onto geometry"
+        );
+        time = Instant::now();
+        let mesh = mesh.buffer(&tessellation, fitting)?;
+        let embedding = self.embedding(None);
+        let mesh = scaled(mesh, embedding.scale, embedding.translate);
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
+            time.elapsed(),
+            mesh.number_of_elements(),
+            mesh.number_of_nodes()
+        );
+        if matches!(self.element, Element::Tetrahedra) {
+            crate::echo!(
+                quiet,
+                "   \x1b[1;96mSplitting\x1b[0m hexahedra into tetrahedra"
+            );
+            time = Instant::now();
+            let mesh = hexahedra_to_tetrahedra(mesh)?;
+            crate::echo!(
+                quiet,
+                "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
+                time.elapsed(),
+                mesh.number_of_elements(),
+                mesh.number_of_nodes()
+            );
+            Ok(mesh)
+        } else {
+            Ok(mesh)
+        }
     }
 
-    crate::echo!(
-        quiet,
-        "     \x1b[1;96mMeshing\x1b[0m {} {}",
-        if polyhedral { "polyhedra" } else { "hexahedra" },
-        if args.uniform.is_some() {
-            "uniformly"
-        } else {
-            "adaptively"
+    /// Cuts an octree fitted to a tessellation (stl) input to the surface.
+    ///
+    /// [`Element::Polyhedra`] cuts the octree itself, while [`Element::HexDominant`]
+    /// cuts its dual, leaving hexahedra everywhere but at the boundary.
+    fn cut(&self, input: &str, quiet: bool) -> Result<Mesh<3>, Error> {
+        crate::echo!(quiet, "     \x1b[1;96mReading\x1b[0m {input}");
+        let mut time = Instant::now();
+        let tessellation = Tessellation::try_from(Path::new(input))?;
+        crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+        let polyhedral = matches!(self.element, Element::Polyhedra);
+        if polyhedral && self.uniform.is_some() {
+            return Err(Error::InvalidOption(
+                "Uniform lattice meshing applies to mesh hex and mesh hexdom only".into(),
+            ));
         }
-    );
-    time = Instant::now();
-    let (background, classes) = if let Some(spacing) = args.uniform {
-        tessellation.lattice_background(spacing)
-    } else {
-        let balancing = if args.strong {
-            Balancing::Strong(args.levels)
-        } else {
-            Balancing::Weak(args.levels)
-        };
-        if polyhedral {
-            tessellation.octree_background(balancing, args.scale)
-        } else {
-            tessellation.dual_background(balancing, args.scale)
+        if !polyhedral && self.levels != 1 {
+            return Err(Error::InvalidOption(
+                "Dualization requires 2:1 balancing, so levels applies to mesh poly only".into(),
+            ));
         }
-    }?;
-    let (elements, nodes) = retained(&background, &classes);
-    crate::echo!(
-        quiet,
-        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{elements} elements, {nodes} nodes]\x1b[0m",
-        time.elapsed()
-    );
 
-    crate::echo!(
-        quiet,
-        "   \x1b[1;96mBuffering\x1b[0m polyhedra onto geometry"
-    );
-    time = Instant::now();
-    let mesh = if polyhedral {
-        tessellation.cut_polyhedral(background, &classes)
-    } else {
-        tessellation.cut(background, &classes)
-    }?;
-    let mesh = scaled(
-        mesh,
-        [args.xscale, args.yscale, args.zscale],
-        [args.xtranslate, args.ytranslate, args.ztranslate],
-    );
-    crate::echo!(
-        quiet,
-        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
-        time.elapsed(),
-        mesh.number_of_elements(),
-        mesh.number_of_nodes()
-    );
-    finish(mesh, args, quiet)
+        crate::echo!(
+            quiet,
+            "     \x1b[1;96mMeshing\x1b[0m {} {}",
+            if polyhedral { "polyhedra" } else { "hexahedra" },
+            if self.uniform.is_some() {
+                "uniformly"
+            } else {
+                "adaptively"
+            }
+        );
+        time = Instant::now();
+        let (background, classes) = if let Some(spacing) = self.uniform {
+            tessellation.lattice_background(spacing)
+        } else {
+            let balancing = if self.strong {
+                Balancing::Strong(self.levels)
+            } else {
+                Balancing::Weak(self.levels)
+            };
+            if polyhedral {
+                tessellation.octree_background(balancing, self.octree_scale)
+            } else {
+                tessellation.dual_background(balancing, self.octree_scale)
+            }
+        }?;
+        let (elements, nodes) = retained(&background, &classes);
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{elements} elements, {nodes} nodes]\x1b[0m",
+            time.elapsed()
+        );
+
+        crate::echo!(
+            quiet,
+            "   \x1b[1;96mBuffering\x1b[0m polyhedra onto geometry"
+        );
+        time = Instant::now();
+        let mesh = if polyhedral {
+            tessellation.cut_polyhedral(background, &classes)
+        } else {
+            tessellation.cut(background, &classes)
+        }?;
+        let embedding = self.embedding(None);
+        let mesh = scaled(mesh, embedding.scale, embedding.translate);
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
+            time.elapsed(),
+            mesh.number_of_elements(),
+            mesh.number_of_nodes()
+        );
+        Ok(mesh)
+    }
 }
 
 /// Counts the background cells the cut will keep, and the nodes they use.
@@ -418,24 +491,15 @@ fn retained(background: &Mesh<3>, classes: &[Class]) -> (usize, usize) {
 }
 
 /// Zeroes out (treats as void) any voxels whose material is in `remove`.
-fn remove_materials(voxels: Voxels<u8>, remove: Option<&[usize]>) -> Voxels<u8> {
+fn remove_materials(
+    voxels: Voxels<Label>,
+    remove: Option<&[Label]>,
+) -> Result<Voxels<Label>, Error> {
     match remove {
-        Some(remove) if !remove.is_empty() => {
-            let nel = *voxels.nel();
-            let data = voxels
-                .data()
-                .iter()
-                .map(|&block| {
-                    if remove.contains(&(block as usize)) {
-                        0
-                    } else {
-                        block
-                    }
-                })
-                .collect();
-            Voxels::new(data, nel)
-        }
-        _ => voxels,
+        Some(remove) if !remove.is_empty() => relabel(&voxels, |block| {
+            Ok(if remove.contains(&block) { 0 } else { block })
+        }),
+        _ => Ok(voxels),
     }
 }
 
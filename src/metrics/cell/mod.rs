@@ -0,0 +1,213 @@
+use crate::{
+    smooth::points,
+    vector::{Point, add, angle, cross, dot, norm, normalized, scale, sub},
+};
+use conspire::geometry::mesh::{Connectivity, Mesh};
+use std::collections::HashMap;
+
+/// Quality metrics of a cell in the finite volume sense, defined for any
+/// polyhedron from the centroids and vector areas of its faces, with angles in
+/// degrees.  A metric of a surface element is NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Polyhedron {
+    Volume,
+    MaximumNonplanarity,
+    CentroidRatio,
+    MaximumNonOrthogonality,
+    MaximumSkewness,
+}
+
+/// A face of a cell, with its vector area pointing out of the cell.
+struct Face {
+    key: Vec<usize>,
+    centroid: Point,
+    area: Point,
+    nodes: Vec<Point>,
+}
+
+/// A cell from the pyramids between its faces and the average of its nodes.
+struct Cell {
+    centroid: Point,
+    volume: f64,
+    faces: Vec<Face>,
+}
+
+impl Polyhedron {
+    /// Evaluates the metric for each element of each block of a mesh.
+    pub(super) fn evaluate(self, mesh: &Mesh<3>) -> Vec<Vec<f64>> {
+        let coordinates = points(mesh.coordinates());
+        let cells: Vec<Vec<Option<Cell>>> = mesh
+            .iter()
+            .map(|block| match block {
+                Connectivity::Polygonal(_)
+                | Connectivity::Quadrilateral(_)
+                | Connectivity::Triangular(_) => {
+                    (0..block.number_of_elements()).map(|_| None).collect()
+                }
+                Connectivity::Hexahedral(_)
+                | Connectivity::Polyhedral(_)
+                | Connectivity::Pyramidal(_)
+                | Connectivity::Tetrahedral(_)
+                | Connectivity::Wedge(_) => block
+                    .into_iter()
+                    .map(|element| Some(Cell::new(block, element, &coordinates)))
+                    .collect(),
+            })
+            .collect();
+        let all: Vec<&Cell> = cells.iter().flatten().flatten().collect();
+        let mut owners: HashMap<&[usize], Vec<usize>> = HashMap::new();
+        if matches!(self, Self::MaximumNonOrthogonality | Self::MaximumSkewness) {
+            all.iter().enumerate().for_each(|(index, cell)| {
+                cell.faces
+                    .iter()
+                    .for_each(|face| owners.entry(&face.key).or_default().push(index))
+            });
+        }
+        let neighbor =
+            |index: usize, face: &Face| match owners.get(face.key.as_slice()).map(Vec::as_slice) {
+                Some(&[a, b]) => Some(all[if a == index { b } else { a }].centroid),
+                _ => None,
+            };
+        let mut index = 0;
+        cells
+            .iter()
+            .map(|block| {
+                block
+                    .iter()
+                    .map(|cell| match cell {
+                        Some(cell) => {
+                            let current = index;
+                            index += 1;
+                            self.cell(cell, |face| neighbor(current, face))
+                        }
+                        None => f64::NAN,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    fn cell(self, cell: &Cell, neighbor: impl Fn(&Face) -> Option<Point>) -> f64 {
+        match self {
+            Self::Volume => cell.volume,
+            Self::MaximumNonplanarity => cell
+                .faces
+                .iter()
+                .map(|face| {
+                    let normal = normalized(&face.area);
+                    face.nodes
+                        .iter()
+                        .map(|node| dot(&sub(node, &face.centroid), &normal).abs())
+                        .fold(0.0, f64::max)
+                        / norm(&face.area).sqrt()
+                })
+                .fold(0.0, f64::max),
+            Self::CentroidRatio => {
+                let distances: Vec<f64> = cell
+                    .faces
+                    .iter()
+                    .map(|face| {
+                        dot(
+                            &sub(&face.centroid, &cell.centroid),
+                            &normalized(&face.area),
+                        )
+                        .abs()
+                    })
+                    .collect();
+                distances.iter().copied().fold(f64::INFINITY, f64::min)
+                    / distances.iter().copied().fold(0.0, f64::max)
+            }
+            Self::MaximumNonOrthogonality => cell
+                .faces
+                .iter()
+                .map(|face| {
+                    let other = neighbor(face).unwrap_or(face.centroid);
+                    angle(&sub(&other, &cell.centroid), &face.area)
+                })
+                .fold(0.0, f64::max),
+            Self::MaximumSkewness => cell
+                .faces
+                .iter()
+                .filter_map(|face| {
+                    neighbor(face).map(|other| {
+                        let between = sub(&other, &cell.centroid);
+                        let along = dot(&sub(&face.centroid, &cell.centroid), &face.area)
+                            / dot(&between, &face.area);
+                        let intersection = add(&cell.centroid, &scale(&between, along));
+                        norm(&sub(&face.centroid, &intersection)) / norm(&between)
+                    })
+                })
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+impl Cell {
+    fn new(block: &Connectivity, element: &[usize], coordinates: &[Point]) -> Self {
+        let nodes = block.element_nodes(element);
+        let average = scale(
+            &nodes
+                .iter()
+                .fold([0.0; 3], |sum, &node| add(&sum, &coordinates[node])),
+            1.0 / nodes.len() as f64,
+        );
+        let faces: Vec<Face> = block
+            .element_faces(element)
+            .into_iter()
+            .map(|face| Face::new(face, coordinates, &average))
+            .collect();
+        let (volume, moment) = faces
+            .iter()
+            .fold((0.0, [0.0; 3]), |(volume, moment), face| {
+                let pyramid = dot(&sub(&face.centroid, &average), &face.area) / 3.0;
+                let centroid = add(&scale(&face.centroid, 0.75), &scale(&average, 0.25));
+                (volume + pyramid, add(&moment, &scale(&centroid, pyramid)))
+            });
+        let centroid = if volume.abs() > 0.0 {
+            scale(&moment, 1.0 / volume)
+        } else {
+            average
+        };
+        Self {
+            centroid,
+            volume,
+            faces,
+        }
+    }
+}
+
+impl Face {
+    fn new(mut key: Vec<usize>, coordinates: &[Point], inside: &Point) -> Self {
+        let nodes: Vec<Point> = key.iter().map(|&node| coordinates[node]).collect();
+        let average = scale(
+            &nodes.iter().fold([0.0; 3], |sum, node| add(&sum, node)),
+            1.0 / nodes.len() as f64,
+        );
+        let (mut area, weighted, total) =
+            (0..nodes.len()).fold(([0.0; 3], [0.0; 3], 0.0), |(area, weighted, total), k| {
+                let (a, b) = (&nodes[k], &nodes[(k + 1) % nodes.len()]);
+                let triangle = scale(&cross(&sub(a, &average), &sub(b, &average)), 0.5);
+                let magnitude = norm(&triangle);
+                let centroid = scale(&add(&add(a, b), &average), 1.0 / 3.0);
+                (
+                    add(&area, &triangle),
+                    add(&weighted, &scale(&centroid, magnitude)),
+                    total + magnitude,
+                )
+            });
+        let centroid = if total > 0.0 {
+            scale(&weighted, 1.0 / total)
+        } else {
+            average
+        };
+        if dot(&area, &sub(&centroid, inside)) < 0.0 {
+            area = scale(&area, -1.0)
+        }
+        key.sort_unstable();
+        Self {
+            key,
+            centroid,
+            area,
+            nodes,
+        }
+    }
+}
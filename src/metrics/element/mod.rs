@@ -0,0 +1,277 @@
+use crate::{
+    smooth::points,
+    vector::{Point, add, angle, cross, dot, norm, normalized, sub},
+};
+use conspire::geometry::mesh::{Connectivity, Mesh};
+
+/// Nodes at the ends of the three edges from each corner of a hexahedron,
+/// ordered so that the corner Jacobian of an ideal element is positive.
+const HEXAHEDRON_CORNERS: [[usize; 4]; 8] = [
+    [0, 1, 3, 4],
+    [1, 2, 0, 5],
+    [2, 3, 1, 6],
+    [3, 0, 2, 7],
+    [4, 7, 5, 0],
+    [5, 4, 6, 1],
+    [6, 5, 7, 2],
+    [7, 6, 4, 3],
+];
+
+const HEXAHEDRON_FACES: [[usize; 4]; 6] = [
+    [0, 3, 2, 1],
+    [4, 5, 6, 7],
+    [0, 1, 5, 4],
+    [1, 2, 6, 5],
+    [2, 3, 7, 6],
+    [3, 0, 4, 7],
+];
+
+const TETRAHEDRON_EDGES: [[usize; 4]; 6] = [
+    [0, 1, 2, 3],
+    [0, 2, 1, 3],
+    [0, 3, 1, 2],
+    [1, 2, 0, 3],
+    [1, 3, 0, 2],
+    [2, 3, 0, 1],
+];
+
+/// Quality metrics of an element beyond those of conspire, defined as in
+/// Verdict, with angles in degrees.  A metric that does not apply to a type
+/// of element is NaN, and a condition number of an inverted element infinite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Shape {
+    AspectRatio,
+    ConditionNumber,
+    JacobianRatio,
+    MinimumDihedralAngle,
+    MaximumDihedralAngle,
+    MinimumInteriorAngle,
+    MaximumInteriorAngle,
+    MaximumWarpage,
+}
+
+impl Shape {
+    /// Evaluates the metric for each element of each block of a mesh.
+    pub(super) fn evaluate(self, mesh: &Mesh<3>) -> Vec<Vec<f64>> {
+        let coordinates = points(mesh.coordinates());
+        let nodes = |element: &[usize]| -> Vec<Point> {
+            element.iter().map(|&node| coordinates[node]).collect()
+        };
+        mesh.iter()
+            .map(|block| match block {
+                Connectivity::Triangular(elements) => elements
+                    .iter()
+                    .map(|element| self.triangle(&nodes(element)))
+                    .collect(),
+                Connectivity::Quadrilateral(elements) => elements
+                    .iter()
+                    .map(|element| self.quadrilateral(&nodes(element)))
+                    .collect(),
+                Connectivity::Tetrahedral(elements) => elements
+                    .iter()
+                    .map(|element| self.tetrahedron(&nodes(element)))
+                    .collect(),
+                Connectivity::Hexahedral(elements) => elements
+                    .iter()
+                    .map(|element| self.hexahedron(&nodes(element)))
+                    .collect(),
+                Connectivity::Polygonal(_)
+                | Connectivity::Polyhedral(_)
+                | Connectivity::Pyramidal(_)
+                | Connectivity::Wedge(_) => vec![f64::NAN; block.number_of_elements()],
+            })
+            .collect()
+    }
+    fn triangle(self, p: &[Point]) -> f64 {
+        let lengths = [0, 1, 2].map(|i| norm(&sub(&p[(i + 1) % 3], &p[i])));
+        let area = 0.5 * norm(&cross(&sub(&p[1], &p[0]), &sub(&p[2], &p[0])));
+        let scale = 4.0 * 3.0_f64.sqrt() * area;
+        match self {
+            Self::AspectRatio => {
+                lengths.iter().copied().fold(0.0, f64::max) * lengths.iter().sum::<f64>() / scale
+            }
+            Self::ConditionNumber if area > 0.0 => {
+                lengths.iter().map(|length| length * length).sum::<f64>() / scale
+            }
+            Self::ConditionNumber => f64::INFINITY,
+            Self::JacobianRatio => ratio(&[area]),
+            Self::MinimumInteriorAngle => polygon_angles(p).into_iter().fold(180.0, f64::min),
+            Self::MaximumInteriorAngle => polygon_angles(p).into_iter().fold(0.0, f64::max),
+            _ => f64::NAN,
+        }
+    }
+    fn quadrilateral(self, p: &[Point]) -> f64 {
+        let normal = normalized(&cross(&sub(&p[2], &p[0]), &sub(&p[3], &p[1])));
+        let corners =
+            [0, 1, 2, 3].map(|k| (sub(&p[(k + 1) % 4], &p[k]), sub(&p[(k + 3) % 4], &p[k])));
+        let areas = corners.map(|(a, b)| dot(&cross(&a, &b), &normal));
+        match self {
+            Self::AspectRatio => {
+                let x = [
+                    norm(&add(&sub(&p[1], &p[0]), &sub(&p[2], &p[3]))),
+                    norm(&add(&sub(&p[2], &p[1]), &sub(&p[3], &p[0]))),
+                ];
+                x[0].max(x[1]) / x[0].min(x[1])
+            }
+            Self::ConditionNumber => corners
+                .iter()
+                .zip(areas)
+                .map(|((a, b), area)| match area > 0.0 {
+                    true => (dot(a, a) + dot(b, b)) / (2.0 * area),
+                    false => f64::INFINITY,
+                })
+                .fold(0.0, f64::max),
+            Self::JacobianRatio => ratio(&areas),
+            Self::MinimumInteriorAngle => polygon_angles(p).into_iter().fold(360.0, f64::min),
+            Self::MaximumInteriorAngle => polygon_angles(p).into_iter().fold(0.0, f64::max),
+            Self::MaximumWarpage => warpage(p),
+            _ => f64::NAN,
+        }
+    }
+    fn tetrahedron(self, p: &[Point]) -> f64 {
+        let columns = [1, 2, 3].map(|i| sub(&p[i], &p[0]));
+        let determinant = det(&columns);
+        match self {
+            Self::AspectRatio => {
+                let longest = TETRAHEDRON_EDGES
+                    .iter()
+                    .map(|&[i, j, _, _]| norm(&sub(&p[j], &p[i])))
+                    .fold(0.0, f64::max);
+                let areas: f64 = [[1, 2, 3], [0, 2, 3], [0, 1, 3], [0, 1, 2]]
+                    .iter()
+                    .map(|&[i, j, k]| 0.5 * norm(&cross(&sub(&p[j], &p[i]), &sub(&p[k], &p[i]))))
+                    .sum();
+                let inradius = 0.5 * determinant.abs() / areas;
+                longest / (2.0 * 6.0_f64.sqrt() * inradius)
+            }
+            Self::ConditionNumber if determinant > 0.0 => {
+                // Relative to the regular tetrahedron of unit edges.
+                let root = 3.0_f64.sqrt();
+                let weighted = [
+                    columns[0],
+                    [0, 1, 2].map(|i| (2.0 * columns[1][i] - columns[0][i]) / root),
+                    [0, 1, 2].map(|i| {
+                        (3.0 * columns[2][i] - columns[0][i] - columns[1][i])
+                            / (root * 2.0_f64.sqrt())
+                    }),
+                ];
+                condition(&weighted)
+            }
+            Self::ConditionNumber => f64::INFINITY,
+            Self::JacobianRatio => ratio(&[determinant]),
+            Self::MinimumDihedralAngle => dihedral_angles(p).into_iter().fold(180.0, f64::min),
+            Self::MaximumDihedralAngle => dihedral_angles(p).into_iter().fold(0.0, f64::max),
+            _ => f64::NAN,
+        }
+    }
+    fn hexahedron(self, p: &[Point]) -> f64 {
+        let corners = HEXAHEDRON_CORNERS
+            .map(|[k, a, b, c]| [sub(&p[a], &p[k]), sub(&p[b], &p[k]), sub(&p[c], &p[k])]);
+        match self {
+            Self::AspectRatio => {
+                let axes = [
+                    [[1, 0], [2, 3], [5, 4], [6, 7]],
+                    [[3, 0], [2, 1], [7, 4], [6, 5]],
+                    [[4, 0], [5, 1], [6, 2], [7, 3]],
+                ]
+                .map(|pairs| {
+                    norm(
+                        &pairs
+                            .iter()
+                            .fold([0.0; 3], |axis, &[a, b]| add(&axis, &sub(&p[a], &p[b]))),
+                    )
+                });
+                axes.iter().copied().fold(0.0, f64::max)
+                    / axes.iter().copied().fold(f64::INFINITY, f64::min)
+            }
+            Self::ConditionNumber => corners
+                .iter()
+                .map(|columns| match det(columns) > 0.0 {
+                    true => condition(columns),
+                    false => f64::INFINITY,
+                })
+                .fold(0.0, f64::max),
+            Self::JacobianRatio => ratio(&corners.map(|columns| det(&columns))),
+            Self::MaximumWarpage => HEXAHEDRON_FACES
+                .iter()
+                .map(|face| warpage(&face.map(|node| p[node])))
+                .fold(0.0, f64::max),
+            _ => f64::NAN,
+        }
+    }
+}
+
+/// Smallest over largest magnitude of the Jacobians at the corners, which is
+/// one for an ideal element and not positive for an inverted one.
+fn ratio(jacobians: &[f64]) -> f64 {
+    let largest = jacobians
+        .iter()
+        .map(|jacobian| jacobian.abs())
+        .fold(0.0, f64::max);
+    if largest > 0.0 {
+        jacobians.iter().copied().fold(f64::INFINITY, f64::min) / largest
+    } else {
+        0.0
+    }
+}
+
+/// Condition number of a corner Jacobian, which is one for an orthonormal one.
+fn condition(columns: &[Point; 3]) -> f64 {
+    let frobenius = columns
+        .iter()
+        .map(|column| dot(column, column))
+        .sum::<f64>();
+    let adjugate = [
+        cross(&columns[1], &columns[2]),
+        cross(&columns[2], &columns[0]),
+        cross(&columns[0], &columns[1]),
+    ];
+    let inverse = adjugate.iter().map(|row| dot(row, row)).sum::<f64>();
+    (frobenius * inverse).sqrt() / (3.0 * det(columns))
+}
+
+/// Interior angle at each corner of a polygon.
+fn polygon_angles(p: &[Point]) -> Vec<f64> {
+    (0..p.len())
+        .map(|k| {
+            angle(
+                &sub(&p[(k + 1) % p.len()], &p[k]),
+                &sub(&p[(k + p.len() - 1) % p.len()], &p[k]),
+            )
+        })
+        .collect()
+}
+
+/// Dihedral angle at each edge of a tetrahedron, between its two faces.
+fn dihedral_angles(p: &[Point]) -> Vec<f64> {
+    TETRAHEDRON_EDGES
+        .iter()
+        .map(|&[i, j, k, l]| {
+            let edge = normalized(&sub(&p[j], &p[i]));
+            let across = |node: usize| {
+                let toward = sub(&p[node], &p[i]);
+                let along = dot(&toward, &edge);
+                sub(&toward, &edge.map(|component| along * component))
+            };
+            angle(&across(k), &across(l))
+        })
+        .collect()
+}
+
+/// Warpage of a quadrilateral from the normals at opposite corners, which is
+/// zero for a planar one.
+fn warpage(p: &[Point]) -> f64 {
+    let normals = [0, 1, 2, 3].map(|k| {
+        normalized(&cross(
+            &sub(&p[(k + 1) % 4], &p[k]),
+            &sub(&p[(k + 3) % 4], &p[k]),
+        ))
+    });
+    1.0 - dot(&normals[0], &normals[2])
+        .min(dot(&normals[1], &normals[3]))
+        .powi(3)
+}
+
+fn det(columns: &[Point; 3]) -> f64 {
+    dot(&columns[0], &cross(&columns[1], &columns[2]))
+}
@@ -1,89 +1,634 @@
-use super::{ErrorWrapper, io::read_mesh};
+mod cell;
+mod element;
+
+use super::{Error, SetNames, io::write_mesh_fields};
+use cell::Polyhedron;
 use conspire::{
     geometry::mesh::{Mesh, Verdict},
     io::{Npy, Write},
 };
+use element::Shape;
 use std::{
+    fmt::{self, Display, Formatter},
     fs::File,
     io::{BufWriter, Write as WriteIO},
+    iter::{once, repeat_n},
+    str::FromStr,
     time::Instant,
 };
 
-#[derive(clap::Args)]
-pub struct MetricsArgs {
-    /// Mesh input file (exo | inp | stl | vtu)
-    #[arg(long, short, value_name = "FILE")]
-    pub input: String,
+fn flatten(metric: Vec<Vec<f64>>) -> Vec<f64> {
+    metric.into_iter().flatten().collect()
+}
 
-    /// Quality metrics output file (csv | npy)
-    #[arg(long, short, value_name = "FILE")]
-    pub output: String,
+/// Quality metrics of each element of a mesh, in element order.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    pub maximum_edge_ratios: Vec<f64>,
+    pub minimum_scaled_jacobians: Vec<f64>,
+    pub maximum_skews: Vec<f64>,
+    pub volumes: Vec<f64>,
 }
 
-pub fn metrics(args: MetricsArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    let mesh = read_mesh(&args.input, quiet, true)?;
-    write_metrics(&mesh, &args.output, quiet)
+impl From<&Mesh<3>> for Metrics {
+    fn from(mesh: &Mesh<3>) -> Self {
+        Self {
+            maximum_edge_ratios: flatten(mesh.maximum_edge_ratios()),
+            minimum_scaled_jacobians: flatten(mesh.minimum_scaled_jacobians()),
+            maximum_skews: flatten(mesh.maximum_skews()),
+            volumes: flatten(Metric::Volume.evaluate(mesh)),
+        }
+    }
 }
 
-fn flatten(metric: Vec<Vec<f64>>) -> Vec<f64> {
-    metric.into_iter().flatten().collect()
+/// A quality metric of each element, selected by its key.
+///
+/// A metric that does not apply to a type of element, such as a dihedral
+/// angle of a hexahedron, is NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    EdgeRatio,
+    ScaledJacobian,
+    Skew,
+    Volume,
+    AspectRatio,
+    ConditionNumber,
+    JacobianRatio,
+    MinimumDihedralAngle,
+    MaximumDihedralAngle,
+    MinimumInteriorAngle,
+    MaximumInteriorAngle,
+    MaximumWarpage,
+    MaximumNonplanarity,
+    CentroidRatio,
+    MaximumNonOrthogonality,
+    MaximumSkewness,
+}
+
+const ALL_METRICS: [Metric; 16] = [
+    Metric::EdgeRatio,
+    Metric::ScaledJacobian,
+    Metric::Skew,
+    Metric::Volume,
+    Metric::AspectRatio,
+    Metric::ConditionNumber,
+    Metric::JacobianRatio,
+    Metric::MinimumDihedralAngle,
+    Metric::MaximumDihedralAngle,
+    Metric::MinimumInteriorAngle,
+    Metric::MaximumInteriorAngle,
+    Metric::MaximumWarpage,
+    Metric::MaximumNonplanarity,
+    Metric::CentroidRatio,
+    Metric::MaximumNonOrthogonality,
+    Metric::MaximumSkewness,
+];
+
+/// Metrics written when no columns are selected.
+pub const METRICS_DEFAULT_COLUMNS: [Metric; 4] = [
+    Metric::EdgeRatio,
+    Metric::ScaledJacobian,
+    Metric::Skew,
+    Metric::Volume,
+];
+
+impl Metric {
+    /// Name of the metric, as in a csv header.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::EdgeRatio => "maximum edge ratio",
+            Self::ScaledJacobian => "minimum scaled jacobian",
+            Self::Skew => "maximum skew",
+            Self::Volume => "element volume",
+            Self::AspectRatio => "aspect ratio",
+            Self::ConditionNumber => "condition number",
+            Self::JacobianRatio => "jacobian ratio",
+            Self::MinimumDihedralAngle => "minimum dihedral angle",
+            Self::MaximumDihedralAngle => "maximum dihedral angle",
+            Self::MinimumInteriorAngle => "minimum interior angle",
+            Self::MaximumInteriorAngle => "maximum interior angle",
+            Self::MaximumWarpage => "maximum warpage",
+            Self::MaximumNonplanarity => "maximum face nonplanarity",
+            Self::CentroidRatio => "centroid face distance ratio",
+            Self::MaximumNonOrthogonality => "maximum non-orthogonality",
+            Self::MaximumSkewness => "maximum finite volume skewness",
+        }
+    }
+    /// Key of the metric, as given to select columns or in gates.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::EdgeRatio => "edge_ratio",
+            Self::ScaledJacobian => "jacobian",
+            Self::Skew => "skew",
+            Self::Volume => "volume",
+            Self::AspectRatio => "aspect_ratio",
+            Self::ConditionNumber => "condition",
+            Self::JacobianRatio => "jacobian_ratio",
+            Self::MinimumDihedralAngle => "min_dihedral",
+            Self::MaximumDihedralAngle => "max_dihedral",
+            Self::MinimumInteriorAngle => "min_angle",
+            Self::MaximumInteriorAngle => "max_angle",
+            Self::MaximumWarpage => "warpage",
+            Self::MaximumNonplanarity => "planarity",
+            Self::CentroidRatio => "centroid_ratio",
+            Self::MaximumNonOrthogonality => "non_orthogonality",
+            Self::MaximumSkewness => "fv_skewness",
+        }
+    }
+    /// Name of the metric as an element field.
+    pub fn field(self) -> &'static str {
+        match self {
+            Self::EdgeRatio => "maximum_edge_ratio",
+            Self::ScaledJacobian => "minimum_scaled_jacobian",
+            Self::Skew => "maximum_skew",
+            Self::Volume => "volume",
+            Self::AspectRatio => "aspect_ratio",
+            Self::ConditionNumber => "condition_number",
+            Self::JacobianRatio => "jacobian_ratio",
+            Self::MinimumDihedralAngle => "minimum_dihedral_angle",
+            Self::MaximumDihedralAngle => "maximum_dihedral_angle",
+            Self::MinimumInteriorAngle => "minimum_interior_angle",
+            Self::MaximumInteriorAngle => "maximum_interior_angle",
+            Self::MaximumWarpage => "maximum_warpage",
+            Self::MaximumNonplanarity => "maximum_face_nonplanarity",
+            Self::CentroidRatio => "centroid_face_distance_ratio",
+            Self::MaximumNonOrthogonality => "maximum_non_orthogonality",
+            Self::MaximumSkewness => "maximum_finite_volume_skewness",
+        }
+    }
+    /// The metric of each element of each block of a mesh.
+    pub fn evaluate(self, mesh: &Mesh<3>) -> Vec<Vec<f64>> {
+        match self {
+            Self::EdgeRatio => mesh.maximum_edge_ratios(),
+            Self::ScaledJacobian => mesh.minimum_scaled_jacobians(),
+            Self::Skew => mesh.maximum_skews(),
+            Self::Volume => mesh
+                .volumes()
+                .into_iter()
+                .zip(Polyhedron::Volume.evaluate(mesh))
+                .map(|(volumes, cells)| {
+                    volumes
+                        .into_iter()
+                        .zip(cells)
+                        .map(|(volume, cell)| if volume.is_nan() { cell } else { volume })
+                        .collect()
+                })
+                .collect(),
+            Self::AspectRatio => Shape::AspectRatio.evaluate(mesh),
+            Self::ConditionNumber => Shape::ConditionNumber.evaluate(mesh),
+            Self::JacobianRatio => Shape::JacobianRatio.evaluate(mesh),
+            Self::MinimumDihedralAngle => Shape::MinimumDihedralAngle.evaluate(mesh),
+            Self::MaximumDihedralAngle => Shape::MaximumDihedralAngle.evaluate(mesh),
+            Self::MinimumInteriorAngle => Shape::MinimumInteriorAngle.evaluate(mesh),
+            Self::MaximumInteriorAngle => Shape::MaximumInteriorAngle.evaluate(mesh),
+            Self::MaximumWarpage => Shape::MaximumWarpage.evaluate(mesh),
+            Self::MaximumNonplanarity => Polyhedron::MaximumNonplanarity.evaluate(mesh),
+            Self::CentroidRatio => Polyhedron::CentroidRatio.evaluate(mesh),
+            Self::MaximumNonOrthogonality => Polyhedron::MaximumNonOrthogonality.evaluate(mesh),
+            Self::MaximumSkewness => Polyhedron::MaximumSkewness.evaluate(mesh),
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+impl FromStr for Metric {
+    type Err = Error;
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        ALL_METRICS
+            .into_iter()
+            .find(|metric| metric.key() == key.trim())
+            .ok_or_else(|| {
+                Error::InvalidOption(format!("Metric {key} is not one of {}", metric_keys()))
+            })
+    }
+}
+
+fn metric_keys() -> String {
+    ALL_METRICS.map(Metric::key).join(", ")
+}
+
+/// Writes the quality metrics of each element of a mesh (csv | npy), or the
+/// mesh itself with the metrics as element fields (exo | vtu).
+pub fn write_metrics(mesh: &Mesh<3>, file: &str, quiet: bool) -> Result<(), Error> {
+    write_metric_columns(mesh, file, &METRICS_DEFAULT_COLUMNS, quiet)
+}
+
+/// Writes the quality metrics like [`write_metrics`], giving the sets of a
+/// mesh written with them their names.
+#[cfg(feature = "cli")]
+pub(crate) fn write_metrics_named(
+    mesh: &Mesh<3>,
+    file: &str,
+    names: &SetNames,
+    quiet: bool,
+) -> Result<(), Error> {
+    write_columns(mesh, file, &METRICS_DEFAULT_COLUMNS, names, quiet)
 }
 
-pub fn write_metrics(mesh: &Mesh<3>, file: &str, quiet: bool) -> Result<(), ErrorWrapper> {
+/// Writes the given quality metrics of each element of a mesh, as columns in
+/// the given order (csv | npy), or as element fields (exo | vtu).
+pub fn write_metric_columns(
+    mesh: &Mesh<3>,
+    file: &str,
+    columns: &[Metric],
+    quiet: bool,
+) -> Result<(), Error> {
+    write_columns(mesh, file, columns, &SetNames::default(), quiet)
+}
+
+fn write_columns(
+    mesh: &Mesh<3>,
+    file: &str,
+    columns: &[Metric],
+    names: &SetNames,
+    quiet: bool,
+) -> Result<(), Error> {
     crate::echo!(quiet, "     \x1b[1;96mMetrics\x1b[0m {file}");
+    if columns.is_empty() {
+        return Err(Error::InvalidOption(
+            "Metrics need at least one column".into(),
+        ));
+    }
     let time = Instant::now();
-    let maximum_edge_ratios = flatten(mesh.maximum_edge_ratios());
-    let minimum_scaled_jacobians = flatten(mesh.minimum_scaled_jacobians());
-    let maximum_skews = flatten(mesh.maximum_skews());
-    let volumes = flatten(mesh.volumes());
+    let values: Vec<Vec<f64>> = columns
+        .iter()
+        .map(|metric| flatten(metric.evaluate(mesh)))
+        .collect();
+    let rows = mesh.number_of_elements();
     let extension = super::io::extension(file);
     match extension {
         Some("csv") => {
             let mut writer = BufWriter::new(File::create(file)?);
-            writer.write_all(
-                b"maximum edge ratio,minimum scaled jacobian,maximum skew,element volume\n",
-            )?;
-            for (((ratio, jacobian), skew), volume) in maximum_edge_ratios
-                .iter()
-                .zip(minimum_scaled_jacobians.iter())
-                .zip(maximum_skews.iter())
-                .zip(volumes.iter())
-            {
-                writer.write_all(
-                    format!("{ratio:>10.6e},{jacobian:>10.6e},{skew:>10.6e},{volume:>10.6e}\n")
-                        .as_bytes(),
-                )?;
+            let header: Vec<&str> = columns.iter().map(|metric| metric.name()).collect();
+            writeln!(writer, "{}", header.join(","))?;
+            for row in 0..rows {
+                let line: Vec<String> = values
+                    .iter()
+                    .map(|column| format!("{:>10.6e}", column[row]))
+                    .collect();
+                writeln!(writer, "{}", line.join(","))?;
             }
             writer.flush()?;
         }
-        Some("npy") => {
-            let rows = maximum_edge_ratios.len();
-            let mut data = Vec::with_capacity(rows * 4);
-            for (((ratio, jacobian), skew), volume) in maximum_edge_ratios
+        Some("npy") => Npy {
+            data: (0..rows)
+                .flat_map(|row| values.iter().map(move |column| column[row]))
+                .collect(),
+            shape: vec![rows, columns.len()],
+            fortran_order: false,
+        }
+        .write(file)?,
+        Some("exo" | "vtu") => {
+            let fields: Vec<(&str, Vec<f64>)> = columns
                 .iter()
-                .zip(minimum_scaled_jacobians.iter())
-                .zip(maximum_skews.iter())
-                .zip(volumes.iter())
-            {
-                data.push(*ratio);
-                data.push(*jacobian);
-                data.push(*skew);
-                data.push(*volume);
-            }
-            Npy {
-                data,
-                shape: vec![rows, 4],
-                fortran_order: false,
-            }
-            .write(file)?;
+                .map(|metric| metric.field())
+                .zip(values)
+                .collect();
+            write_mesh_fields(file, mesh, names, &fields)?
         }
         _ => {
-            return Err(ErrorWrapper::from(format!(
-                "Unsupported metrics extension .{} (use csv or npy)",
-                extension.unwrap_or("UNDEFINED")
-            )));
+            return Err(super::io::invalid_output(file, extension));
         }
     }
     crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
     Ok(())
 }
+
+pub const REPORT_DEFAULT_BINS: usize = 10;
+pub const REPORT_DEFAULT_WORST: usize = 10;
+
+const BAR_WIDTH: usize = 40;
+const PERCENTILES: [f64; 3] = [5.0, 50.0, 95.0];
+
+/// A bound on a quality metric that every element must meet, written as
+/// `METRIC>=VALUE`, with any of `<`, `<=`, `>`, or `>=`, and the key of a
+/// [`Metric`], such as `jacobian`. An element the metric does not apply to,
+/// whose value is NaN, is left out of the gate and counted separately.
+#[derive(Clone, Debug)]
+pub struct Gate {
+    metric: Metric,
+    above: bool,
+    inclusive: bool,
+    value: f64,
+}
+
+impl Gate {
+    fn passes(&self, value: f64) -> bool {
+        match (self.above, self.inclusive) {
+            (true, true) => value >= self.value,
+            (true, false) => value > self.value,
+            (false, true) => value <= self.value,
+            (false, false) => value < self.value,
+        }
+    }
+    /// How far a value is beyond the bound.
+    fn violation(&self, value: f64) -> f64 {
+        (value - self.value).abs()
+    }
+}
+
+impl Display for Gate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let operator = match (self.above, self.inclusive) {
+            (true, true) => ">=",
+            (true, false) => ">",
+            (false, true) => "<=",
+            (false, false) => "<",
+        };
+        write!(f, "{}{operator}{}", self.metric, self.value)
+    }
+}
+
+impl FromStr for Gate {
+    type Err = Error;
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::InvalidOption(format!(
+                "Gate {spec} is not METRIC<VALUE, METRIC<=VALUE, METRIC>VALUE, or METRIC>=VALUE, with METRIC one of {}",
+                metric_keys()
+            ))
+        };
+        let at = spec.find(['<', '>']).ok_or_else(invalid)?;
+        let (key, rest) = spec.split_at(at);
+        let above = rest.starts_with('>');
+        let (inclusive, value) = match rest[1..].strip_prefix('=') {
+            Some(value) => (true, value),
+            None => (false, &rest[1..]),
+        };
+        let metric = key.parse().map_err(|_| invalid())?;
+        let value = value.trim().parse().map_err(|_| invalid())?;
+        Ok(Self {
+            metric,
+            above,
+            inclusive,
+            value,
+        })
+    }
+}
+
+/// Options for a report of the quality of a mesh, with statistics and
+/// histograms of each metric, and gates that every element must pass.
+///
+/// ```no_run
+/// use automesh::{ReportOptions, read_mesh};
+///
+/// let mesh = read_mesh("model.inp", true, false)?;
+/// ReportOptions::default()
+///     .gate("jacobian>=0.2".parse()?)
+///     .worst(5)
+///     .report(&mesh, false)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReportOptions {
+    bins: usize,
+    worst: usize,
+    statistics: bool,
+    columns: Vec<Metric>,
+    gates: Vec<Gate>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            bins: REPORT_DEFAULT_BINS,
+            worst: REPORT_DEFAULT_WORST,
+            statistics: true,
+            columns: METRICS_DEFAULT_COLUMNS.to_vec(),
+            gates: Vec::new(),
+        }
+    }
+}
+
+impl ReportOptions {
+    /// Number of bins of each histogram.
+    pub fn bins(mut self, bins: usize) -> Self {
+        self.bins = bins;
+        self
+    }
+    /// Number of the worst elements listed for each gate.
+    pub fn worst(mut self, worst: usize) -> Self {
+        self.worst = worst;
+        self
+    }
+    /// Whether to report the statistics and histograms of each metric, or
+    /// only the gates.
+    pub fn statistics(mut self, statistics: bool) -> Self {
+        self.statistics = statistics;
+        self
+    }
+    /// Metrics to report the statistics and histograms of.
+    pub fn columns(mut self, columns: Vec<Metric>) -> Self {
+        self.columns = columns;
+        self
+    }
+    /// Adds a gate that every element must pass.
+    pub fn gate(mut self, gate: Gate) -> Self {
+        self.gates.push(gate);
+        self
+    }
+    /// Reports on the quality of a mesh, and fails if any element fails a
+    /// gate.
+    pub fn report(&self, mesh: &Mesh<3>, quiet: bool) -> Result<(), Error> {
+        if self.bins == 0 {
+            return Err(Error::InvalidOption(
+                "Histograms need at least one bin".into(),
+            ));
+        }
+        let mut columns: Vec<(Metric, Vec<Vec<f64>>)> = Vec::new();
+        self.columns
+            .iter()
+            .chain(self.gates.iter().map(|gate| &gate.metric))
+            .for_each(|&metric| {
+                if columns.iter().all(|(column, _)| *column != metric) {
+                    columns.push((metric, metric.evaluate(mesh)))
+                }
+            });
+        let numbers: Vec<usize> = (0..mesh.number_of_element_blocks())
+            .map(|block| mesh.blocks().map_or(block + 1, |numbers| numbers[block]))
+            .collect();
+        crate::echo!(
+            quiet,
+            "      \x1b[1;96mReport\x1b[0m {} elements in {} blocks",
+            mesh.number_of_elements(),
+            numbers.len()
+        );
+        if self.statistics {
+            self.summarize(&columns, &numbers, quiet);
+            self.histograms(&columns, quiet);
+        }
+        if self.gates.is_empty() {
+            return Ok(());
+        }
+        let blocks: Vec<usize> = mesh
+            .iter()
+            .zip(&numbers)
+            .flat_map(|(block, &number)| repeat_n(number, block.number_of_elements()))
+            .collect();
+        let centroids = mesh.centroids();
+        let mut failed = 0;
+        for gate in &self.gates {
+            let values = flatten(column(&columns, gate.metric).to_vec());
+            let skipped = values.iter().filter(|value| value.is_nan()).count();
+            let skipped = match skipped {
+                0 => String::new(),
+                skipped => {
+                    format!(" \x1b[2m[{skipped} elements not applicable]\x1b[0m")
+                }
+            };
+            let mut failures: Vec<usize> = (0..values.len())
+                .filter(|&element| !values[element].is_nan() && !gate.passes(values[element]))
+                .collect();
+            if failures.is_empty() {
+                crate::echo!(
+                    quiet,
+                    "        \x1b[1;96mGate\x1b[0m {gate} \x1b[1;92mpassed\x1b[0m{skipped}"
+                );
+                continue;
+            }
+            failed += 1;
+            crate::echo!(
+                quiet,
+                "        \x1b[1;96mGate\x1b[0m {gate} \x1b[1;91mfailed\x1b[0m by {} of {} elements{skipped}",
+                failures.len(),
+                values.len()
+            );
+            failures.sort_by(|&a, &b| {
+                gate.violation(values[b])
+                    .total_cmp(&gate.violation(values[a]))
+            });
+            failures.iter().take(self.worst).for_each(|&element| {
+                let centroid = &centroids[element];
+                crate::echo!(
+                    quiet,
+                    "             element {} in block {} \x1b[91m{:.6e}\x1b[0m \
+                    \x1b[2mat [{:.6e}, {:.6e}, {:.6e}]\x1b[0m",
+                    element + 1,
+                    blocks[element],
+                    values[element],
+                    centroid[0],
+                    centroid[1],
+                    centroid[2]
+                )
+            });
+        }
+        match failed {
+            0 => Ok(()),
+            failed => Err(Error::Mesh(format!(
+                "{failed} of {} quality gates failed",
+                self.gates.len()
+            ))),
+        }
+    }
+    /// Prints the minimum, percentiles, maximum, and mean of each metric,
+    /// over all the elements and over each block.
+    fn summarize(&self, columns: &[(Metric, Vec<Vec<f64>>)], numbers: &[usize], quiet: bool) {
+        let percentiles: String = PERCENTILES
+            .iter()
+            .map(|percentile| format!("{:>12}", format!("p{percentile}")))
+            .collect();
+        crate::echo!(
+            quiet,
+            "             \x1b[1m{:<32}{:>6}{:>12}{percentiles}{:>12}{:>12}\x1b[0m",
+            "metric",
+            "block",
+            "minimum",
+            "maximum",
+            "mean"
+        );
+        for &metric in &self.columns {
+            let blocks = column(columns, metric);
+            let all: Vec<f64> = blocks.iter().flatten().copied().collect();
+            let rows = once(("all".to_string(), all)).chain(
+                (blocks.len() > 1)
+                    .then(|| {
+                        blocks
+                            .iter()
+                            .zip(numbers)
+                            .map(|(values, number)| (number.to_string(), values.clone()))
+                    })
+                    .into_iter()
+                    .flatten(),
+            );
+            for (block, values) in rows {
+                let mut sorted: Vec<f64> = values
+                    .into_iter()
+                    .filter(|value| value.is_finite())
+                    .collect();
+                if sorted.is_empty() {
+                    continue;
+                }
+                sorted.sort_by(f64::total_cmp);
+                let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+                let percentiles: String = PERCENTILES
+                    .iter()
+                    .map(|&percentile| format!("{:>12.4e}", percentile_of(&sorted, percentile)))
+                    .collect();
+                crate::echo!(
+                    quiet,
+                    "             {:<32}{block:>6}{:>12.4e}{percentiles}{:>12.4e}{mean:>12.4e}",
+                    metric.name(),
+                    sorted[0],
+                    sorted[sorted.len() - 1]
+                );
+            }
+        }
+    }
+    /// Prints a histogram of each metric over all the elements.
+    fn histograms(&self, columns: &[(Metric, Vec<Vec<f64>>)], quiet: bool) {
+        for &metric in &self.columns {
+            let values: Vec<f64> = column(columns, metric)
+                .iter()
+                .flatten()
+                .copied()
+                .filter(|value| value.is_finite())
+                .collect();
+            let Some(minimum) = values.iter().copied().reduce(f64::min) else {
+                continue;
+            };
+            let maximum = values.iter().copied().fold(minimum, f64::max);
+            let bins = if maximum > minimum { self.bins } else { 1 };
+            let width = (maximum - minimum) / bins as f64;
+            let mut counts = vec![0_usize; bins];
+            values.iter().for_each(|value| {
+                let bin = if width > 0.0 {
+                    (((value - minimum) / width) as usize).min(bins - 1)
+                } else {
+                    0
+                };
+                counts[bin] += 1
+            });
+            let most = counts.iter().copied().max().unwrap_or(0).max(1);
+            crate::echo!(quiet, "             \x1b[1m{}\x1b[0m", metric.name());
+            counts.iter().enumerate().for_each(|(bin, &count)| {
+                let low = minimum + bin as f64 * width;
+                let bar = "\u{2588}".repeat((count * BAR_WIDTH).div_ceil(most));
+                crate::echo!(
+                    quiet,
+                    "             {low:>12.4e} {:>12.4e} {bar:<BAR_WIDTH$} {count}",
+                    low + width
+                )
+            });
+        }
+    }
+}
+
+/// Values of a metric among those evaluated.
+fn column(columns: &[(Metric, Vec<Vec<f64>>)], metric: Metric) -> &[Vec<f64>] {
+    columns
+        .iter()
+        .find(|(column, _)| *column == metric)
+        .map(|(_, values)| values.as_slice())
+        .expect("every reported metric is evaluated")
+}
+
+/// Percentile of sorted values, by the nearest rank.
+fn percentile_of(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
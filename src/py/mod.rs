@@ -0,0 +1,529 @@
+//! Python bindings, built by maturin with the `python` feature.
+//!
+//! Segmentations come in as 3D integer numpy arrays indexed `[x, y, z]`, and
+//! meshes hand back their nodes, elements, and blocks as numpy arrays, so a
+//! notebook never has to round-trip through files.
+
+use super::{
+    Element, Error, FeatureOptions, ImproveOptions, Label, MeshOptions, Metric, RemeshOptions,
+    ReportOptions, SetNames, Sizing, SmoothOptions,
+    improve::{IMPROVE_DEFAULT_ITERS, IMPROVE_DEFAULT_TARGET},
+    io::{narrow, read_mesh, write_mesh_borrowed},
+    metrics::{METRICS_DEFAULT_COLUMNS, REPORT_DEFAULT_BINS, REPORT_DEFAULT_WORST},
+    remesh::{ADAPTIVE_DEFAULT_GRADATION, ADAPTIVE_DEFAULT_TOLERANCE, REMESH_DEFAULT_ITERS},
+    smooth::{
+        HC_DEFAULT_ALPHA, HC_DEFAULT_BETA, TAUBIN_DEFAULT_BAND, TAUBIN_DEFAULT_ITERS,
+        TAUBIN_DEFAULT_SCALE,
+    },
+    write_metrics,
+};
+use conspire::{
+    geometry::{
+        Coordinate, Coordinates,
+        grid::Voxels,
+        mesh::{Connectivities, Connectivity, Mesh as MeshBase, PrimitiveConnectivity},
+    },
+    math::Tensor,
+};
+use numpy::{
+    PyArray1, PyArray2, PyArray3, PyArrayMethods, PyReadonlyArray2, PyReadonlyArray3,
+    npyffi::NPY_ORDER,
+};
+use pyo3::{
+    exceptions::{PyOSError, PyRuntimeError, PyTypeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use std::collections::BTreeMap;
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> PyErr {
+        match error {
+            Error::Io(_) => PyOSError::new_err(error.to_string()),
+            Error::Mesh(_) => PyRuntimeError::new_err(error.to_string()),
+            _ => PyValueError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// Reads labels of any integer dtype, erroring on those outside of [`Label`].
+fn voxels(labels: &Bound<'_, PyAny>) -> PyResult<Voxels<Label>> {
+    macro_rules! extract {
+        ($($dtype:ty),*) => {$(
+            if let Ok(labels) = labels.extract::<PyReadonlyArray3<'_, $dtype>>() {
+                let labels = labels.as_array();
+                let shape = labels.shape();
+                let voxels = Voxels::new_row_major(
+                    labels.iter().copied().collect(),
+                    [shape[0], shape[1], shape[2]],
+                );
+                return Ok(narrow(&voxels, "array")?);
+            }
+        )*};
+    }
+    extract!(u8, u16, u32, u64, i8, i16, i32, i64);
+    Err(PyTypeError::new_err(
+        "Labels must be a 3D numpy array of integers",
+    ))
+}
+
+fn labels<'py>(py: Python<'py>, voxels: &Voxels<Label>) -> PyResult<Bound<'py, PyArray3<Label>>> {
+    PyArray1::from_vec(py, voxels.data_col_major().into_owned())
+        .reshape_with_order(*voxels.nel(), NPY_ORDER::NPY_FORTRANORDER)
+}
+
+fn element(element: &str) -> PyResult<Element> {
+    match element {
+        "hex" => Ok(Element::Hexahedra),
+        "hexdom" => Ok(Element::HexDominant),
+        "poly" => Ok(Element::Polyhedra),
+        "tet" => Ok(Element::Tetrahedra),
+        "tri" => Ok(Element::Triangles),
+        _ => Err(PyValueError::new_err(format!(
+            "Invalid element {element} specified"
+        ))),
+    }
+}
+
+fn parse_columns(columns: Option<Vec<String>>) -> PyResult<Vec<Metric>> {
+    match columns {
+        Some(columns) => Ok(columns
+            .iter()
+            .map(|column| column.parse())
+            .collect::<Result<_, Error>>()?),
+        None => Ok(METRICS_DEFAULT_COLUMNS.to_vec()),
+    }
+}
+
+/// A finite element mesh.
+#[pyclass(module = "automesh", unsendable)]
+pub struct Mesh {
+    /// Only empty after a remesh has failed, since remeshing consumes the mesh.
+    mesh: Option<MeshBase<3>>,
+}
+
+fn lost() -> PyErr {
+    PyRuntimeError::new_err("Mesh was lost to a failed remesh")
+}
+
+impl Mesh {
+    fn inner(&self) -> PyResult<&MeshBase<3>> {
+        self.mesh.as_ref().ok_or_else(lost)
+    }
+    fn inner_mut(&mut self) -> PyResult<&mut MeshBase<3>> {
+        self.mesh.as_mut().ok_or_else(lost)
+    }
+}
+
+impl From<MeshBase<3>> for Mesh {
+    fn from(mesh: MeshBase<3>) -> Self {
+        Self { mesh: Some(mesh) }
+    }
+}
+
+#[pymethods]
+impl Mesh {
+    /// Builds a mesh from nodes (n, 3), elements (m, 3 | 4 | 8) of triangles,
+    /// tetrahedra, or hexahedra, and optional block IDs (m,) of each element.
+    /// Elements are grouped by block, in increasing block order.
+    #[new]
+    #[pyo3(signature = (nodes, elements, blocks=None))]
+    fn new(
+        nodes: PyReadonlyArray2<'_, f64>,
+        elements: PyReadonlyArray2<'_, i64>,
+        blocks: Option<Vec<usize>>,
+    ) -> PyResult<Self> {
+        let nodes = nodes.as_array();
+        if nodes.ncols() != 3 {
+            return Err(PyValueError::new_err("Nodes must have shape (n, 3)"));
+        }
+        let coordinates: Coordinates<3> = nodes
+            .rows()
+            .into_iter()
+            .map(|node| Coordinate::from([node[0], node[1], node[2]]))
+            .collect();
+        let elements = elements.as_array();
+        let blocks = blocks.unwrap_or_else(|| vec![1; elements.nrows()]);
+        if blocks.len() != elements.nrows() {
+            return Err(PyValueError::new_err(
+                "Blocks must have one entry per element",
+            ));
+        }
+        let mut grouped = BTreeMap::<usize, Vec<Vec<usize>>>::new();
+        for (element, block) in elements.rows().into_iter().zip(blocks) {
+            let element = element
+                .iter()
+                .map(|&node| {
+                    usize::try_from(node)
+                        .ok()
+                        .filter(|&node| node < coordinates.len())
+                        .ok_or_else(|| {
+                            PyValueError::new_err(format!("Node {node} is out of range"))
+                        })
+                })
+                .collect::<PyResult<Vec<usize>>>()?;
+            grouped.entry(block).or_default().push(element);
+        }
+        let (numbers, connectivities): (Vec<usize>, Vec<Connectivity>) = grouped
+            .into_iter()
+            .map(|(block, elements)| Ok((block, connectivity(elements)?)))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        Ok(MeshBase::from((
+            Connectivities::from((connectivities, numbers)),
+            coordinates.into(),
+        ))
+        .into())
+    }
+
+    /// Meshes a segmentation, a 3D integer array indexed [x, y, z].
+    #[staticmethod]
+    #[pyo3(signature = (
+        labels,
+        element="hex",
+        remove=None,
+        scale=[1.0; 3],
+        translate=[0.0; 3],
+        defeature=None,
+        quiet=true,
+    ))]
+    fn from_segmentation(
+        labels: &Bound<'_, PyAny>,
+        element: &str,
+        remove: Option<Vec<Label>>,
+        scale: [f64; 3],
+        translate: [f64; 3],
+        defeature: Option<usize>,
+        quiet: bool,
+    ) -> PyResult<Self> {
+        let [xscale, yscale, zscale] = scale;
+        let [xtranslate, ytranslate, ztranslate] = translate;
+        Ok(MeshOptions::new(self::element(element)?)
+            .remove(remove)
+            .xscale(xscale)
+            .yscale(yscale)
+            .zscale(zscale)
+            .xtranslate(xtranslate)
+            .ytranslate(ytranslate)
+            .ztranslate(ztranslate)
+            .defeature(defeature)
+            .mesh_voxels(voxels(labels)?, quiet)?
+            .into())
+    }
+
+    /// Reads a mesh file (exo | inp | stl | vtu).
+    #[staticmethod]
+    #[pyo3(signature = (file, quiet=true))]
+    fn read(file: &str, quiet: bool) -> PyResult<Self> {
+        Ok(read_mesh(file, quiet, false)?.into())
+    }
+
+    /// Writes the mesh to a file (exo | inp | mesh | stl | vtu), with its
+    /// quality metrics as element fields if requested (exo | vtu).
+    #[pyo3(signature = (file, metrics=false, quiet=true))]
+    fn write(&mut self, file: &str, metrics: bool, quiet: bool) -> PyResult<()> {
+        if metrics {
+            Ok(write_metrics(self.inner()?, file, quiet)?)
+        } else {
+            Ok(write_mesh_borrowed(
+                file,
+                self.inner_mut()?,
+                &SetNames::default(),
+                quiet,
+            )?)
+        }
+    }
+
+    /// Nodal coordinates, with shape (n, 3).
+    #[getter]
+    fn nodes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let coordinates = self.inner()?.coordinates();
+        PyArray1::from_vec(
+            py,
+            coordinates
+                .iter()
+                .flat_map(|coordinate| [coordinate[0], coordinate[1], coordinate[2]])
+                .collect(),
+        )
+        .reshape([coordinates.len(), 3])
+    }
+
+    /// Element connectivity, with shape (m, nodes per element).
+    #[getter]
+    fn elements<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<i64>>> {
+        let mesh = self.inner()?;
+        let mut width = None;
+        let mut nodes = Vec::new();
+        for block in mesh.connectivities() {
+            for element in block {
+                let element = block.element_nodes(element);
+                if *width.get_or_insert(element.len()) != element.len() {
+                    return Err(PyValueError::new_err(
+                        "Elements have differing numbers of nodes",
+                    ));
+                }
+                nodes.extend(element.into_iter().map(|node| node as i64));
+            }
+        }
+        PyArray1::from_vec(py, nodes).reshape([mesh.number_of_elements(), width.unwrap_or(0)])
+    }
+
+    /// Block ID of each element, with shape (m,).
+    #[getter]
+    fn blocks<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray1<i64>>> {
+        let mesh = self.inner()?;
+        let numbers = mesh.blocks();
+        Ok(PyArray1::from_vec(
+            py,
+            mesh.connectivities()
+                .iter()
+                .enumerate()
+                .flat_map(|(index, block)| {
+                    let number = numbers.map_or(index + 1, |numbers| numbers[index]);
+                    vec![number as i64; block.number_of_elements()]
+                })
+                .collect(),
+        ))
+    }
+
+    /// Smooths the mesh in place.
+    #[pyo3(signature = (
+        method="taubin",
+        iterations=TAUBIN_DEFAULT_ITERS,
+        pass_band=TAUBIN_DEFAULT_BAND,
+        scale=TAUBIN_DEFAULT_SCALE,
+        weighting="uniform",
+        alpha=HC_DEFAULT_ALPHA,
+        beta=HC_DEFAULT_BETA,
+        hierarchical=false,
+        preserve_boundary=false,
+        preserve_interfaces=false,
+        freeze=Vec::new(),
+        guard=None,
+        preserve_volume=None,
+        feature_angle=None,
+        features=None,
+        quiet=true,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn smooth(
+        &mut self,
+        method: &str,
+        iterations: usize,
+        pass_band: f64,
+        scale: f64,
+        weighting: &str,
+        alpha: f64,
+        beta: f64,
+        hierarchical: bool,
+        preserve_boundary: bool,
+        preserve_interfaces: bool,
+        freeze: Vec<usize>,
+        guard: Option<f64>,
+        preserve_volume: Option<f64>,
+        feature_angle: Option<f64>,
+        features: Option<String>,
+        quiet: bool,
+    ) -> PyResult<()> {
+        Ok(SmoothOptions::default()
+            .method(method.parse()?)
+            .iterations(iterations)
+            .pass_band(pass_band)
+            .scale(scale)
+            .weighting(weighting.parse()?)
+            .alpha(alpha)
+            .beta(beta)
+            .preserve_boundary(hierarchical || preserve_boundary)
+            .preserve_interfaces(hierarchical || preserve_interfaces)
+            .freeze(freeze)
+            .guard(guard)
+            .preserve_volume(preserve_volume)
+            .features(
+                FeatureOptions::default()
+                    .angle(feature_angle)
+                    .polylines(features),
+            )
+            .smooth(self.inner_mut()?, quiet)?)
+    }
+
+    /// Untangles and improves the quality of a hexahedral or tetrahedral mesh
+    /// in place.
+    #[pyo3(signature = (
+        iterations=IMPROVE_DEFAULT_ITERS,
+        target=IMPROVE_DEFAULT_TARGET,
+        quiet=true,
+    ))]
+    fn improve(&mut self, iterations: usize, target: f64, quiet: bool) -> PyResult<()> {
+        Ok(ImproveOptions::default()
+            .iterations(iterations)
+            .target(target)
+            .improve(self.inner_mut()?, quiet)?)
+    }
+
+    /// Remeshes a triangular mesh in place, from a size field (npy | vtu) if
+    /// given one, adaptively or anisotropically if given a minimum and maximum
+    /// edge length, and otherwise uniformly.
+    #[pyo3(signature = (
+        iterations=REMESH_DEFAULT_ITERS,
+        size=None,
+        minimum=None,
+        maximum=None,
+        tolerance=ADAPTIVE_DEFAULT_TOLERANCE,
+        gradation=ADAPTIVE_DEFAULT_GRADATION,
+        anisotropic=false,
+        field=None,
+        array=None,
+        feature_angle=None,
+        features=None,
+        quiet=true,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn remesh(
+        &mut self,
+        iterations: usize,
+        size: Option<f64>,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+        tolerance: f64,
+        gradation: f64,
+        anisotropic: bool,
+        field: Option<String>,
+        array: Option<String>,
+        feature_angle: Option<f64>,
+        features: Option<String>,
+        quiet: bool,
+    ) -> PyResult<()> {
+        let sizing = match field {
+            Some(file) => Sizing::Field { file, array },
+            None if anisotropic => {
+                Sizing::bounded(size, minimum, maximum, tolerance, gradation)?.anisotropic()?
+            }
+            None => Sizing::bounded(size, minimum, maximum, tolerance, gradation)?,
+        };
+        let mesh = self.mesh.take().ok_or_else(lost)?;
+        self.mesh = Some(
+            RemeshOptions::default()
+                .iterations(iterations)
+                .sizing(sizing)
+                .features(
+                    FeatureOptions::default()
+                        .angle(feature_angle)
+                        .polylines(features),
+                )
+                .remesh(mesh, quiet)?,
+        );
+        Ok(())
+    }
+
+    /// Quality metrics of each element, as a dict of arrays, of the given
+    /// metrics (such as `"condition"`) or of the default ones.
+    #[pyo3(signature = (columns=None))]
+    fn metrics<'py>(
+        &self,
+        py: Python<'py>,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let mesh = self.inner()?;
+        let metrics = PyDict::new(py);
+        for metric in parse_columns(columns)? {
+            let values = metric.evaluate(mesh).into_iter().flatten().collect();
+            metrics.set_item(metric.field(), PyArray1::from_vec(py, values))?;
+        }
+        Ok(metrics)
+    }
+
+    /// Reports statistics and histograms of the quality metrics, and raises
+    /// if any element fails a gate such as `"jacobian>=0.2"`.
+    #[pyo3(signature = (
+        gates=Vec::new(),
+        columns=None,
+        worst=REPORT_DEFAULT_WORST,
+        bins=REPORT_DEFAULT_BINS,
+        statistics=true,
+        quiet=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn report(
+        &self,
+        gates: Vec<String>,
+        columns: Option<Vec<String>>,
+        worst: usize,
+        bins: usize,
+        statistics: bool,
+        quiet: bool,
+    ) -> PyResult<()> {
+        Ok(gates
+            .iter()
+            .try_fold(
+                ReportOptions::default()
+                    .bins(bins)
+                    .worst(worst)
+                    .statistics(statistics)
+                    .columns(parse_columns(columns)?),
+                |options, gate| Ok::<_, Error>(options.gate(gate.parse()?)),
+            )?
+            .report(self.inner()?, quiet)?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let mesh = self.inner()?;
+        Ok(format!(
+            "Mesh({} elements, {} nodes)",
+            mesh.number_of_elements(),
+            mesh.number_of_nodes()
+        ))
+    }
+}
+
+fn connectivity(elements: Vec<Vec<usize>>) -> PyResult<Connectivity> {
+    fn fixed<const N: usize>(elements: Vec<Vec<usize>>) -> Vec<[usize; N]> {
+        elements
+            .into_iter()
+            .map(|element| element.try_into().unwrap())
+            .collect()
+    }
+    match elements.first().map_or(0, Vec::len) {
+        3 => Ok(Connectivity::Triangular(PrimitiveConnectivity::from(
+            fixed(elements),
+        ))),
+        4 => Ok(Connectivity::Tetrahedral(PrimitiveConnectivity::from(
+            fixed(elements),
+        ))),
+        8 => Ok(Connectivity::Hexahedral(PrimitiveConnectivity::from(
+            fixed(elements),
+        ))),
+        nodes => Err(PyValueError::new_err(format!(
+            "Elements with {nodes} nodes are not supported"
+        ))),
+    }
+}
+
+/// Defeatures clusters with less than `minimum` voxels from a segmentation.
+#[pyfunction]
+fn defeature<'py>(
+    py: Python<'py>,
+    labels: &Bound<'py, PyAny>,
+    minimum: usize,
+) -> PyResult<Bound<'py, PyArray3<Label>>> {
+    self::labels(py, &voxels(labels)?.defeature(minimum))
+}
+
+/// Runs the command line interface, for the `automesh` console script.
+#[pyfunction]
+fn main(py: Python<'_>) -> PyResult<()> {
+    let args: Vec<String> = py.import("sys")?.getattr("argv")?.extract()?;
+    if let Err(error) = super::cli::run(args) {
+        eprintln!("Error: {error:?}");
+        std::process::exit(1)
+    }
+    Ok(())
+}
+
+#[pymodule]
+fn automesh(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Mesh>()?;
+    module.add_function(wrap_pyfunction!(defeature, module)?)?;
+    module.add_function(wrap_pyfunction!(main, module)?)?;
+    Ok(())
+}
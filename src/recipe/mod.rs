@@ -0,0 +1,407 @@
+use super::{
+    Element, Error, FeatureOptions, ImproveOptions, MeshOptions, RemeshOptions, SetOptions, Sizing,
+    SmoothOptions, SmoothingMethod, SmoothingWeighting,
+    improve::{IMPROVE_DEFAULT_ITERS, IMPROVE_DEFAULT_TARGET},
+    io::{Label, extension, invalid_input, write_mesh_borrowed},
+    mesh::{OCTREE_DEFAULT_LEVELS, OCTREE_DEFAULT_SCALE},
+    metrics::write_metrics,
+    remesh::{ADAPTIVE_DEFAULT_GRADATION, ADAPTIVE_DEFAULT_TOLERANCE, REMESH_DEFAULT_ITERS},
+    smooth::{
+        HC_DEFAULT_ALPHA, HC_DEFAULT_BETA, TAUBIN_DEFAULT_BAND, TAUBIN_DEFAULT_ITERS,
+        TAUBIN_DEFAULT_SCALE,
+    },
+};
+use conspire::geometry::mesh::Mesh;
+use serde::{Deserialize, Serialize};
+use std::{fs::read_to_string, path::Path};
+
+/// A reproducible pipeline run, read from a recipe file (toml).
+///
+/// The input is meshed, passed through each of the stages in order, tagged
+/// with any sidesets and nodesets, and then written to every output. Relative
+/// paths are taken from the directory of the recipe.
+///
+/// ```toml
+/// input = "letter_f_3d.npy"
+/// remove = [0]
+/// outputs = ["letter_f_3d.exo", "letter_f_3d.vtu"]
+/// metrics = "letter_f_3d.csv"
+///
+/// [mesh]
+/// element = "hex"
+///
+/// [[stages]]
+/// stage = "smooth"
+/// method = "taubin"
+/// hierarchical = true
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    /// Segmentation (nii | npy | spn | tif) or tessellation (stl) input file.
+    pub input: String,
+    /// Number of voxels in each direction (spn).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nel: Option<[usize; 3]>,
+    /// Defeature clusters with less than this many voxels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defeature: Option<usize>,
+    /// Voxel IDs to remove from the mesh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remove: Option<Vec<Label>>,
+    /// Scaling in each direction [default: 1.0 or nii header].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<[f64; 3]>,
+    /// Translation in each direction [default: 0.0 or nii header].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<[f64; 3]>,
+    /// Sidesets to tag, as in `--sideset`.
+    #[serde(default)]
+    pub sidesets: Vec<String>,
+    /// Nodesets to tag, as in `--nodeset`.
+    #[serde(default)]
+    pub nodesets: Vec<String>,
+    /// Quality metrics output file (csv | npy).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<String>,
+    /// Mesh output files (exo | inp | mesh | stl | vtu).
+    pub outputs: Vec<String>,
+    /// Meshing of the input.
+    pub mesh: MeshStage,
+    /// Smoothing, improvement, and remeshing, applied in order after meshing.
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+}
+
+/// Meshing options of a [`Recipe`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshStage {
+    /// Type of elements (hex | hexdom | poly | tet | tri).
+    pub element: Element,
+    /// Octree refinement scale (stl).
+    #[serde(default = "octree_scale")]
+    pub octree_scale: f64,
+    /// Uniform lattice cell size instead of an octree (stl).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uniform: Option<f64>,
+    /// Chord-error tolerance for curvature-driven refinement (stl).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    /// Strong instead of weak balancing (stl).
+    #[serde(default)]
+    pub strong: bool,
+    /// Snap the buffer layer onto the surface (stl).
+    #[serde(default)]
+    pub snap: bool,
+    /// Level difference allowed between neighboring octree cells (poly).
+    #[serde(default = "levels")]
+    pub levels: usize,
+}
+
+/// A stage of a [`Recipe`] applied after meshing.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "lowercase", tag = "stage")]
+pub enum Stage {
+    /// Smooths the mesh, as in `smooth`.
+    Smooth {
+        #[serde(default = "method")]
+        method: SmoothingMethod,
+        #[serde(default = "smooth_iterations")]
+        iterations: usize,
+        #[serde(default = "pass_band")]
+        pass_band: f64,
+        #[serde(default = "smooth_scale")]
+        scale: f64,
+        #[serde(default)]
+        weighting: SmoothingWeighting,
+        #[serde(default = "alpha")]
+        alpha: f64,
+        #[serde(default = "beta")]
+        beta: f64,
+        #[serde(default)]
+        hierarchical: bool,
+        #[serde(default)]
+        preserve_boundary: bool,
+        #[serde(default)]
+        preserve_interfaces: bool,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        freeze: Vec<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        guard: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preserve_volume: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        feature_angle: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        features: Option<String>,
+    },
+    /// Untangles and improves the quality of the mesh, as in `improve`.
+    Improve {
+        #[serde(default = "improve_iterations")]
+        iterations: usize,
+        #[serde(default = "improve_target")]
+        target: f64,
+    },
+    /// Remeshes the mesh, as in `remesh`, from a size field if given one,
+    /// adaptively or anisotropically if given a minimum and maximum edge
+    /// length, and otherwise uniformly.
+    Remesh {
+        #[serde(default = "remesh_iterations")]
+        iterations: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        minimum: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        maximum: Option<f64>,
+        #[serde(default = "tolerance")]
+        tolerance: f64,
+        #[serde(default = "gradation")]
+        gradation: f64,
+        #[serde(default)]
+        anisotropic: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        field: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        array: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        feature_angle: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        features: Option<String>,
+    },
+}
+
+fn octree_scale() -> f64 {
+    OCTREE_DEFAULT_SCALE
+}
+
+fn levels() -> usize {
+    OCTREE_DEFAULT_LEVELS
+}
+
+fn method() -> SmoothingMethod {
+    SmoothingMethod::Taubin
+}
+
+fn smooth_iterations() -> usize {
+    TAUBIN_DEFAULT_ITERS
+}
+
+fn pass_band() -> f64 {
+    TAUBIN_DEFAULT_BAND
+}
+
+fn smooth_scale() -> f64 {
+    TAUBIN_DEFAULT_SCALE
+}
+
+fn alpha() -> f64 {
+    HC_DEFAULT_ALPHA
+}
+
+fn beta() -> f64 {
+    HC_DEFAULT_BETA
+}
+
+fn improve_iterations() -> usize {
+    IMPROVE_DEFAULT_ITERS
+}
+
+fn improve_target() -> f64 {
+    IMPROVE_DEFAULT_TARGET
+}
+
+fn remesh_iterations() -> usize {
+    REMESH_DEFAULT_ITERS
+}
+
+fn tolerance() -> f64 {
+    ADAPTIVE_DEFAULT_TOLERANCE
+}
+
+fn gradation() -> f64 {
+    ADAPTIVE_DEFAULT_GRADATION
+}
+
+/// Takes a relative path from the given directory.
+pub(crate) fn resolve(directory: &Path, file: &str) -> String {
+    if Path::new(file).is_absolute() {
+        file.to_string()
+    } else {
+        directory.join(file).to_string_lossy().into_owned()
+    }
+}
+
+impl Recipe {
+    /// Reads a recipe file (toml), resolving its paths.
+    pub fn read(file: &str) -> Result<Self, Error> {
+        if extension(file) != Some("toml") {
+            return Err(invalid_input(file, extension(file)));
+        }
+        let mut recipe: Self = toml::from_str(&read_to_string(file)?).map_err(|error| {
+            Error::InvalidData(format!(
+                "Recipe {file} is invalid: {}",
+                error.message().trim_end_matches('\n')
+            ))
+        })?;
+        let directory = Path::new(file).parent().unwrap_or(Path::new(""));
+        recipe.input = resolve(directory, &recipe.input);
+        recipe.metrics = recipe.metrics.map(|file| resolve(directory, &file));
+        recipe.outputs = recipe
+            .outputs
+            .iter()
+            .map(|file| resolve(directory, file))
+            .collect();
+        recipe.stages.iter_mut().for_each(|stage| {
+            let (field, features) = match stage {
+                Stage::Smooth { features, .. } => (None, features),
+                Stage::Remesh {
+                    field, features, ..
+                } => (field.as_mut(), features),
+                Stage::Improve { .. } => return,
+            };
+            field
+                .into_iter()
+                .chain(features.as_mut())
+                .for_each(|file| *file = resolve(directory, file))
+        });
+        Ok(recipe)
+    }
+
+    /// Runs every step of the recipe, logging it first as resolved, and
+    /// returns the final mesh.
+    pub fn run(&self, quiet: bool) -> Result<Mesh<3>, Error> {
+        if self.outputs.is_empty() {
+            return Err(Error::InvalidOption(
+                "Recipe needs at least one output".into(),
+            ));
+        }
+        let resolved = toml::to_string(self)
+            .map_err(|error| Error::InvalidData(format!("Recipe is invalid: {error}")))?;
+        crate::log::write_log("Recipe:");
+        resolved.lines().for_each(|line| match line {
+            "" => crate::log::write_log(""),
+            line => crate::log::write_log(&format!("    {line}")),
+        });
+        crate::log::write_log("");
+        let [xscale, yscale, zscale] = self.scale.map_or([None; 3], |scale| scale.map(Some));
+        let [xtranslate, ytranslate, ztranslate] = self
+            .translate
+            .map_or([None; 3], |translate| translate.map(Some));
+        let (mut mesh, mut names) = MeshOptions::new(self.mesh.element)
+            .defeature(self.defeature)
+            .nel(self.nel)
+            .remove(self.remove.clone())
+            .xscale(xscale)
+            .yscale(yscale)
+            .zscale(zscale)
+            .xtranslate(xtranslate)
+            .ytranslate(ytranslate)
+            .ztranslate(ztranslate)
+            .octree_scale(self.mesh.octree_scale)
+            .uniform(self.mesh.uniform)
+            .tolerance(self.mesh.tolerance)
+            .strong(self.mesh.strong)
+            .snap(self.mesh.snap)
+            .levels(self.mesh.levels)
+            .sets(
+                self.outputs
+                    .iter()
+                    .any(|output| matches!(extension(output), Some("exo") | Some("inp"))),
+            )
+            .mesh_named(&self.input, quiet)?;
+        for stage in &self.stages {
+            match *stage {
+                Stage::Smooth {
+                    method,
+                    iterations,
+                    pass_band,
+                    scale,
+                    weighting,
+                    alpha,
+                    beta,
+                    hierarchical,
+                    preserve_boundary,
+                    preserve_interfaces,
+                    ref freeze,
+                    guard,
+                    preserve_volume,
+                    feature_angle,
+                    ref features,
+                } => SmoothOptions::default()
+                    .method(method)
+                    .iterations(iterations)
+                    .pass_band(pass_band)
+                    .scale(scale)
+                    .weighting(weighting)
+                    .alpha(alpha)
+                    .beta(beta)
+                    .preserve_boundary(hierarchical || preserve_boundary)
+                    .preserve_interfaces(hierarchical || preserve_interfaces)
+                    .freeze(freeze.clone())
+                    .guard(guard)
+                    .preserve_volume(preserve_volume)
+                    .features(
+                        FeatureOptions::default()
+                            .angle(feature_angle)
+                            .polylines(features.clone()),
+                    )
+                    .smooth(&mut mesh, quiet)?,
+                Stage::Improve { iterations, target } => ImproveOptions::default()
+                    .iterations(iterations)
+                    .target(target)
+                    .improve(&mut mesh, quiet)?,
+                Stage::Remesh {
+                    iterations,
+                    size,
+                    minimum,
+                    maximum,
+                    tolerance,
+                    gradation,
+                    anisotropic,
+                    ref field,
+                    ref array,
+                    feature_angle,
+                    ref features,
+                } => {
+                    let sizing = match field {
+                        Some(file) => Sizing::Field {
+                            file: file.clone(),
+                            array: array.clone(),
+                        },
+                        None if anisotropic => {
+                            Sizing::bounded(size, minimum, maximum, tolerance, gradation)?
+                                .anisotropic()?
+                        }
+                        None => Sizing::bounded(size, minimum, maximum, tolerance, gradation)?,
+                    };
+                    mesh = RemeshOptions::default()
+                        .iterations(iterations)
+                        .sizing(sizing)
+                        .features(
+                            FeatureOptions::default()
+                                .angle(feature_angle)
+                                .polylines(features.clone()),
+                        )
+                        .remesh(mesh, quiet)?
+                }
+            }
+        }
+        let sets = self
+            .sidesets
+            .iter()
+            .fold(SetOptions::default(), |options, spec| options.sideset(spec));
+        self.nodesets
+            .iter()
+            .fold(sets, |options, spec| options.nodeset(spec))
+            .tag(&mut mesh, &mut names, quiet)?;
+        if let Some(file) = &self.metrics {
+            write_metrics(&mesh, file, quiet)?;
+        }
+        self.outputs
+            .iter()
+            .try_for_each(|output| write_mesh_borrowed(output, &mut mesh, &names, quiet))?;
+        Ok(mesh)
+    }
+}
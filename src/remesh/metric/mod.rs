@@ -0,0 +1,785 @@
+use crate::{
+    Error, FeatureOptions,
+    feature::{closest, edge},
+    smooth::solve,
+    vector::{Point, add, cross, direction, distance, dot, scale, sub},
+};
+use conspire::{
+    geometry::{
+        Coordinate,
+        bvh::BoundingVolumeHierarchy,
+        mesh::{Connectivity, Mesh},
+    },
+    math::Tensor,
+};
+use std::collections::{HashMap, HashSet};
+
+const SPLIT_ABOVE: f64 = 4.0 / 3.0;
+const COLLAPSE_BELOW: f64 = 4.0 / 5.0;
+
+/// Number of passes of splitting within each iteration.
+const SPLIT_PASSES: usize = 8;
+
+/// A symmetric tensor measuring edges in units of their target length.
+type Metric = [[f64; 3]; 3];
+
+/// Target edge lengths of a remeshed surface.
+pub(super) enum Target {
+    /// One edge length over the whole surface.
+    Uniform(f64),
+    /// Edge length from the largest principal curvature, as for the adaptive
+    /// sizing of conspire.
+    Adaptive {
+        minimum: f64,
+        maximum: f64,
+        tolerance: f64,
+        gradation: f64,
+    },
+    /// Edge length at each node of the original surface, interpolated over
+    /// its triangles.
+    Field(Vec<f64>),
+    /// Edge lengths along the principal curvature directions, each from the
+    /// chord tolerance and the curvature in that direction.
+    Curvature {
+        minimum: f64,
+        maximum: f64,
+        tolerance: f64,
+    },
+}
+
+/// A triangulated surface being remeshed.
+struct Surface {
+    triangles: Vec<[usize; 3]>,
+    points: Vec<Point>,
+    metrics: Vec<Metric>,
+    /// Edges along sharp features, kept as edges of the surface.
+    features: HashSet<(usize, usize)>,
+    /// Nodes that never move or go away.
+    corners: Vec<bool>,
+}
+
+/// Remeshes a triangular surface toward edges of unit length in the metric
+/// of the target, by splitting long edges, collapsing short edges, flipping
+/// edges toward regular valences, relaxing nodes tangentially, and projecting
+/// them back onto the original surface, in each iteration.
+///
+/// Nodes on the given feature edges move only along them, and project back
+/// onto them, while corners of the features and of the boundary stay put.
+pub(super) fn remesh(
+    mesh: Mesh<3>,
+    iterations: usize,
+    target: &Target,
+    features: &FeatureOptions,
+    edges: HashSet<(usize, usize)>,
+) -> Result<Mesh<3>, Error> {
+    let triangles: Vec<[usize; 3]> = match mesh.connectivities() {
+        [Connectivity::Triangular(elements)] => elements
+            .iter()
+            .map(|element| [element[0], element[1], element[2]])
+            .collect(),
+        [_] => return Err(Error::Mesh("Can only remesh triangular meshes".into())),
+        _ => return Err(Error::Mesh("Can only remesh lone blocks for now".into())),
+    };
+    if let Target::Field(sizes) = target
+        && sizes.len() != mesh.number_of_nodes()
+    {
+        return Err(Error::InvalidData(format!(
+            "Size field has {} values for {} nodes",
+            sizes.len(),
+            mesh.number_of_nodes()
+        )));
+    }
+    let bvh = BoundingVolumeHierarchy::from(&mesh);
+    let elements: Vec<&[usize]> = mesh.connectivities().iter().flatten().collect();
+    let original = mesh.coordinates();
+    let points: Vec<Point> = original
+        .iter()
+        .map(|coordinate| [coordinate[0], coordinate[1], coordinate[2]])
+        .collect();
+    let segments: Vec<[Point; 2]> = edges.iter().map(|&(u, v)| [points[u], points[v]]).collect();
+    let lines: HashSet<(usize, usize)> = edge_faces(&triangles)
+        .into_iter()
+        .filter(|(_, faces)| faces.len() == 1)
+        .map(|(edge, _)| edge)
+        .chain(edges.iter().copied())
+        .collect();
+    let mut surface = Surface {
+        corners: features.corners(&lines, &points),
+        metrics: Vec::new(),
+        triangles,
+        points,
+        features: edges,
+    };
+    let project = |point: &Point| {
+        bvh.closest_point(&Coordinate::from(*point), original, &elements)
+            .map(|(closest, element)| ([closest[0], closest[1], closest[2]], element))
+    };
+    for _ in 0..iterations {
+        surface.metrics = match target {
+            Target::Uniform(size) => vec![isotropic(*size); surface.points.len()],
+            Target::Adaptive {
+                minimum,
+                maximum,
+                tolerance,
+                gradation,
+            } => {
+                let mut sizes: Vec<f64> = principal_curvatures(&surface.triangles, &surface.points)
+                    .into_iter()
+                    .map(|principal| {
+                        principal.map_or(*maximum, |principal| {
+                            let curvature = principal.curvatures[0]
+                                .abs()
+                                .max(principal.curvatures[1].abs());
+                            dunyach_length(curvature, *tolerance, *minimum, *maximum)
+                        })
+                    })
+                    .collect();
+                graduate(&mut sizes, &surface.triangles, &surface.points, *gradation);
+                sizes.into_iter().map(isotropic).collect()
+            }
+            Target::Field(sizes) => surface
+                .points
+                .iter()
+                .map(|point| {
+                    let size = project(point).map_or(sizes[0], |(closest, element)| {
+                        let nodes = elements[element];
+                        let corners = [0, 1, 2].map(|corner| {
+                            let node = &original[nodes[corner]];
+                            [node[0], node[1], node[2]]
+                        });
+                        let weights = barycentric(&closest, &corners);
+                        (0..3)
+                            .map(|corner| weights[corner] * sizes[nodes[corner]])
+                            .sum()
+                    });
+                    isotropic(size)
+                })
+                .collect(),
+            Target::Curvature {
+                minimum,
+                maximum,
+                tolerance,
+            } => principal_curvatures(&surface.triangles, &surface.points)
+                .into_iter()
+                .map(|principal| {
+                    principal.map_or(isotropic(*maximum), |principal| {
+                        anisotropic(&principal, *minimum, *maximum, *tolerance)
+                    })
+                })
+                .collect(),
+        };
+        for _ in 0..SPLIT_PASSES {
+            if surface.split_long_edges() == 0 {
+                break;
+            }
+        }
+        surface.collapse_short_edges();
+        surface.flip_edges()?;
+        surface.tangential_smooth();
+        let on_feature = surface.on_feature();
+        let Surface {
+            points, corners, ..
+        } = &mut surface;
+        points
+            .iter_mut()
+            .zip(corners.iter())
+            .zip(on_feature)
+            .filter(|((_, corner), _)| !**corner)
+            .for_each(|((point, _), on_feature)| {
+                if on_feature {
+                    *point = segments
+                        .iter()
+                        .map(|segment| closest(point, segment))
+                        .min_by(|a, b| distance(a, point).total_cmp(&distance(b, point)))
+                        .unwrap_or(*point)
+                } else if let Some((closest, _)) = project(point) {
+                    *point = closest
+                }
+            });
+    }
+    Ok(Mesh::from((
+        vec![Connectivity::Triangular(surface.triangles.into())],
+        surface.points.into_iter().map(Coordinate::from).collect(),
+    )))
+}
+
+fn isotropic(size: f64) -> Metric {
+    let scale = 1.0 / (size * size);
+    [[scale, 0.0, 0.0], [0.0, scale, 0.0], [0.0, 0.0, scale]]
+}
+
+/// Metric with edge lengths along the principal curvature directions from
+/// the chord tolerance, and across the surface the shorter of the two.
+fn anisotropic(principal: &Principal, minimum: f64, maximum: f64, tolerance: f64) -> Metric {
+    let lengths = principal
+        .curvatures
+        .map(|curvature| dunyach_length(curvature.abs(), tolerance, minimum, maximum));
+    let across = lengths[0].min(lengths[1]);
+    let mut metric = [[0.0; 3]; 3];
+    principal
+        .directions
+        .iter()
+        .zip(lengths)
+        .chain([(&principal.normal, across)])
+        .for_each(|(direction, length)| {
+            let weight = 1.0 / (length * length);
+            for i in 0..3 {
+                for j in 0..3 {
+                    metric[i][j] += weight * direction[i] * direction[j];
+                }
+            }
+        });
+    metric
+}
+
+/// Length of an edge in the average metric of its nodes.
+fn metric_length(points: &[Point], metrics: &[Metric], u: usize, v: usize) -> f64 {
+    let edge = sub(&points[v], &points[u]);
+    let mut squared = 0.0;
+    for i in 0..3 {
+        for j in 0..3 {
+            squared += edge[i] * 0.5 * (metrics[u][i][j] + metrics[v][i][j]) * edge[j];
+        }
+    }
+    squared.max(0.0).sqrt()
+}
+
+fn average(a: &Metric, b: &Metric) -> Metric {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| 0.5 * (a[i][j] + b[i][j])))
+}
+
+/// Principal curvatures and directions of a surface at a node.
+struct Principal {
+    normal: Point,
+    curvatures: [f64; 2],
+    directions: [Point; 2],
+}
+
+/// Principal curvatures of each node, from a quadric fitted to its neighbors
+/// in a frame about its normal, if there are enough of them.
+fn principal_curvatures(triangles: &[[usize; 3]], points: &[Point]) -> Vec<Option<Principal>> {
+    let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); points.len()];
+    let mut normals = vec![[0.0; 3]; points.len()];
+    triangles.iter().for_each(|&[a, b, c]| {
+        let normal = normal(&points[a], &points[b], &points[c]);
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            neighbors[u].insert(v);
+            neighbors[v].insert(u);
+        }
+        [a, b, c]
+            .into_iter()
+            .for_each(|node| (0..3).for_each(|i| normals[node][i] += normal[i]));
+    });
+    (0..points.len())
+        .map(|node| {
+            let normal = direction(&normals[node])?;
+            let mut ring: HashSet<usize> = neighbors[node].clone();
+            if ring.len() < 5 {
+                neighbors[node].iter().for_each(|&neighbor| {
+                    ring.extend(neighbors[neighbor].iter().filter(|&&other| other != node))
+                });
+            }
+            let axis = if normal[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            let first = direction(&sub(&axis, &scale(&normal, dot(&axis, &normal))))?;
+            let second = cross(&normal, &first);
+            let samples: Vec<[f64; 3]> = ring
+                .iter()
+                .map(|&other| {
+                    let offset = sub(&points[other], &points[node]);
+                    [
+                        dot(&offset, &first),
+                        dot(&offset, &second),
+                        dot(&offset, &normal),
+                    ]
+                })
+                .collect();
+            let unknowns = if samples.len() >= 5 { 5 } else { 3 };
+            if samples.len() < unknowns {
+                return None;
+            }
+            let mut matrix = vec![vec![0.0; unknowns]; unknowns];
+            let mut vector = vec![0.0; unknowns];
+            samples.iter().for_each(|&[u, v, w]| {
+                let row = [u * u, u * v, v * v, u, v];
+                for i in 0..unknowns {
+                    for j in 0..unknowns {
+                        matrix[i][j] += row[i] * row[j];
+                    }
+                    vector[i] += row[i] * w;
+                }
+            });
+            let coefficients = solve(matrix, vector);
+            let (a, b, c) = (
+                2.0 * coefficients[0],
+                coefficients[1],
+                2.0 * coefficients[2],
+            );
+            let mean = 0.5 * (a + c);
+            let radius = (0.25 * (a - c) * (a - c) + b * b).sqrt();
+            let angle = 0.5 * (2.0 * b).atan2(a - c);
+            let (cosine, sine) = (angle.cos(), angle.sin());
+            Some(Principal {
+                normal,
+                curvatures: [mean + radius, mean - radius],
+                directions: [
+                    add(&scale(&first, cosine), &scale(&second, sine)),
+                    add(&scale(&first, -sine), &scale(&second, cosine)),
+                ],
+            })
+        })
+        .collect()
+}
+
+/// Edge length keeping the chord error within the tolerance on a curve of
+/// the given curvature, as for the adaptive sizing of conspire.
+fn dunyach_length(curvature: f64, tolerance: f64, minimum: f64, maximum: f64) -> f64 {
+    if curvature <= 0.0 {
+        return maximum;
+    }
+    let argument = 6.0 * tolerance / curvature - 3.0 * tolerance * tolerance;
+    let length = if argument > 0.0 {
+        argument.sqrt()
+    } else {
+        minimum
+    };
+    length.clamp(minimum, maximum)
+}
+
+/// Limits how fast sizes grow away from small ones, as in conspire.
+///
+/// Each pass relaxes every edge, so as for shortest paths the sizes settle
+/// within one pass per node, and a pass without change ends it sooner.
+fn graduate(sizes: &mut [f64], triangles: &[[usize; 3]], points: &[Point], gradation: f64) {
+    for _ in 0..points.len() {
+        let mut changed = false;
+        for &[a, b, c] in triangles {
+            for (i, j) in [(a, b), (b, c), (c, a)] {
+                let slope = gradation * distance(&points[j], &points[i]);
+                if sizes[i] + slope < sizes[j] {
+                    sizes[j] = sizes[i] + slope;
+                    changed = true;
+                } else if sizes[j] + slope < sizes[i] {
+                    sizes[i] = sizes[j] + slope;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn edge_faces(triangles: &[[usize; 3]]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    triangles.iter().enumerate().for_each(|(face, &[a, b, c])| {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            edges.entry(edge(u, v)).or_default().push(face);
+        }
+    });
+    edges
+}
+
+impl Surface {
+    /// Whether each node is on a feature edge.
+    fn on_feature(&self) -> Vec<bool> {
+        let mut on_feature = vec![false; self.points.len()];
+        self.features.iter().for_each(|&(u, v)| {
+            on_feature[u] = true;
+            on_feature[v] = true;
+        });
+        on_feature
+    }
+    /// Splits the edges longer than the target at their midpoints, longest
+    /// first and at most one per triangle, returning the number split.
+    fn split_long_edges(&mut self) -> usize {
+        let Self {
+            triangles,
+            points,
+            metrics,
+            features,
+            corners,
+        } = self;
+        let edges = edge_faces(triangles);
+        let mut long: Vec<((usize, usize), f64)> = edges
+            .keys()
+            .map(|&(u, v)| ((u, v), metric_length(points, metrics, u, v)))
+            .filter(|(_, length)| *length > SPLIT_ABOVE)
+            .collect();
+        long.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut touched = vec![false; triangles.len()];
+        let mut split = 0;
+        for ((u, v), _) in long {
+            let faces = &edges[&(u, v)];
+            if faces.iter().any(|&face| touched[face]) {
+                continue;
+            }
+            let midpoint = points.len();
+            points.push([0, 1, 2].map(|i| 0.5 * (points[u][i] + points[v][i])));
+            metrics.push(average(&metrics[u], &metrics[v]));
+            corners.push(false);
+            if features.remove(&(u, v)) {
+                features.insert(edge(u, midpoint));
+                features.insert(edge(midpoint, v));
+            }
+            for &face in faces {
+                let original = triangles[face];
+                triangles[face] = original.map(|node| if node == v { midpoint } else { node });
+                triangles.push(original.map(|node| if node == u { midpoint } else { node }));
+                touched[face] = true;
+            }
+            split += 1;
+        }
+        split
+    }
+    /// Collapses the edges shorter than the target, shortest first, unless
+    /// that would change the topology, cut across a feature or the boundary,
+    /// move a corner, make edges longer than the target, or fold a triangle
+    /// over.
+    fn collapse_short_edges(&mut self) {
+        let on_feature = self.on_feature();
+        let Self {
+            triangles,
+            points,
+            metrics,
+            features,
+            corners,
+        } = self;
+        let vertices = points.len();
+        let edges = edge_faces(triangles);
+        let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); vertices];
+        let mut vertex_faces: Vec<Vec<usize>> = vec![Vec::new(); vertices];
+        triangles.iter().enumerate().for_each(|(face, &[a, b, c])| {
+            for (u, v) in [(a, b), (b, c), (c, a)] {
+                neighbors[u].insert(v);
+                neighbors[v].insert(u);
+            }
+            [a, b, c]
+                .into_iter()
+                .for_each(|node| vertex_faces[node].push(face));
+        });
+        let mut on_line = on_feature;
+        edges
+            .iter()
+            .filter(|(_, faces)| faces.len() == 1)
+            .for_each(|(&(u, v), _)| {
+                on_line[u] = true;
+                on_line[v] = true;
+            });
+        let mut short: Vec<((usize, usize), f64)> = edges
+            .keys()
+            .map(|&(u, v)| ((u, v), metric_length(points, metrics, u, v)))
+            .filter(|(_, length)| *length < COLLAPSE_BELOW)
+            .collect();
+        short.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let mut merge: Vec<usize> = (0..vertices).collect();
+        let mut touched = vec![false; vertices];
+        for ((u, v), _) in short {
+            if touched[u] || touched[v] || (corners[u] && corners[v]) {
+                continue;
+            }
+            let faces = &edges[&(u, v)];
+            let opposites: HashSet<usize> = faces
+                .iter()
+                .filter_map(|&face| {
+                    triangles[face]
+                        .into_iter()
+                        .find(|&node| node != u && node != v)
+                })
+                .collect();
+            if neighbors[u]
+                .intersection(&neighbors[v])
+                .copied()
+                .collect::<HashSet<usize>>()
+                != opposites
+            {
+                continue;
+            }
+            let midpoint = [0, 1, 2].map(|i| 0.5 * (points[u][i] + points[v][i]));
+            let along_line = faces.len() == 1 || features.contains(&(u, v));
+            let (survivor, removed, position) = match (on_line[u], on_line[v]) {
+                (false, false) => (u, v, midpoint),
+                (true, false) => (u, v, points[u]),
+                (false, true) => (v, u, points[v]),
+                (true, true) if along_line && corners[u] => (u, v, points[u]),
+                (true, true) if along_line && corners[v] => (v, u, points[v]),
+                (true, true) if along_line => (u, v, midpoint),
+                (true, true) => continue,
+            };
+            if corners[removed] {
+                continue;
+            }
+            let stretched = neighbors[u]
+                .iter()
+                .chain(&neighbors[v])
+                .filter(|&&other| other != u && other != v)
+                .any(|&other| {
+                    metric_length(
+                        &[position, points[other]],
+                        &[metrics[survivor], metrics[other]],
+                        0,
+                        1,
+                    ) > SPLIT_ABOVE
+                });
+            if stretched {
+                continue;
+            }
+            let moved = |node: usize| {
+                if node == u || node == v {
+                    position
+                } else {
+                    points[node]
+                }
+            };
+            let folds = vertex_faces[u].iter().chain(&vertex_faces[v]).any(|&face| {
+                let [a, b, c] = triangles[face];
+                let degenerate = [a, b, c].contains(&u) && [a, b, c].contains(&v);
+                !degenerate
+                    && dot(
+                        &normal(&points[a], &points[b], &points[c]),
+                        &normal(&moved(a), &moved(b), &moved(c)),
+                    ) <= 0.0
+            });
+            if folds {
+                continue;
+            }
+            merge[removed] = survivor;
+            points[survivor] = position;
+            neighbors[u]
+                .iter()
+                .chain(&neighbors[v])
+                .chain([&u, &v])
+                .for_each(|&node| touched[node] = true);
+        }
+        let kept: Vec<[usize; 3]> = triangles
+            .iter()
+            .map(|triangle| triangle.map(|node| merge[node]))
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect();
+        let mut remap = vec![usize::MAX; vertices];
+        let mut used = vec![false; vertices];
+        kept.iter().flatten().for_each(|&node| used[node] = true);
+        let mut compact_points = Vec::with_capacity(vertices);
+        let mut compact_metrics = Vec::with_capacity(vertices);
+        let mut compact_corners = Vec::with_capacity(vertices);
+        (0..vertices).filter(|&node| used[node]).for_each(|node| {
+            remap[node] = compact_points.len();
+            compact_points.push(points[node]);
+            compact_metrics.push(metrics[node]);
+            compact_corners.push(corners[node]);
+        });
+        *triangles = kept
+            .into_iter()
+            .map(|triangle| triangle.map(|node| remap[node]))
+            .collect();
+        *features = features
+            .iter()
+            .map(|&(u, v)| (merge[u], merge[v]))
+            .filter(|(u, v)| u != v && used[*u] && used[*v])
+            .map(|(u, v)| edge(remap[u], remap[v]))
+            .collect();
+        *points = compact_points;
+        *metrics = compact_metrics;
+        *corners = compact_corners;
+    }
+    /// Flips interior edges that bring the valences of their four nodes
+    /// closer to six, or four on the boundary, unless they are features or
+    /// that would fold a triangle over.
+    fn flip_edges(&mut self) -> Result<(), Error> {
+        let Self {
+            triangles,
+            points,
+            features,
+            ..
+        } = self;
+        let vertices = points.len();
+        let edges = edge_faces(triangles);
+        let mut valence = vec![0_i64; vertices];
+        let mut boundary = vec![false; vertices];
+        edges.iter().for_each(|(&(u, v), faces)| {
+            valence[u] += 1;
+            valence[v] += 1;
+            if faces.len() == 1 {
+                boundary[u] = true;
+                boundary[v] = true;
+            }
+        });
+        let target = |node: usize| if boundary[node] { 4 } else { 6 };
+        let deviation = |valence: i64, target: i64| (valence - target) * (valence - target);
+        let mut interior: Vec<(usize, usize)> = edges
+            .iter()
+            .filter(|(edge, faces)| faces.len() == 2 && !features.contains(edge))
+            .map(|(&edge, _)| edge)
+            .collect();
+        interior.sort_unstable();
+        let mut existing: HashSet<(usize, usize)> = edges.keys().copied().collect();
+        let mut touched = vec![false; vertices];
+        for (u, v) in interior {
+            if touched[u] || touched[v] {
+                continue;
+            }
+            let faces = &edges[&(u, v)];
+            let (left, right) = (faces[0], faces[1]);
+            let (c, directed) = opposite_and_direction(&triangles[left], u, v)?;
+            let (d, _) = opposite_and_direction(&triangles[right], u, v)?;
+            if c == d || touched[c] || touched[d] || existing.contains(&edge(c, d)) {
+                continue;
+            }
+            let before = deviation(valence[u], target(u))
+                + deviation(valence[v], target(v))
+                + deviation(valence[c], target(c))
+                + deviation(valence[d], target(d));
+            let after = deviation(valence[u] - 1, target(u))
+                + deviation(valence[v] - 1, target(v))
+                + deviation(valence[c] + 1, target(c))
+                + deviation(valence[d] + 1, target(d));
+            if after >= before {
+                continue;
+            }
+            let (uv, vu) = if directed { (c, d) } else { (d, c) };
+            let face_normal = |[a, b, c]: [usize; 3]| normal(&points[a], &points[b], &points[c]);
+            let surface = add(
+                &face_normal(triangles[left]),
+                &face_normal(triangles[right]),
+            );
+            if dot(&face_normal([v, uv, vu]), &surface) <= 0.0
+                || dot(&face_normal([uv, u, vu]), &surface) <= 0.0
+            {
+                continue;
+            }
+            triangles[left] = [v, uv, vu];
+            triangles[right] = [uv, u, vu];
+            existing.remove(&(u, v));
+            existing.insert(edge(c, d));
+            valence[u] -= 1;
+            valence[v] -= 1;
+            valence[c] += 1;
+            valence[d] += 1;
+            [u, v, c, d]
+                .into_iter()
+                .for_each(|node| touched[node] = true);
+        }
+        Ok(())
+    }
+    /// Moves each interior node toward the centroid of its neighbors, within
+    /// the tangent plane of the surface there, and each node on a feature
+    /// toward the midpoint of its neighbors along the feature, leaving the
+    /// boundary and corners in place.
+    fn tangential_smooth(&mut self) {
+        let Self {
+            triangles,
+            points,
+            features,
+            corners,
+            ..
+        } = self;
+        let vertices = points.len();
+        let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); vertices];
+        let mut along: Vec<Vec<usize>> = vec![Vec::new(); vertices];
+        let mut uses: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut normals = vec![[0.0; 3]; vertices];
+        triangles.iter().for_each(|&[a, b, c]| {
+            for (u, v) in [(a, b), (b, c), (c, a)] {
+                neighbors[u].insert(v);
+                neighbors[v].insert(u);
+                *uses.entry(edge(u, v)).or_default() += 1;
+            }
+            let normal = normal(&points[a], &points[b], &points[c]);
+            [a, b, c]
+                .into_iter()
+                .for_each(|node| (0..3).for_each(|i| normals[node][i] += normal[i]));
+        });
+        features.iter().for_each(|&(u, v)| {
+            along[u].push(v);
+            along[v].push(u);
+        });
+        let mut boundary = vec![false; vertices];
+        uses.iter()
+            .filter(|(_, uses)| **uses == 1)
+            .for_each(|(&(u, v), _)| {
+                boundary[u] = true;
+                boundary[v] = true;
+            });
+        let smoothed: Vec<Point> = (0..vertices)
+            .map(|node| {
+                if boundary[node] || corners[node] || neighbors[node].is_empty() {
+                    return points[node];
+                }
+                if let [before, after] = along[node][..] {
+                    let midpoint = [0, 1, 2].map(|i| 0.5 * (points[before][i] + points[after][i]));
+                    let displacement = sub(&midpoint, &points[node]);
+                    return match direction(&sub(&points[after], &points[before])) {
+                        Some(tangent) => add(
+                            &points[node],
+                            &scale(&tangent, dot(&displacement, &tangent)),
+                        ),
+                        None => points[node],
+                    };
+                }
+                if !along[node].is_empty() {
+                    return points[node];
+                }
+                let degree = neighbors[node].len() as f64;
+                let centroid = [0, 1, 2].map(|i| {
+                    neighbors[node]
+                        .iter()
+                        .map(|&neighbor| points[neighbor][i])
+                        .sum::<f64>()
+                        / degree
+                });
+                let displacement = sub(&centroid, &points[node]);
+                let displacement = match direction(&normals[node]) {
+                    Some(normal) => {
+                        sub(&displacement, &scale(&normal, dot(&displacement, &normal)))
+                    }
+                    None => displacement,
+                };
+                add(&points[node], &displacement)
+            })
+            .collect();
+        points.copy_from_slice(&smoothed);
+    }
+}
+
+/// The node of a triangle opposite an edge, and whether the triangle runs
+/// along the edge from its first node to its second.
+fn opposite_and_direction(face: &[usize; 3], u: usize, v: usize) -> Result<(usize, bool), Error> {
+    (0..3)
+        .find_map(|i| {
+            let (a, b) = (face[i], face[(i + 1) % 3]);
+            if a == u && b == v {
+                Some((face[(i + 2) % 3], true))
+            } else if a == v && b == u {
+                Some((face[(i + 2) % 3], false))
+            } else {
+                None
+            }
+        })
+        .ok_or_else(|| {
+            Error::Mesh(format!(
+                "Triangle {face:?} does not have the edge ({u}, {v}) it was listed with"
+            ))
+        })
+}
+
+/// Barycentric coordinates of a point on a triangle.
+fn barycentric(point: &Point, [a, b, c]: &[Point; 3]) -> [f64; 3] {
+    let (ab, ac, ap) = (sub(b, a), sub(c, a), sub(point, a));
+    let (d00, d01, d11) = (dot(&ab, &ab), dot(&ab, &ac), dot(&ac, &ac));
+    let (d20, d21) = (dot(&ap, &ab), dot(&ap, &ac));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator <= 0.0 {
+        return [1.0 / 3.0; 3];
+    }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    [1.0 - v - w, v, w]
+}
+
+fn normal(a: &Point, b: &Point, c: &Point) -> Point {
+    cross(&sub(b, a), &sub(c, a))
+}
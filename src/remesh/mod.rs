@@ -1,137 +1,279 @@
-use super::{
-    ErrorWrapper,
-    io::{read_mesh, write_mesh},
+mod metric;
+
+use super::{Error, FeatureOptions, io::read_field, smooth::points};
+use conspire::{
+    geometry::mesh::{IsotropicSizing, Mesh, Remeshing, RemeshingMetric},
+    math::Tensor,
 };
-use clap::Subcommand;
-use conspire::geometry::mesh::{IsotropicSizing, Mesh, Remeshing, RemeshingMetric};
+use metric::Target;
 use std::time::Instant;
 
 pub const REMESH_DEFAULT_ITERS: usize = 5;
 pub const ADAPTIVE_DEFAULT_TOLERANCE: f64 = 0.1;
 pub const ADAPTIVE_DEFAULT_GRADATION: f64 = 0.5;
 
-#[derive(Subcommand, Debug)]
-pub enum MeshRemeshSubcommand {
-    /// Applies remeshing to the mesh before output [default mode: uniform]
-    Remesh {
-        /// Sizing mode [default: uniform]
-        #[command(subcommand)]
-        mode: Option<MeshRemeshCommands>,
-    },
-}
-
-#[derive(Subcommand, Debug)]
-pub enum MeshRemeshCommands {
-    /// Uniform target edge length over the whole mesh
-    Uniform {
-        /// Number of remeshing iterations
-        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
-        iterations: usize,
-
-        /// Target edge length [default: mean edge length]
-        #[arg(long, short = 's', value_name = "SIZE")]
-        size: Option<f64>,
-    },
-
-    /// Curvature-adaptive target edge length
+/// Target edge length for remeshing.
+#[derive(Clone, Debug)]
+pub enum Sizing {
+    /// Uniform target edge length over the whole mesh [default: mean edge length].
+    Uniform { size: Option<f64> },
+    /// Curvature-adaptive target edge length.
     Adaptive {
-        /// Number of remeshing iterations
-        #[arg(default_value_t = REMESH_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
-        iterations: usize,
-
-        /// Minimum edge length
-        #[arg(long, value_name = "MIN")]
         minimum: f64,
-
-        /// Maximum edge length
-        #[arg(long, value_name = "MAX")]
         maximum: f64,
-
-        /// Curvature tolerance
-        #[arg(default_value_t = ADAPTIVE_DEFAULT_TOLERANCE, long, short = 't', value_name = "TOL")]
         tolerance: f64,
-
-        /// Size gradation factor
-        #[arg(default_value_t = ADAPTIVE_DEFAULT_GRADATION, long, short = 'g', value_name = "GRAD")]
         gradation: f64,
     },
+    /// Target edge length from a field (npy | vtu) of one size per node or
+    /// per element, such as from an error estimate.
+    Field { file: String, array: Option<String> },
+    /// Target edge lengths along the principal curvature directions, so that
+    /// edges are long along flat directions and short across curved ones.
+    Anisotropic {
+        minimum: f64,
+        maximum: f64,
+        tolerance: f64,
+    },
 }
 
-pub fn remesh(
-    input: String,
-    output: String,
-    mode: Option<MeshRemeshCommands>,
-    quiet: bool,
-) -> Result<(), ErrorWrapper> {
-    let mesh = read_mesh(&input, quiet, true)?;
-    let mesh = apply_remeshing(mesh, mode_or_default(mode), quiet)?;
-    write_mesh(&output, mesh, quiet)
+impl Sizing {
+    /// Adaptive sizing if given both a minimum and a maximum edge length, and
+    /// otherwise uniform sizing.
+    pub fn bounded(
+        size: Option<f64>,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+        tolerance: f64,
+        gradation: f64,
+    ) -> Result<Self, Error> {
+        match (minimum, maximum) {
+            (Some(minimum), Some(maximum)) => Ok(Self::Adaptive {
+                minimum,
+                maximum,
+                tolerance,
+                gradation,
+            }),
+            (None, None) => Ok(Self::Uniform { size }),
+            _ => Err(Error::InvalidOption(
+                "Adaptive remeshing needs both a minimum and a maximum".into(),
+            )),
+        }
+    }
+    /// Anisotropic sizing between the bounds of adaptive sizing.
+    pub fn anisotropic(self) -> Result<Self, Error> {
+        match self {
+            Self::Adaptive {
+                minimum,
+                maximum,
+                tolerance,
+                ..
+            } => Ok(Self::Anisotropic {
+                minimum,
+                maximum,
+                tolerance,
+            }),
+            _ => Err(Error::InvalidOption(
+                "Anisotropic remeshing needs both a minimum and a maximum".into(),
+            )),
+        }
+    }
 }
 
-fn mode_or_default(mode: Option<MeshRemeshCommands>) -> MeshRemeshCommands {
-    mode.unwrap_or(MeshRemeshCommands::Uniform {
-        iterations: REMESH_DEFAULT_ITERS,
-        size: None,
-    })
+/// Options for remeshing a triangular mesh.
+///
+/// ```no_run
+/// use automesh::{RemeshOptions, Sizing, read_mesh};
+///
+/// let mesh = read_mesh("sphere.stl", true, false)?;
+/// let mesh = RemeshOptions::default()
+///     .iterations(3)
+///     .sizing(Sizing::Uniform { size: Some(0.1) })
+///     .remesh(mesh, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RemeshOptions {
+    iterations: usize,
+    sizing: Sizing,
+    features: FeatureOptions,
 }
 
-pub fn apply_remesh_subcommand(
-    mesh: Mesh<3>,
-    subcommand: MeshRemeshSubcommand,
-    quiet: bool,
-) -> Result<Mesh<3>, ErrorWrapper> {
-    let MeshRemeshSubcommand::Remesh { mode } = subcommand;
-    apply_remeshing(mesh, mode_or_default(mode), quiet)
+impl Default for RemeshOptions {
+    fn default() -> Self {
+        Self {
+            iterations: REMESH_DEFAULT_ITERS,
+            sizing: Sizing::Uniform { size: None },
+            features: FeatureOptions::default(),
+        }
+    }
 }
 
-pub fn apply_remeshing(
-    mesh: Mesh<3>,
-    mode: MeshRemeshCommands,
-    quiet: bool,
-) -> Result<Mesh<3>, ErrorWrapper> {
-    let time = Instant::now();
-    let remeshing = match mode {
-        MeshRemeshCommands::Uniform { iterations, size } => {
-            match size {
-                Some(length) => crate::echo!(
+impl RemeshOptions {
+    /// Number of remeshing iterations.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    /// Target edge length.
+    pub fn sizing(mut self, sizing: Sizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+    /// Sharp edges and corners to keep.
+    ///
+    /// Uniform and adaptive sizing otherwise remesh with conspire's
+    /// `Mesh::remesh`, but with any features given they remesh with the
+    /// in-crate remesher of field and anisotropic sizing, which keeps them.
+    pub fn features(mut self, features: FeatureOptions) -> Self {
+        self.features = features;
+        self
+    }
+    /// Remeshes a triangular mesh.
+    pub fn remesh(&self, mesh: Mesh<3>, quiet: bool) -> Result<Mesh<3>, Error> {
+        let iterations = self.iterations;
+        let time = Instant::now();
+        let remeshing = match &self.sizing {
+            Sizing::Uniform { size } => {
+                match size {
+                    Some(length) => crate::echo!(
+                        quiet,
+                        "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of uniform sizing \
+                        (target edge length {length})"
+                    ),
+                    None => crate::echo!(
+                        quiet,
+                        "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of uniform sizing"
+                    ),
+                }
+                if !self.features.is_empty() {
+                    let size = size.unwrap_or_else(|| mean_edge_length(&mesh));
+                    return self.constrained(mesh, Target::Uniform(size), time, quiet);
+                }
+                Remeshing {
+                    iterations,
+                    metric: RemeshingMetric::Isotropic(IsotropicSizing::Uniform { length: *size }),
+                }
+            }
+            Sizing::Adaptive {
+                minimum,
+                maximum,
+                tolerance,
+                gradation,
+            } => {
+                crate::echo!(
                     quiet,
-                    "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of uniform sizing \
-                    (target edge length {length})"
-                ),
-                None => crate::echo!(
+                    "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of adaptive sizing \
+                    (edge length {minimum}\u{2013}{maximum}, tolerance {tolerance}, gradation {gradation})"
+                );
+                if gradation.is_nan() || *gradation < 0.0 {
+                    return Err(Error::InvalidOption(
+                        "Adaptive remeshing needs a non-negative gradation".into(),
+                    ));
+                }
+                if !self.features.is_empty() {
+                    let target = Target::Adaptive {
+                        minimum: *minimum,
+                        maximum: *maximum,
+                        tolerance: *tolerance,
+                        gradation: *gradation,
+                    };
+                    return self.constrained(mesh, target, time, quiet);
+                }
+                Remeshing {
+                    iterations,
+                    metric: RemeshingMetric::Isotropic(IsotropicSizing::Adaptive {
+                        tolerance: *tolerance,
+                        minimum: *minimum,
+                        maximum: *maximum,
+                        gradation: *gradation,
+                    }),
+                }
+            }
+            Sizing::Field { file, array } => {
+                crate::echo!(
                     quiet,
-                    "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of uniform sizing"
-                ),
+                    "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of field sizing \
+                    (from {file})"
+                );
+                let sizes = node_sizes(&mesh, read_field(file, array.as_deref())?, file)?;
+                return self.constrained(mesh, Target::Field(sizes), time, quiet);
             }
-            Remeshing {
-                iterations,
-                metric: RemeshingMetric::Isotropic(IsotropicSizing::Uniform { length: size }),
+            Sizing::Anisotropic {
+                minimum,
+                maximum,
+                tolerance,
+            } => {
+                crate::echo!(
+                    quiet,
+                    "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of anisotropic sizing \
+                    (edge length {minimum}\u{2013}{maximum}, tolerance {tolerance})"
+                );
+                if !(*minimum > 0.0 && minimum <= maximum && *tolerance > 0.0) {
+                    return Err(Error::InvalidOption(
+                        "Anisotropic remeshing needs 0 < minimum <= maximum and a positive tolerance"
+                            .into(),
+                    ));
+                }
+                let target = Target::Curvature {
+                    minimum: *minimum,
+                    maximum: *maximum,
+                    tolerance: *tolerance,
+                };
+                return self.constrained(mesh, target, time, quiet);
             }
-        }
-        MeshRemeshCommands::Adaptive {
-            iterations,
-            minimum,
-            maximum,
-            tolerance,
-            gradation,
-        } => {
+        };
+        done(mesh.remesh(remeshing).map_err(Error::from), time, quiet)
+    }
+    /// Remeshes toward a target with the in-crate remesher, which keeps the
+    /// features.
+    fn constrained(
+        &self,
+        mesh: Mesh<3>,
+        target: Target,
+        time: Instant,
+        quiet: bool,
+    ) -> Result<Mesh<3>, Error> {
+        let faces: Vec<Vec<usize>> = mesh
+            .connectivities()
+            .iter()
+            .flatten()
+            .map(|face| face.to_vec())
+            .collect();
+        let edges = self.features.edges(&faces, &points(mesh.coordinates()))?;
+        if !self.features.is_empty() {
             crate::echo!(
                 quiet,
-                "   \x1b[1;96mRemeshing\x1b[0m with {iterations} iterations of adaptive sizing \
-                (edge length {minimum}\u{2013}{maximum}, tolerance {tolerance}, gradation {gradation})"
+                "     \x1b[1;96mKeeping\x1b[0m {} feature edges",
+                edges.len()
             );
-            Remeshing {
-                iterations,
-                metric: RemeshingMetric::Isotropic(IsotropicSizing::Adaptive {
-                    tolerance,
-                    minimum,
-                    maximum,
-                    gradation,
-                }),
-            }
         }
-    };
-    let mesh = mesh.remesh(remeshing)?;
+        let mesh = metric::remesh(mesh, self.iterations, &target, &self.features, edges);
+        done(mesh, time, quiet)
+    }
+}
+
+/// Mean length of the edges of the elements of a mesh.
+fn mean_edge_length(mesh: &Mesh<3>) -> f64 {
+    let coordinates = mesh.coordinates();
+    let (total, count) = mesh
+        .connectivities()
+        .iter()
+        .flatten()
+        .flat_map(|element| {
+            (0..element.len())
+                .map(move |corner| (element[corner], element[(corner + 1) % element.len()]))
+        })
+        .fold((0.0, 0), |(total, count), (a, b)| {
+            (
+                total + (&coordinates[a] - &coordinates[b]).norm(),
+                count + 1,
+            )
+        });
+    total / count.max(1) as f64
+}
+
+fn done(mesh: Result<Mesh<3>, Error>, time: Instant, quiet: bool) -> Result<Mesh<3>, Error> {
+    let mesh = mesh?;
     crate::echo!(
         quiet,
         "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{} elements, {} nodes]\x1b[0m",
@@ -141,3 +283,49 @@ pub fn apply_remeshing(
     );
     Ok(mesh)
 }
+
+/// Size at each node of a mesh from a field of one size per node, or of one
+/// size per element, averaged over the elements about each node, with a node
+/// of no elements given the mean size. A field for a mesh with as many nodes as
+/// elements could be either, and is an error.
+fn node_sizes(mesh: &Mesh<3>, field: Vec<f64>, file: &str) -> Result<Vec<f64>, Error> {
+    if field.iter().any(|size| !(size.is_finite() && *size > 0.0)) {
+        return Err(Error::InvalidData(format!(
+            "Field {file} has sizes that are not positive"
+        )));
+    }
+    let nodes = mesh.number_of_nodes();
+    if field.len() == nodes && nodes == mesh.number_of_elements() {
+        return Err(Error::InvalidData(format!(
+            "Field {file} cannot be told apart as per node or per element, since the mesh has {nodes} of each"
+        )));
+    }
+    if field.len() == nodes {
+        return Ok(field);
+    }
+    if field.len() != mesh.number_of_elements() {
+        return Err(Error::InvalidData(format!(
+            "Field {file} has {} sizes, but the mesh has {nodes} nodes and {} elements",
+            field.len(),
+            mesh.number_of_elements()
+        )));
+    }
+    let mut sums = vec![0.0; nodes];
+    let mut counts = vec![0_usize; nodes];
+    mesh.connectivities()
+        .iter()
+        .flatten()
+        .zip(&field)
+        .for_each(|(element, size)| {
+            element.iter().for_each(|&node| {
+                sums[node] += size;
+                counts[node] += 1;
+            })
+        });
+    let mean = field.iter().sum::<f64>() / field.len() as f64;
+    Ok(sums
+        .into_iter()
+        .zip(counts)
+        .map(|(sum, count)| if count > 0 { sum / count as f64 } else { mean })
+        .collect())
+}
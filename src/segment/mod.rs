@@ -1,59 +1,315 @@
 use super::{
-    ErrorWrapper,
-    io::{extension, invalid_output, read_mesh, write_mesh, write_segmentation},
+    Error,
+    io::{Embedding, Label, relabel},
+    split::{element_tetrahedra, orientation},
+    vector::Point,
 };
 use conspire::geometry::{grid::Voxels, mesh::Mesh};
-use std::time::Instant;
-
-#[derive(clap::Args)]
-pub struct SegmentArgs {
-    /// Mesh input file (exo | inp | stl | vtu)
-    #[arg(long, short, value_name = "FILE")]
-    pub input: String,
-
-    /// Segmentation (npy | spn | vti) or mesh (exo | inp | mesh | vtu) output file
-    #[arg(long, short, value_name = "FILE")]
-    pub output: String,
-
-    /// Grid length for sampling within each element (currently unused)
-    #[arg(default_value_t = 1, long, short = 'g', value_name = "NUM")]
-    pub grid: usize,
-
-    /// Element size which is the side length
-    #[arg(long, short = 's', value_name = "NUM")]
-    pub size: f64,
-
-    /// Block IDs to remove from the segmentation
-    #[arg(long, num_args = 1.., short, value_delimiter = ' ', value_name = "ID")]
-    pub remove: Option<Vec<usize>>,
-}
-
-pub fn segment(args: SegmentArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    let mesh = read_mesh(&args.input, quiet, true)?;
-    let time = Instant::now();
-    crate::echo!(quiet, "  \x1b[1;96mSegmenting\x1b[0m from finite elements");
-    let voxels = Voxels::<usize>::from_finite_elements(&mesh, args.size);
-    let nel = *voxels.nel();
-    let remove = args.remove.unwrap_or_default();
-    let data: Vec<u8> = voxels
-        .data()
-        .iter()
-        .map(|&block| {
-            if remove.contains(&block) {
-                0
+use std::{array::from_fn, str::FromStr, time::Instant};
+
+/// Material kept when the vote within a voxel is tied.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tie {
+    Lowest,
+    Highest,
+}
+
+impl FromStr for Tie {
+    type Err = Error;
+    fn from_str(tie: &str) -> Result<Self, Error> {
+        match tie {
+            "lowest" | "Lowest" => Ok(Self::Lowest),
+            "highest" | "Highest" => Ok(Self::Highest),
+            _ => Err(Error::InvalidOption(format!(
+                "Invalid tie-break {tie}, expected lowest or highest"
+            ))),
+        }
+    }
+}
+
+/// Options for segmenting a mesh into voxels.
+///
+/// ```no_run
+/// use automesh::{SegmentOptions, read_mesh};
+///
+/// let mesh = read_mesh("letter_f_3d.inp", true, false)?;
+/// let segmented = SegmentOptions::new(0.5).grid(2).segment(&mesh, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SegmentOptions {
+    size: f64,
+    grid: usize,
+    remove: Vec<usize>,
+    tie: Tie,
+}
+
+/// Voxels segmented from a mesh, their embedding, and the volume fraction of
+/// each material (in ascending order) within each voxel.
+pub struct Segmented {
+    pub voxels: Voxels<Label>,
+    pub embedding: Embedding,
+    pub fractions: Vec<(usize, Vec<f64>)>,
+}
+
+impl SegmentOptions {
+    /// Options for voxels of the given size, which is the side length.
+    pub fn new(size: f64) -> Self {
+        Self {
+            size,
+            grid: 1,
+            remove: Vec::new(),
+            tie: Tie::Lowest,
+        }
+    }
+    /// Grid length for sampling within each voxel, with grid^3 points per voxel.
+    ///
+    /// The default of one samples only voxel centers, using conspire's
+    /// `Voxels::from_finite_elements` unchanged.
+    pub fn grid(mut self, grid: usize) -> Self {
+        self.grid = grid;
+        self
+    }
+    /// Block IDs to remove from the segmentation.
+    pub fn remove(mut self, remove: Vec<usize>) -> Self {
+        self.remove = remove;
+        self
+    }
+    /// Material kept when the vote is tied.
+    pub fn tie(mut self, tie: Tie) -> Self {
+        self.tie = tie;
+        self
+    }
+    /// Segments a mesh, giving each voxel the material at most of its points.
+    pub fn segment(&self, mesh: &Mesh<3>, quiet: bool) -> Result<Segmented, Error> {
+        if self.grid == 0 {
+            return Err(Error::InvalidOption(
+                "Grid length must be at least 1".into(),
+            ));
+        }
+        let time = Instant::now();
+        crate::echo!(
+            quiet,
+            "  \x1b[1;96mSegmenting\x1b[0m from finite elements \x1b[2m[{} points per voxel]\x1b[0m",
+            self.grid.pow(3)
+        );
+        let min = minimum(mesh);
+        let (voxels, fractions) = if self.grid == 1 {
+            let voxels = Voxels::<usize>::from_finite_elements(mesh, self.size);
+            let fractions = indicators(&voxels, &materials(mesh, &self.remove));
+            (voxels, fractions)
+        } else {
+            let samples = sample(mesh, self.size, self.grid, min);
+            let voxels = vote(&samples, self.tie == Tie::Highest);
+            (voxels, fractions(&samples, &materials(mesh, &self.remove)))
+        };
+        let embedding = Embedding {
+            scale: [self.size; 3],
+            translate: min,
+        };
+        let voxels = relabel(&voxels, |block| {
+            if self.remove.contains(&block) {
+                Ok(0)
             } else {
-                block as u8
+                Label::try_from(block).map_err(|_| {
+                    Error::InvalidData(format!(
+                        "Block {block} is out of range for {}-bit labels",
+                        Label::BITS
+                    ))
+                })
             }
+        })?;
+        crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+        Ok(Segmented {
+            voxels,
+            embedding,
+            fractions,
         })
+    }
+}
+
+/// Materials sampled at grid^3 points within each voxel.
+struct Samples {
+    nel: [usize; 3],
+    grid: usize,
+    /// Material number of each block of the mesh.
+    materials: Vec<usize>,
+    /// Block (one-based, zero is void) at each point, points of a voxel contiguous.
+    blocks: Vec<u32>,
+}
+
+/// Minimum corner of the bounding box of a mesh.
+fn minimum(mesh: &Mesh<3>) -> Point {
+    let mut min = [f64::INFINITY; 3];
+    mesh.coordinates()
+        .into_iter()
+        .for_each(|point| (0..3).for_each(|axis| min[axis] = min[axis].min(point[axis])));
+    min
+}
+
+/// Material numbers of a mesh in ascending order, leaving out removed ones.
+fn materials(mesh: &Mesh<3>, remove: &[usize]) -> Vec<usize> {
+    let mut materials: Vec<usize> = (0..mesh.connectivities().len())
+        .map(|block| mesh.blocks().map_or(block + 1, |numbers| numbers[block]))
         .collect();
-    let voxels = Voxels::<u8>::new(data, nel);
-    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-    match extension(&args.output) {
-        Some("npy") | Some("spn") | Some("vti") => write_segmentation(&args.output, &voxels, quiet),
-        Some("exo") | Some("inp") | Some("mesh") | Some("vtu") => {
-            let mesh = Mesh::from_voxels(voxels, Some(&[0u8]));
-            write_mesh(&args.output, mesh, quiet)
+    materials.sort_unstable();
+    materials.dedup();
+    materials.retain(|material| !remove.contains(material));
+    materials
+}
+
+/// Samples the materials of a mesh at grid^3 points within each voxel.
+///
+/// Voxels of the given size span the bounding box of the mesh from its minimum
+/// corner, as they do for a single point per voxel. Elements are split into
+/// tetrahedra, and a point takes the block of the last element found to
+/// contain it, or void if none do.
+fn sample(mesh: &Mesh<3>, size: f64, grid: usize, min: Point) -> Samples {
+    let coordinates = mesh.coordinates();
+    let mut max = [f64::NEG_INFINITY; 3];
+    coordinates
+        .into_iter()
+        .for_each(|point| (0..3).for_each(|axis| max[axis] = max[axis].max(point[axis])));
+    let nel: [usize; 3] = from_fn(|axis| (((max[axis] - min[axis]) / size).ceil() as usize).max(1));
+    let count = grid.pow(3);
+    let spacing = size / grid as f64;
+    let points = from_fn::<usize, 3, _>(|axis| nel[axis] * grid);
+    let mut blocks = vec![0; nel.iter().product::<usize>() * count];
+    for (block, connectivity) in mesh.connectivities().iter().enumerate() {
+        for nodes in connectivity {
+            let Some(tets) = element_tetrahedra(nodes.len()) else {
+                continue;
+            };
+            let vertices: Vec<Point> = nodes
+                .iter()
+                .map(|&node| from_fn(|axis| coordinates[node][axis]))
+                .collect();
+            let mut lo = [usize::MAX; 3];
+            let mut hi = [0; 3];
+            vertices.iter().for_each(|vertex| {
+                (0..3).for_each(|axis| {
+                    let index = ((vertex[axis] - min[axis]) / spacing).floor().max(0.0) as usize;
+                    lo[axis] = lo[axis].min(index);
+                    hi[axis] = hi[axis].max((index + 1).min(points[axis]));
+                })
+            });
+            for k in lo[2]..hi[2] {
+                for j in lo[1]..hi[1] {
+                    for i in lo[0]..hi[0] {
+                        let point = [i, j, k];
+                        let center =
+                            from_fn(|axis| min[axis] + (point[axis] as f64 + 0.5) * spacing);
+                        if tets
+                            .iter()
+                            .any(|tet| inside(&center, tet.map(|vertex| vertices[vertex])))
+                        {
+                            let voxel = from_fn::<usize, 3, _>(|axis| point[axis] / grid);
+                            let sub = from_fn::<usize, 3, _>(|axis| point[axis] % grid);
+                            let index = voxel[0] + nel[0] * (voxel[1] + nel[1] * voxel[2]);
+                            let offset = sub[0] + grid * (sub[1] + grid * sub[2]);
+                            blocks[index * count + offset] = block as u32 + 1;
+                        }
+                    }
+                }
+            }
         }
-        extension => Err(invalid_output(&args.output, extension)),
     }
+    let materials = (0..mesh.connectivities().len())
+        .map(|block| mesh.blocks().map_or(block + 1, |numbers| numbers[block]))
+        .collect();
+    Samples {
+        nel,
+        grid,
+        materials,
+        blocks,
+    }
+}
+
+/// Assigns each voxel the material found at the most of its points.
+///
+/// Void counts as a material, and ties go to the lowest (or highest) material.
+fn vote(samples: &Samples, highest: bool) -> Voxels<usize> {
+    let data = samples
+        .blocks
+        .chunks_exact(samples.grid.pow(3))
+        .map(|points| {
+            let mut counts: Vec<(usize, usize)> = Vec::new();
+            points.iter().for_each(|&block| {
+                let material = match block {
+                    0 => 0,
+                    block => samples.materials[block as usize - 1],
+                };
+                match counts.iter_mut().find(|(entry, _)| *entry == material) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((material, 1)),
+                }
+            });
+            counts
+                .into_iter()
+                .max_by(|(a, count_a), (b, count_b)| {
+                    count_a
+                        .cmp(count_b)
+                        .then_with(|| if highest { a.cmp(b) } else { b.cmp(a) })
+                })
+                .map_or(0, |(material, _)| material)
+        })
+        .collect();
+    Voxels::new(data, samples.nel)
+}
+
+/// Volume fraction of each of the given materials within each voxel, as sampled.
+fn fractions(samples: &Samples, materials: &[usize]) -> Vec<(usize, Vec<f64>)> {
+    let count = samples.grid.pow(3);
+    let mut fractions: Vec<(usize, Vec<f64>)> = materials
+        .iter()
+        .map(|&material| (material, vec![0.0; samples.blocks.len() / count]))
+        .collect();
+    samples
+        .blocks
+        .chunks_exact(count)
+        .enumerate()
+        .for_each(|(voxel, points)| {
+            points
+                .iter()
+                .filter(|&&block| block > 0)
+                .for_each(|&block| {
+                    let material = samples.materials[block as usize - 1];
+                    if let Some((_, fraction)) =
+                        fractions.iter_mut().find(|(entry, _)| *entry == material)
+                    {
+                        fraction[voxel] += 1.0 / count as f64
+                    }
+                })
+        });
+    fractions
+}
+
+/// Volume fraction of each of the given materials within each voxel, which is
+/// one in voxels of that material and zero elsewhere.
+fn indicators(voxels: &Voxels<usize>, materials: &[usize]) -> Vec<(usize, Vec<f64>)> {
+    materials
+        .iter()
+        .map(|&material| {
+            let fraction = voxels
+                .data()
+                .iter()
+                .map(|&voxel| if voxel == material { 1.0 } else { 0.0 })
+                .collect();
+            (material, fraction)
+        })
+        .collect()
+}
+
+/// Whether a point is inside (or on the boundary of) a tetrahedron.
+fn inside(query: &Point, [a, b, c, d]: [Point; 4]) -> bool {
+    let volume = orientation(&a, &b, &c, &d);
+    if volume == 0.0 {
+        return false;
+    }
+    let sign = volume.signum();
+    let tolerance = -1e-9 * volume.abs();
+    sign * orientation(query, &b, &c, &d) >= tolerance
+        && sign * orientation(&a, query, &c, &d) >= tolerance
+        && sign * orientation(&a, &b, query, &d) >= tolerance
+        && sign * orientation(&a, &b, &c, query) >= tolerance
 }
@@ -0,0 +1,520 @@
+use super::Error;
+use conspire::geometry::mesh::{Connectivity, Mesh, NodeSets, SideSets};
+use std::{
+    array::from_fn,
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+/// A named node set and side set, sides being an element and its local face.
+pub struct Set {
+    pub name: String,
+    pub nodes: Vec<usize>,
+    pub sides: Vec<(usize, usize)>,
+}
+
+/// Classifies the sides and nodes of a mesh of a segmentation into sets.
+///
+/// Exterior faces are split into those on the bounding box of the segmentation
+/// (`boundary`) and those facing void or removed materials (`void`). Faces
+/// between blocks are grouped by material pair (`interface_11_21`), taking the
+/// side from the element of the lower material. As in hierarchical smoothing,
+/// a node is on an interface when its elements span both of its materials.
+/// Empty sets are left out.
+pub fn classify(mesh: &Mesh<3>, lower: [f64; 3], upper: [f64; 3]) -> Vec<Set> {
+    let coordinates = mesh.coordinates();
+    let tolerance: [f64; 3] = from_fn(|axis| 1e-9 * (upper[axis] - lower[axis]).abs());
+    let on_bounds = |face: &[usize]| {
+        (0..3).any(|axis| {
+            [lower[axis], upper[axis]].iter().any(|&plane| {
+                face.iter()
+                    .all(|&node| (coordinates[node][axis] - plane).abs() <= tolerance[axis])
+            })
+        })
+    };
+    let mut faces: HashMap<Vec<usize>, Vec<(usize, usize, usize)>> = HashMap::new();
+    let mut element = 0;
+    let mut node_materials = vec![Vec::new(); mesh.number_of_nodes()];
+    for (block, connectivity) in mesh.connectivities().iter().enumerate() {
+        let material = mesh.blocks().map_or(block + 1, |numbers| numbers[block]);
+        for nodes in connectivity.iter() {
+            nodes.iter().for_each(|&node| {
+                if !node_materials[node].contains(&material) {
+                    node_materials[node].push(material)
+                }
+            });
+            for (ordinal, mut face) in connectivity.element_faces(nodes).into_iter().enumerate() {
+                face.sort_unstable();
+                faces
+                    .entry(face)
+                    .or_default()
+                    .push((element, ordinal, material));
+            }
+            element += 1;
+        }
+    }
+    let mut boundary = Set::new("boundary");
+    let mut void = Set::new("void");
+    let mut interfaces: BTreeMap<(usize, usize), Set> = BTreeMap::new();
+    for (face, sides) in faces {
+        match sides[..] {
+            [(element, ordinal, _)] => {
+                let set = if on_bounds(&face) {
+                    &mut boundary
+                } else {
+                    &mut void
+                };
+                set.sides.push((element, ordinal));
+                set.nodes.extend(face);
+            }
+            [a, b] if a.2 != b.2 => {
+                let (element, ordinal, low) = if a.2 < b.2 { a } else { b };
+                let high = a.2.max(b.2);
+                interfaces
+                    .entry((low, high))
+                    .or_insert_with(|| Set::new(&format!("interface_{low}_{high}")))
+                    .sides
+                    .push((element, ordinal));
+            }
+            _ => {}
+        }
+    }
+    node_materials
+        .iter()
+        .enumerate()
+        .filter(|(_, materials)| materials.len() > 1)
+        .for_each(|(node, materials)| {
+            materials.iter().for_each(|&a| {
+                materials.iter().filter(|&&b| a < b).for_each(|&b| {
+                    if let Some(set) = interfaces.get_mut(&(a, b)) {
+                        set.nodes.push(node)
+                    }
+                })
+            })
+        });
+    let mut sets: Vec<Set> = [boundary, void]
+        .into_iter()
+        .chain(interfaces.into_values())
+        .filter(|set| !set.sides.is_empty())
+        .collect();
+    sets.iter_mut().for_each(|set| {
+        set.nodes.sort_unstable();
+        set.nodes.dedup();
+        set.sides.sort_unstable();
+    });
+    sets
+}
+
+/// Attaches sets to a mesh, numbered in order, returning their names for the
+/// exo and inp writers.
+pub fn attach(mesh: &mut Mesh<3>, sets: Vec<Set>) -> SetNames {
+    let names: Vec<String> = sets.iter().map(|set| set.name.clone()).collect();
+    let (nodes, sides): (Vec<_>, Vec<_>) =
+        sets.into_iter().map(|set| (set.nodes, set.sides)).unzip();
+    mesh.set_node_sets(NodeSets::from(nodes));
+    mesh.set_side_sets(SideSets::from(sides));
+    SetNames {
+        node_sets: names.clone(),
+        side_sets: names,
+    }
+}
+
+/// Names of the node sets and side sets of a mesh, in the order of its sets,
+/// which the exo and inp writers give them.
+///
+/// A set without a name, such as one read from a file, keeps the name the
+/// writer gives it by default, as does a set beyond the names given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SetNames {
+    node_sets: Vec<String>,
+    side_sets: Vec<String>,
+}
+
+impl SetNames {
+    /// Names of the node sets, with an empty name for an unnamed set.
+    pub fn node_sets(&self) -> &[String] {
+        &self.node_sets
+    }
+    /// Names of the side sets, with an empty name for an unnamed set.
+    pub fn side_sets(&self) -> &[String] {
+        &self.side_sets
+    }
+    /// Whether no set is named.
+    pub fn is_empty(&self) -> bool {
+        self.node_sets
+            .iter()
+            .chain(&self.side_sets)
+            .all(String::is_empty)
+    }
+    /// Fits the names to the sets a mesh has, such as none after remeshing.
+    pub(crate) fn fit(&mut self, mesh: &Mesh<3>) {
+        self.node_sets.resize(mesh.node_sets().len(), String::new());
+        self.side_sets.resize(mesh.side_sets().len(), String::new());
+    }
+}
+
+/// Options for tagging exterior sides of a mesh selected by `NAME:EXPR` as
+/// side sets, and the nodes of selected sides as node sets.
+///
+/// An expression is one or more conditions joined by `&`, each comparing a
+/// linear combination of `x`, `y`, and `z` (which every node of a side must
+/// satisfy) or of `nx`, `ny`, and `nz` (the outward unit normal of a side)
+/// against a number with `<`, `<=`, `>`, or `>=`. Axis-aligned boxes are
+/// bounds on each axis (`x>0&x<1`), half-spaces are planes (`x+y<1`), and
+/// normal directions are bounds on the normal (`nz<-0.9`). The new sets are
+/// numbered after any the mesh already has, and their names, of letters,
+/// digits, and underscores starting with a letter, are added to its
+/// [`SetNames`] for the writers.
+///
+/// ```no_run
+/// use automesh::{SetNames, SetOptions, read_mesh, write_mesh_named};
+///
+/// let mut mesh = read_mesh("letter_f_3d.inp", true, false)?;
+/// let mut names = SetNames::default();
+/// SetOptions::default()
+///     .sideset("bottom:z<0.01")
+///     .nodeset("top:nz>0.9")
+///     .tag(&mut mesh, &mut names, true)?;
+/// write_mesh_named("letter_f_3d_sets.inp", mesh, &names, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SetOptions {
+    sidesets: Vec<String>,
+    nodesets: Vec<String>,
+}
+
+impl SetOptions {
+    /// Adds a side set, as `NAME:EXPR`.
+    pub fn sideset(mut self, spec: impl Into<String>) -> Self {
+        self.sidesets.push(spec.into());
+        self
+    }
+    /// Adds a node set, as `NAME:EXPR`.
+    pub fn nodeset(mut self, spec: impl Into<String>) -> Self {
+        self.nodesets.push(spec.into());
+        self
+    }
+    /// Tags the sets on a mesh, adding their names to the names of its sets.
+    pub fn tag(&self, mesh: &mut Mesh<3>, names: &mut SetNames, quiet: bool) -> Result<(), Error> {
+        tag(mesh, names, &self.sidesets, &self.nodesets, quiet)
+    }
+}
+
+fn tag(
+    mesh: &mut Mesh<3>,
+    names: &mut SetNames,
+    sidesets: &[String],
+    nodesets: &[String],
+    quiet: bool,
+) -> Result<(), Error> {
+    if sidesets.is_empty() && nodesets.is_empty() {
+        return Ok(());
+    }
+    names.fit(mesh);
+    let time = Instant::now();
+    crate::echo!(quiet, "     \x1b[1;96mTagging\x1b[0m sidesets and nodesets");
+    let sides = exterior_sides(mesh)?;
+    let select = |spec: &String| -> Result<Set, Error> {
+        let (name, conditions) = parse(spec)?;
+        let mut set = Set::new(name);
+        sides
+            .iter()
+            .filter(|side| {
+                conditions
+                    .iter()
+                    .all(|condition| condition.holds(mesh, side))
+            })
+            .for_each(|side| {
+                set.sides.push((side.element, side.ordinal));
+                set.nodes.extend(&side.nodes);
+            });
+        if set.sides.is_empty() {
+            return Err(Error::InvalidOption(format!(
+                "Set {name} selects no exterior sides"
+            )));
+        }
+        set.nodes.sort_unstable();
+        set.nodes.dedup();
+        set.sides.sort_unstable();
+        Ok(set)
+    };
+    let sidesets = sidesets.iter().map(select).collect::<Result<Vec<_>, _>>()?;
+    let nodesets = nodesets.iter().map(select).collect::<Result<Vec<_>, _>>()?;
+    for (kind, sets, existing) in [
+        ("Side", &sidesets, &names.side_sets),
+        ("Node", &nodesets, &names.node_sets),
+    ] {
+        let mut taken: Vec<&str> = sets
+            .iter()
+            .map(|set| set.name.as_str())
+            .chain(
+                existing
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|name| !name.is_empty()),
+            )
+            .collect();
+        taken.sort_unstable();
+        if let Some(pair) = taken.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(Error::InvalidOption(format!(
+                "{kind} set {} is defined more than once",
+                pair[0]
+            )));
+        }
+    }
+    let side_numbers = next_numbers(
+        mesh.side_sets().len(),
+        mesh.side_set_numbers(),
+        sidesets.len(),
+    );
+    let node_numbers = next_numbers(
+        mesh.node_sets().len(),
+        mesh.node_set_numbers(),
+        nodesets.len(),
+    );
+    let legend = sidesets
+        .iter()
+        .zip(&side_numbers)
+        .map(|(set, number)| format!("side set {number} {}", set.name))
+        .chain(
+            nodesets
+                .iter()
+                .zip(&node_numbers)
+                .map(|(set, number)| format!("node set {number} {}", set.name)),
+        )
+        .collect::<Vec<_>>()
+        .join(", ");
+    names
+        .side_sets
+        .extend(sidesets.iter().map(|set| set.name.clone()));
+    names
+        .node_sets
+        .extend(nodesets.iter().map(|set| set.name.clone()));
+    let (mut members, mut numbers) = numbered(mesh.side_sets(), mesh.side_set_numbers());
+    members.extend(sidesets.into_iter().map(|set| set.sides));
+    numbers.extend(side_numbers);
+    mesh.set_side_sets(SideSets::from((members, numbers)));
+    let (mut members, mut numbers) = numbered(mesh.node_sets(), mesh.node_set_numbers());
+    members.extend(nodesets.into_iter().map(|set| set.nodes));
+    numbers.extend(node_numbers);
+    mesh.set_node_sets(NodeSets::from((members, numbers)));
+    crate::echo!(
+        quiet,
+        "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{legend}]\x1b[0m",
+        time.elapsed()
+    );
+    Ok(())
+}
+
+/// An exterior side of a mesh, with its nodes and outward unit normal.
+struct Side {
+    element: usize,
+    ordinal: usize,
+    nodes: Vec<usize>,
+    normal: [f64; 3],
+}
+
+/// Comparison of a linear combination of `x, y, z, nx, ny, nz` to a bound.
+struct Condition {
+    coefficients: [f64; 6],
+    less: bool,
+    inclusive: bool,
+    bound: f64,
+}
+
+/// Longest name of a set, which Exodus and Abaqus both allow.
+const NAME_LENGTH: usize = 32;
+
+const VARIABLES: [&str; 6] = ["x", "y", "z", "nx", "ny", "nz"];
+
+impl Condition {
+    fn holds(&self, mesh: &Mesh<3>, side: &Side) -> bool {
+        let compare = |value: f64| match (self.less, self.inclusive) {
+            (true, true) => value <= self.bound,
+            (true, false) => value < self.bound,
+            (false, true) => value >= self.bound,
+            (false, false) => value > self.bound,
+        };
+        let [x, y, z, nx, ny, nz] = self.coefficients;
+        if [x, y, z] == [0.0; 3] {
+            compare(nx * side.normal[0] + ny * side.normal[1] + nz * side.normal[2])
+        } else {
+            side.nodes.iter().all(|&node| {
+                let point = &mesh.coordinates()[node];
+                compare(x * point[0] + y * point[1] + z * point[2])
+            })
+        }
+    }
+}
+
+/// Parses `NAME:EXPR` into the name and the conditions of its expression.
+fn parse(spec: &str) -> Result<(&str, Vec<Condition>), Error> {
+    let invalid = |problem: &str| Error::InvalidOption(format!("Invalid set {spec}, {problem}"));
+    let (name, expression) = spec
+        .split_once(':')
+        .ok_or_else(|| invalid("expected NAME:EXPR"))?;
+    if !name.starts_with(|character: char| character.is_ascii_alphabetic())
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+        || name.len() > NAME_LENGTH
+    {
+        return Err(invalid(&format!(
+            "expected a name of at most {NAME_LENGTH} letters, digits, and underscores, starting with a letter"
+        )));
+    }
+    let conditions = expression
+        .split('&')
+        .map(|condition| {
+            let condition: String = condition.split_whitespace().collect();
+            let (at, operator) = ["<=", ">=", "<", ">"]
+                .iter()
+                .find_map(|operator| condition.find(operator).map(|at| (at, *operator)))
+                .ok_or_else(|| invalid(&format!("expected <, <=, >, or >= in {condition}")))?;
+            let bound = condition[at + operator.len()..]
+                .parse()
+                .map_err(|_| invalid(&format!("expected a number after {operator}")))?;
+            let mut coefficients = [0.0; 6];
+            terms(&condition[..at]).into_iter().try_for_each(|term| {
+                let (index, variable) = VARIABLES
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, variable)| term.ends_with(*variable))
+                    .ok_or_else(|| {
+                        invalid(&format!("expected x, y, z, nx, ny, or nz in {term}"))
+                    })?;
+                let coefficient = match term[..term.len() - variable.len()].trim_end_matches('*') {
+                    "" | "+" => 1.0,
+                    "-" => -1.0,
+                    number => number
+                        .parse()
+                        .map_err(|_| invalid(&format!("expected a coefficient in {term}")))?,
+                };
+                coefficients[index] += coefficient;
+                Ok::<_, Error>(())
+            })?;
+            if coefficients[..3] != [0.0; 3] && coefficients[3..] != [0.0; 3] {
+                return Err(invalid("a condition cannot mix positions and normals"));
+            }
+            if coefficients == [0.0; 6] {
+                return Err(invalid(&format!("expected a variable in {condition}")));
+            }
+            Ok(Condition {
+                coefficients,
+                less: operator.starts_with('<'),
+                inclusive: operator.ends_with('='),
+                bound,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name, conditions))
+}
+
+/// Splits a linear combination at its signs, keeping signs with the terms.
+fn terms(combination: &str) -> Vec<&str> {
+    let mut terms = Vec::new();
+    let mut start = 0;
+    for (at, character) in combination.char_indices() {
+        let exponent = combination[..at].ends_with(['e', 'E'])
+            && combination[start..at - 1]
+                .trim_start_matches(['+', '-'])
+                .parse::<f64>()
+                .is_ok();
+        if (character == '+' || character == '-') && at > start && !exponent {
+            terms.push(&combination[start..at]);
+            start = at;
+        }
+    }
+    terms.push(&combination[start..]);
+    terms
+}
+
+/// Finds the exterior sides of a mesh of volumetric elements.
+fn exterior_sides(mesh: &Mesh<3>) -> Result<Vec<Side>, Error> {
+    let coordinates = mesh.coordinates();
+    let point = |node: usize| -> [f64; 3] { from_fn(|axis| coordinates[node][axis]) };
+    let mut faces: HashMap<Vec<usize>, Option<Side>> = HashMap::new();
+    let mut element = 0;
+    for connectivity in mesh.connectivities() {
+        match connectivity {
+            Connectivity::Hexahedral(_)
+            | Connectivity::Pyramidal(_)
+            | Connectivity::Tetrahedral(_)
+            | Connectivity::Wedge(_) => {}
+            _ => {
+                return Err(Error::Mesh(
+                    "Sets require hexahedral, pyramidal, tetrahedral, or wedge elements".into(),
+                ));
+            }
+        }
+        for nodes in connectivity.iter() {
+            let center = centroid(nodes.iter().map(|&node| point(node)));
+            for (ordinal, face) in connectivity.element_faces(nodes).into_iter().enumerate() {
+                let mut key = face.clone();
+                key.sort_unstable();
+                faces
+                    .entry(key)
+                    .and_modify(|side| *side = None)
+                    .or_insert_with(|| {
+                        let points: Vec<[f64; 3]> = face.iter().map(|&node| point(node)).collect();
+                        let mut normal = [0.0; 3];
+                        points
+                            .iter()
+                            .zip(points.iter().cycle().skip(1))
+                            .for_each(|(a, b)| {
+                                normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+                                normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+                                normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+                            });
+                        let outward = centroid(points.iter().copied());
+                        let direction: f64 = (0..3)
+                            .map(|axis| normal[axis] * (outward[axis] - center[axis]))
+                            .sum();
+                        let length = normal.iter().map(|value| value * value).sum::<f64>().sqrt();
+                        let sign = if direction < 0.0 { -1.0 } else { 1.0 };
+                        Some(Side {
+                            element,
+                            ordinal,
+                            nodes: face,
+                            normal: normal.map(|value| sign * value / length),
+                        })
+                    });
+            }
+            element += 1;
+        }
+    }
+    Ok(faces.into_values().flatten().collect())
+}
+
+fn centroid(points: impl Iterator<Item = [f64; 3]>) -> [f64; 3] {
+    let (sum, count) = points.fold(([0.0; 3], 0.0), |(sum, count), point| {
+        (from_fn(|axis| sum[axis] + point[axis]), count + 1.0)
+    });
+    sum.map(|value| value / count)
+}
+
+/// Numbers for new sets following those of the existing sets.
+fn next_numbers(count: usize, numbers: Option<&[usize]>, new: usize) -> Vec<usize> {
+    let last = numbers
+        .and_then(|numbers| numbers.iter().max().copied())
+        .unwrap_or(count);
+    (last + 1..=last + new).collect()
+}
+
+/// Existing sets of a mesh with their numbers, which default to their order.
+fn numbered<T: Clone>(sets: &[Vec<T>], numbers: Option<&[usize]>) -> (Vec<Vec<T>>, Vec<usize>) {
+    let numbers = numbers.map_or_else(|| (1..=sets.len()).collect(), <[usize]>::to_vec);
+    (sets.to_vec(), numbers)
+}
+
+impl Set {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            nodes: Vec::new(),
+            sides: Vec::new(),
+        }
+    }
+}
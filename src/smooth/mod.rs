@@ -1,150 +1,932 @@
 use super::{
-    ErrorWrapper,
-    io::{read_mesh, write_mesh},
-    metrics::write_metrics,
-    remesh::{MeshRemeshSubcommand, apply_remesh_subcommand},
+    Error, FeatureOptions,
+    vector::{add, cross, distance, dot, norm, scale, sub},
 };
-use clap::Subcommand;
-use conspire::geometry::mesh::{Mesh, Smoothing, Weighting};
-use std::time::Instant;
+use conspire::{
+    geometry::{
+        Coordinate, Coordinates,
+        mesh::{Connectivities, Connectivity, Mesh, Smoothing, Verdict, Weighting},
+    },
+    math::{Set, Tensor},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::{empty, repeat_n},
+    mem::replace,
+    str::FromStr,
+    time::Instant,
+};
+
+/// Edges as pairs of nodes, the lesser first.
+type Edges = HashSet<(usize, usize)>;
 
 pub const TAUBIN_DEFAULT_ITERS: usize = 20;
 pub const TAUBIN_DEFAULT_BAND: f64 = 0.1;
 pub const TAUBIN_DEFAULT_SCALE: f64 = 0.6307;
+pub const HC_DEFAULT_ALPHA: f64 = 0.0;
+pub const HC_DEFAULT_BETA: f64 = 0.5;
 
-#[derive(Subcommand, Debug)]
-pub enum MeshSmoothCommands {
-    /// Applies smoothing to the mesh before output
-    Smooth {
-        #[command(subcommand)]
-        remeshing: Option<MeshRemeshSubcommand>,
-
-        /// Number of smoothing iterations
-        #[arg(default_value_t = TAUBIN_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
-        iterations: usize,
-
-        /// Smoothing method (Laplace | Taubin) [default: Taubin]
-        #[arg(long, short, value_name = "NAME")]
-        method: Option<String>,
+/// Number of times a guarded node move is halved before it is reverted.
+const GUARD_HALVINGS: u32 = 4;
 
-        /// Pass-band frequency (for Taubin only)
-        #[arg(default_value_t = TAUBIN_DEFAULT_BAND, long, short = 'k', value_name = "FREQ")]
-        pass_band: f64,
+/// Change in quality over an iteration below which guarded smoothing stops.
+const GUARD_TOLERANCE: f64 = 1e-6;
 
-        /// Scaling parameter for all smoothing methods
-        #[arg(default_value_t = TAUBIN_DEFAULT_SCALE, long, short, value_name = "SCALE")]
-        scale: f64,
+/// Maximum number of corrections toward the original volume of each block.
+const VOLUME_CORRECTIONS: usize = 20;
 
-        /// Enables hierarchical smoothing
-        #[arg(action, long, short = 'b')]
-        hierarchical: bool,
-    },
+/// Smoothing method.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmoothingMethod {
+    #[serde(alias = "laplacian")]
+    Laplace,
+    Taubin,
+    /// Laplace smoothing with the HC correction of Vollmer et al. (1999),
+    /// which pushes each node back toward its original and previous position.
+    Hc,
 }
 
-#[derive(clap::Args)]
-pub struct SmoothArgs {
-    #[command(subcommand)]
-    pub remeshing: Option<MeshRemeshSubcommand>,
-
-    /// Mesh input file (exo | inp | stl | vtu)
-    #[arg(long, short, value_name = "FILE")]
-    pub input: String,
-
-    /// Smoothed mesh output file (exo | inp | mesh | stl | vtu)
-    #[arg(long, short, value_name = "FILE")]
-    pub output: String,
-
-    /// Number of smoothing iterations
-    #[arg(default_value_t = TAUBIN_DEFAULT_ITERS, long, short = 'n', value_name = "NUM")]
-    pub iterations: usize,
-
-    /// Smoothing method (Laplace | Taubin) [default: Taubin]
-    #[arg(long, short, value_name = "NAME")]
-    pub method: Option<String>,
-
-    /// Pass-band frequency (for Taubin only)
-    #[arg(default_value_t = TAUBIN_DEFAULT_BAND, long, short = 'k', value_name = "FREQ")]
-    pub pass_band: f64,
-
-    /// Scaling parameter for all smoothing methods
-    #[arg(default_value_t = TAUBIN_DEFAULT_SCALE, long, short, value_name = "SCALE")]
-    pub scale: f64,
-
-    /// Enables hierarchical smoothing
-    #[arg(action, long, short = 'b')]
-    pub hierarchical: bool,
+impl FromStr for SmoothingMethod {
+    type Err = Error;
+    fn from_str(method: &str) -> Result<Self, Error> {
+        match method {
+            "Laplacian" | "Laplace" | "laplacian" | "laplace" => Ok(Self::Laplace),
+            "Taubin" | "taubin" => Ok(Self::Taubin),
+            "HC" | "Hc" | "hc" => Ok(Self::Hc),
+            _ => Err(Error::InvalidOption(format!(
+                "Invalid smoothing method {method} specified"
+            ))),
+        }
+    }
+}
 
-    /// Quality metrics output file (csv | npy)
-    #[arg(long, value_name = "FILE")]
-    pub metrics: Option<String>,
+/// Weighting of the neighbors of a node in the Laplacian.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothingWeighting {
+    /// Equal weights.
+    #[default]
+    Uniform,
+    /// Cotangents of the angles opposite each edge (triangles only).
+    Cotangent,
+    /// Reciprocal of the length of each edge.
+    InverseDistance,
 }
 
-pub fn smooth(args: SmoothArgs, quiet: bool) -> Result<(), ErrorWrapper> {
-    let mut mesh = read_mesh(&args.input, quiet, true)?;
-    apply_smoothing_method(
-        &mut mesh,
-        args.iterations,
-        args.method,
-        args.pass_band,
-        args.scale,
-        args.hierarchical,
-        quiet,
-    )?;
-    if let Some(subcommand) = args.remeshing {
-        mesh = apply_remesh_subcommand(mesh, subcommand, quiet)?;
-    }
-    if let Some(file) = args.metrics {
-        write_metrics(&mesh, &file, quiet)?;
+impl FromStr for SmoothingWeighting {
+    type Err = Error;
+    fn from_str(weighting: &str) -> Result<Self, Error> {
+        match weighting {
+            "Uniform" | "uniform" => Ok(Self::Uniform),
+            "Cotangent" | "cotangent" => Ok(Self::Cotangent),
+            "InverseDistance" | "inverse-distance" | "inverse_distance" => {
+                Ok(Self::InverseDistance)
+            }
+            _ => Err(Error::InvalidOption(format!(
+                "Invalid smoothing weighting {weighting} specified"
+            ))),
+        }
     }
-    write_mesh(&args.output, mesh, quiet)
 }
 
-pub fn apply_smoothing_method(
-    mesh: &mut Mesh<3>,
+/// Options for smoothing a mesh.
+///
+/// ```no_run
+/// use automesh::{SmoothOptions, SmoothingMethod, read_mesh};
+///
+/// let mut mesh = read_mesh("letter_f_3d.inp", true, false)?;
+/// SmoothOptions::default()
+///     .method(SmoothingMethod::Laplace)
+///     .iterations(10)
+///     .hierarchical(true)
+///     .smooth(&mut mesh, true)?;
+/// # Ok::<(), automesh::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SmoothOptions {
     iterations: usize,
-    method: Option<String>,
+    method: SmoothingMethod,
     pass_band: f64,
     scale: f64,
-    hierarchical: bool,
-    quiet: bool,
-) -> Result<(), ErrorWrapper> {
-    let time = Instant::now();
-    let method = method.unwrap_or_else(|| "Taubin".to_string());
-    let smoothing = match method.as_str() {
-        "Laplacian" | "Laplace" | "laplacian" | "laplace" => {
+    weighting: SmoothingWeighting,
+    alpha: f64,
+    beta: f64,
+    preserve_boundary: bool,
+    preserve_interfaces: bool,
+    freeze: Vec<usize>,
+    guard: Option<f64>,
+    volume: Option<f64>,
+    features: FeatureOptions,
+}
+
+impl Default for SmoothOptions {
+    fn default() -> Self {
+        Self {
+            iterations: TAUBIN_DEFAULT_ITERS,
+            method: SmoothingMethod::Taubin,
+            pass_band: TAUBIN_DEFAULT_BAND,
+            scale: TAUBIN_DEFAULT_SCALE,
+            weighting: SmoothingWeighting::Uniform,
+            alpha: HC_DEFAULT_ALPHA,
+            beta: HC_DEFAULT_BETA,
+            preserve_boundary: false,
+            preserve_interfaces: false,
+            freeze: Vec::new(),
+            guard: None,
+            volume: None,
+            features: FeatureOptions::default(),
+        }
+    }
+}
+
+impl SmoothOptions {
+    /// Number of smoothing iterations.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    /// Smoothing method.
+    pub fn method(mut self, method: SmoothingMethod) -> Self {
+        self.method = method;
+        self
+    }
+    /// Pass-band frequency (for Taubin only).
+    pub fn pass_band(mut self, pass_band: f64) -> Self {
+        self.pass_band = pass_band;
+        self
+    }
+    /// Scaling parameter for all smoothing methods.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+    /// Weighting of the neighbors of each node.
+    pub fn weighting(mut self, weighting: SmoothingWeighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+    /// Weight of the original positions in the HC correction (for HC only).
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+    /// Weight of each node against its neighbors in the HC correction (for
+    /// HC only).
+    pub fn beta(mut self, beta: f64) -> Self {
+        self.beta = beta;
+        self
+    }
+    /// Enables hierarchical smoothing, preserving both the boundary and the
+    /// interfaces between blocks.
+    pub fn hierarchical(mut self, hierarchical: bool) -> Self {
+        self.preserve_boundary = hierarchical;
+        self.preserve_interfaces = hierarchical;
+        self
+    }
+    /// Keeps nodes on the boundary moving only along the boundary.
+    pub fn preserve_boundary(mut self, preserve_boundary: bool) -> Self {
+        self.preserve_boundary = preserve_boundary;
+        self
+    }
+    /// Keeps nodes on interfaces between blocks moving only along the
+    /// interfaces.
+    pub fn preserve_interfaces(mut self, preserve_interfaces: bool) -> Self {
+        self.preserve_interfaces = preserve_interfaces;
+        self
+    }
+    /// Numbers of blocks whose nodes are not moved at all.
+    pub fn freeze(mut self, blocks: Vec<usize>) -> Self {
+        self.freeze = blocks;
+        self
+    }
+    /// Guards each iteration against pushing the minimum scaled Jacobian of
+    /// any element below this value, damping and then reverting the offending
+    /// node moves, and stops early once the quality stops changing.
+    pub fn guard(mut self, guard: Option<f64>) -> Self {
+        self.guard = guard;
+        self
+    }
+    /// Restores the volume of each block to within this relative tolerance of
+    /// its volume before smoothing, by moving the surface of the block along
+    /// its normals, and reports the change in the volume of each block.
+    pub fn preserve_volume(mut self, tolerance: Option<f64>) -> Self {
+        self.volume = tolerance;
+        self
+    }
+    /// Sharp edges and corners to keep, moving nodes on edges only along
+    /// them and corners not at all.
+    pub fn features(mut self, features: FeatureOptions) -> Self {
+        self.features = features;
+        self
+    }
+    /// Smooths a mesh in place.
+    pub fn smooth(&self, mesh: &mut Mesh<3>, quiet: bool) -> Result<(), Error> {
+        let time = Instant::now();
+        let name = match self.method {
+            SmoothingMethod::Laplace => "Laplace",
+            SmoothingMethod::Taubin => "Taubin",
+            SmoothingMethod::Hc => "HC",
+        };
+        if self.method == SmoothingMethod::Hc
+            && !((0.0..=1.0).contains(&self.alpha) && (0.0..=1.0).contains(&self.beta))
+        {
+            return Err(Error::InvalidOption(format!(
+                "HC parameters alpha {} and beta {} must be in [0, 1]",
+                self.alpha, self.beta
+            )));
+        }
+        if let Some(tolerance) = self.volume
+            && !(tolerance.is_finite() && tolerance >= 0.0)
+        {
+            return Err(Error::InvalidOption(format!(
+                "Volume tolerance {tolerance} must be non-negative"
+            )));
+        }
+        if self.volume.is_some()
+            && !mesh.connectivities().iter().all(|block| {
+                matches!(
+                    block,
+                    Connectivity::Hexahedral(_) | Connectivity::Tetrahedral(_)
+                )
+            })
+        {
+            return Err(Error::InvalidOption(
+                "Volume preservation requires a hexahedral or tetrahedral mesh".into(),
+            ));
+        }
+        let volumes = self.volume.map(|_| block_volumes(mesh));
+        let origin = mesh.coordinates().clone();
+        let features = if self.features.is_empty() {
+            None
+        } else {
+            let features = self.feature_edges(mesh, &origin)?;
             crate::echo!(
                 quiet,
-                "   \x1b[1;96mSmoothing\x1b[0m with {iterations} iterations of Laplace"
+                "     \x1b[1;96mKeeping\x1b[0m {} feature edges",
+                features.0.len()
             );
-            Smoothing::Laplace {
-                iterations,
-                scale,
-                weighting: Weighting::Uniform,
-                preserve_boundary: hierarchical,
-                preserve_interfaces: hierarchical,
+            Some(features)
+        };
+        let adjacency = if self.relaxes() {
+            self.adjacency(mesh, features.as_ref())?
+        } else {
+            Vec::new()
+        };
+        match self.guard {
+            None => {
+                crate::echo!(
+                    quiet,
+                    "   \x1b[1;96mSmoothing\x1b[0m with {} iterations of {name}",
+                    self.iterations
+                );
+                self.step(mesh, self.iterations, &origin, &adjacency)?;
+                crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
+            }
+            Some(minimum) => {
+                if !(-1.0..=1.0).contains(&minimum) {
+                    return Err(Error::InvalidOption(format!(
+                        "Smoothing guard {minimum} is not a scaled Jacobian in [-1, 1]"
+                    )));
+                }
+                crate::echo!(
+                    quiet,
+                    "   \x1b[1;96mSmoothing\x1b[0m with {} iterations of {name} \x1b[2m[guard {minimum}]\x1b[0m",
+                    self.iterations
+                );
+                let [iterations, damped] = self.guarded(mesh, minimum, &origin, &adjacency)?;
+                crate::echo!(
+                    quiet,
+                    "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{iterations} iterations, {damped} nodes damped]\x1b[0m",
+                    time.elapsed()
+                );
             }
         }
-        "Taubin" | "taubin" => {
-            crate::echo!(
-                quiet,
-                "   \x1b[1;96mSmoothing\x1b[0m with {iterations} iterations of Taubin"
-            );
-            Smoothing::Taubin {
+        if let (Some(tolerance), Some(volumes)) = (self.volume, volumes) {
+            self.preserve(mesh, &volumes, tolerance, quiet)?;
+        }
+        Ok(())
+    }
+    /// Corrects the volume of each block back toward its volume before
+    /// smoothing, and reports the change in the volume of each block.
+    ///
+    /// Each correction moves the nodes on the surface of each block along the
+    /// gradient of its volume, the sum of the outward area vectors of the
+    /// faces around each node shared among their nodes. Since blocks share
+    /// the nodes of their interfaces, the step along each gradient comes from
+    /// the system that restores the volumes of all blocks to first order.
+    fn preserve(
+        &self,
+        mesh: &mut Mesh<3>,
+        volumes: &[f64],
+        tolerance: f64,
+        quiet: bool,
+    ) -> Result<(), Error> {
+        let time = Instant::now();
+        crate::echo!(
+            quiet,
+            "  \x1b[1;96mPreserving\x1b[0m volumes of {} blocks \x1b[2m[tolerance {tolerance}]\x1b[0m",
+            volumes.len()
+        );
+        let frozen = self.frozen(mesh)?;
+        let surfaces = block_surfaces(mesh);
+        let changed = |current: &[f64]| {
+            current
+                .iter()
+                .zip(volumes)
+                .any(|(current, volume)| (current - volume).abs() > tolerance * volume.abs())
+        };
+        let mut current = block_volumes(mesh);
+        let mut corrections = 0;
+        while corrections < VOLUME_CORRECTIONS && changed(&current) {
+            let mut points = points(mesh.coordinates());
+            let mut gradients: Vec<Vec<(usize, [f64; 3])>> = vec![Vec::new(); points.len()];
+            surfaces.iter().enumerate().for_each(|(block, faces)| {
+                faces.iter().for_each(|(face, centroid)| {
+                    let area = outward_area(face, centroid, &points);
+                    let share = 1.0 / face.len() as f64;
+                    face.iter()
+                        .filter(|&&node| !frozen[node])
+                        .for_each(|&node| {
+                            match gradients[node]
+                                .iter_mut()
+                                .find(|(other, _)| *other == block)
+                            {
+                                Some((_, gradient)) => {
+                                    (0..3).for_each(|i| gradient[i] += area[i] * share)
+                                }
+                                None => gradients[node].push((block, area.map(|a| a * share))),
+                            }
+                        })
+                })
+            });
+            let mut gram = vec![vec![0.0; volumes.len()]; volumes.len()];
+            gradients.iter().for_each(|gradients| {
+                gradients.iter().for_each(|(a, u)| {
+                    gradients
+                        .iter()
+                        .for_each(|(b, v)| gram[*a][*b] += dot(u, v))
+                })
+            });
+            let deficits = volumes
+                .iter()
+                .zip(&current)
+                .map(|(volume, current)| volume - current)
+                .collect();
+            let steps = solve(gram, deficits);
+            points
+                .iter_mut()
+                .zip(&gradients)
+                .for_each(|(point, gradients)| {
+                    gradients.iter().for_each(|(block, gradient)| {
+                        (0..3).for_each(|i| point[i] += gradient[i] * steps[*block])
+                    })
+                });
+            replace_coordinates(mesh, points.into_iter().map(Coordinate::from).collect());
+            current = block_volumes(mesh);
+            corrections += 1;
+        }
+        crate::echo!(
+            quiet,
+            "        \x1b[1;92mDone\x1b[0m {:?} \x1b[2m[{corrections} corrections]\x1b[0m",
+            time.elapsed()
+        );
+        current
+            .iter()
+            .zip(volumes)
+            .enumerate()
+            .for_each(|(block, (current, volume))| {
+                let number = mesh.blocks().map_or(block + 1, |numbers| numbers[block]);
+                let change = 100.0 * (current - volume) / volume;
+                let color = if (current - volume).abs() > tolerance * volume.abs() {
+                    91
+                } else {
+                    92
+                };
+                crate::echo!(
+                    quiet,
+                    "      \x1b[1;96mVolume\x1b[0m block {number} \x1b[{color}m{change:+.4}%\x1b[0m \x1b[2m[{volume:.6e} to {current:.6e}]\x1b[0m"
+                );
+            });
+        Ok(())
+    }
+    /// Whether smoothing is done here rather than by conspire, which does
+    /// Laplace and Taubin with uniform or cotangent weights and no frozen
+    /// blocks or features.
+    fn relaxes(&self) -> bool {
+        !(self.freeze.is_empty() && self.features.is_empty())
+            || self.method == SmoothingMethod::Hc
+            || self.weighting == SmoothingWeighting::InverseDistance
+    }
+    /// Smooths the given number of iterations, with conspire where it can and
+    /// otherwise here with the neighbors of each node from `adjacency`.
+    fn step(
+        &self,
+        mesh: &mut Mesh<3>,
+        iterations: usize,
+        origin: &Coordinates<3>,
+        adjacency: &[Vec<usize>],
+    ) -> Result<(), Error> {
+        if self.relaxes() {
+            return self.relax(mesh, iterations, origin, adjacency);
+        }
+        let Self {
+            method,
+            pass_band,
+            scale,
+            weighting,
+            preserve_boundary,
+            preserve_interfaces,
+            ..
+        } = *self;
+        let weighting = match weighting {
+            SmoothingWeighting::Cotangent => Weighting::Cotangent,
+            _ => Weighting::Uniform,
+        };
+        mesh.smooth(match method {
+            SmoothingMethod::Taubin => Smoothing::Taubin {
                 iterations,
                 pass_band,
                 scale,
-                weighting: Weighting::Uniform,
-                preserve_boundary: hierarchical,
-                preserve_interfaces: hierarchical,
+                weighting,
+                preserve_boundary,
+                preserve_interfaces,
+            },
+            _ => Smoothing::Laplace {
+                iterations,
+                scale,
+                weighting,
+                preserve_boundary,
+                preserve_interfaces,
+            },
+        })?;
+        Ok(())
+    }
+    /// Smooths the given number of iterations with any method and weighting.
+    ///
+    /// Each node moves by the scaled Laplacian, its offset from the weighted
+    /// average of its neighbors. For HC, each node is then pulled back by
+    /// beta of its own difference from the blend of its original (alpha) and
+    /// previous positions, and by the rest of the average of its neighbors.
+    fn relax(
+        &self,
+        mesh: &mut Mesh<3>,
+        iterations: usize,
+        origin: &Coordinates<3>,
+        adjacency: &[Vec<usize>],
+    ) -> Result<(), Error> {
+        let Self {
+            method,
+            pass_band,
+            scale,
+            weighting,
+            alpha,
+            beta,
+            ..
+        } = *self;
+        if weighting == SmoothingWeighting::Cotangent
+            && !mesh
+                .connectivities()
+                .iter()
+                .all(|block| block.number_of_nodes_per_element() == Some(3))
+        {
+            return Err(Error::InvalidOption(
+                "Cotangent weighting requires an all-triangular mesh".into(),
+            ));
+        }
+        let inflate = scale / (pass_band * scale - 1.0);
+        for iteration in 0..iterations {
+            let previous: Vec<[f64; 3]> = points(mesh.coordinates());
+            let weights = (weighting == SmoothingWeighting::Cotangent)
+                .then(|| cotangent_weights(mesh, &previous));
+            let scale = match method {
+                SmoothingMethod::Taubin if inflate < 0.0 && iteration % 2 == 1 => inflate,
+                _ => scale,
+            };
+            let mut current: Vec<[f64; 3]> = adjacency
+                .iter()
+                .enumerate()
+                .map(|(node, neighbors)| {
+                    let mut total = 0.0;
+                    let mut average = [0.0; 3];
+                    neighbors.iter().for_each(|&neighbor| {
+                        let weight = match weighting {
+                            SmoothingWeighting::Uniform => 1.0,
+                            SmoothingWeighting::Cotangent => weights
+                                .as_ref()
+                                .and_then(|weights| weights.get(&edge(node, neighbor)))
+                                .copied()
+                                .unwrap_or(0.0),
+                            SmoothingWeighting::InverseDistance => {
+                                1.0 / distance(&previous[node], &previous[neighbor])
+                                    .max(f64::EPSILON)
+                            }
+                        };
+                        total += weight;
+                        (0..3).for_each(|i| average[i] += previous[neighbor][i] * weight);
+                    });
+                    let x = previous[node];
+                    if total == 0.0 {
+                        x
+                    } else {
+                        [0, 1, 2].map(|i| x[i] + (average[i] / total - x[i]) * scale)
+                    }
+                })
+                .collect();
+            if method == SmoothingMethod::Hc {
+                let differences: Vec<[f64; 3]> = current
+                    .iter()
+                    .zip(&previous)
+                    .zip(origin.iter())
+                    .map(|((p, q), o)| {
+                        [0, 1, 2].map(|i| p[i] - (alpha * o[i] + (1.0 - alpha) * q[i]))
+                    })
+                    .collect();
+                current
+                    .iter_mut()
+                    .zip(adjacency)
+                    .zip(&differences)
+                    .filter(|((_, neighbors), _)| !neighbors.is_empty())
+                    .for_each(|((p, neighbors), b)| {
+                        let count = neighbors.len() as f64;
+                        (0..3).for_each(|i| {
+                            let average = neighbors
+                                .iter()
+                                .map(|&neighbor| differences[neighbor][i])
+                                .sum::<f64>()
+                                / count;
+                            p[i] -= beta * b[i] + (1.0 - beta) * average
+                        })
+                    });
             }
+            replace_coordinates(mesh, current.into_iter().map(Coordinate::from).collect());
         }
-        _ => {
-            return Err(ErrorWrapper::from(format!(
-                "Invalid smoothing method {method} specified"
-            )));
+        Ok(())
+    }
+    /// Neighbors of each node, limited to nodes on the boundary for those on
+    /// the boundary and likewise for interfaces, as in conspire, and none for
+    /// the nodes of frozen blocks. Nodes on the given features move only
+    /// along them, and their corners not at all.
+    fn adjacency(
+        &self,
+        mesh: &Mesh<3>,
+        features: Option<&(Edges, Vec<bool>)>,
+    ) -> Result<Vec<Vec<usize>>, Error> {
+        let mut adjacency = mesh.node_node_connectivity().to_vec();
+        let blocks: Vec<usize> = mesh
+            .connectivities()
+            .iter()
+            .enumerate()
+            .flat_map(|(block, connectivity)| repeat_n(block, connectivity.number_of_elements()))
+            .collect();
+        let mut constrained = vec![false; mesh.number_of_nodes()];
+        if self.preserve_boundary {
+            mesh.exterior_faces()
+                .iter()
+                .flatten()
+                .for_each(|&node| constrained[node] = true);
+        }
+        if self.preserve_interfaces {
+            mesh.node_element_connectivity()
+                .iter()
+                .zip(constrained.iter_mut())
+                .filter(|(elements, _)| {
+                    elements
+                        .iter()
+                        .any(|&element| blocks[element] != blocks[elements[0]])
+                })
+                .for_each(|(_, constrained)| *constrained = true);
+        }
+        adjacency
+            .iter_mut()
+            .enumerate()
+            .filter(|(node, _)| constrained[*node])
+            .for_each(|(_, neighbors)| neighbors.retain(|&other| constrained[other]));
+        if let Some((edges, corners)) = features {
+            let mut along: Vec<Vec<usize>> = vec![Vec::new(); adjacency.len()];
+            edges.iter().for_each(|&(u, v)| {
+                along[u].push(v);
+                along[v].push(u);
+            });
+            adjacency
+                .iter_mut()
+                .zip(along.iter().zip(corners))
+                .filter(|(_, (along, _))| !along.is_empty())
+                .for_each(|(neighbors, (along, &corner))| {
+                    if corner {
+                        neighbors.clear()
+                    } else {
+                        neighbors.retain(|other| along.contains(other))
+                    }
+                });
+        }
+        self.frozen(mesh)?
+            .iter()
+            .zip(adjacency.iter_mut())
+            .filter(|(frozen, _)| **frozen)
+            .for_each(|(_, neighbors)| neighbors.clear());
+        Ok(adjacency)
+    }
+    /// Feature edges of the surface of the mesh in its original position, and
+    /// whether each node is a corner of them.
+    fn feature_edges(
+        &self,
+        mesh: &Mesh<3>,
+        origin: &Coordinates<3>,
+    ) -> Result<(Edges, Vec<bool>), Error> {
+        let faces = if mesh.connectivities().iter().all(|block| {
+            matches!(
+                block,
+                Connectivity::Triangular(_)
+                    | Connectivity::Quadrilateral(_)
+                    | Connectivity::Polygonal(_)
+            )
+        }) {
+            mesh.connectivities()
+                .iter()
+                .flatten()
+                .map(|face| face.to_vec())
+                .collect()
+        } else {
+            mesh.exterior_faces()
+        };
+        let points = points(origin);
+        let edges = self.features.edges(&faces, &points)?;
+        let corners = self.features.corners(&edges, &points);
+        Ok((edges, corners))
+    }
+    /// Whether each node is in an element of a frozen block.
+    fn frozen(&self, mesh: &Mesh<3>) -> Result<Vec<bool>, Error> {
+        let numbers: Vec<usize> = (0..mesh.number_of_element_blocks())
+            .map(|block| mesh.blocks().map_or(block + 1, |numbers| numbers[block]))
+            .collect();
+        let mut frozen = vec![false; numbers.len()];
+        for number in &self.freeze {
+            match numbers.iter().position(|block| block == number) {
+                Some(block) => frozen[block] = true,
+                None => {
+                    return Err(Error::InvalidOption(format!(
+                        "Block {number} to freeze is not in the mesh"
+                    )));
+                }
+            }
+        }
+        let blocks: Vec<usize> = mesh
+            .connectivities()
+            .iter()
+            .enumerate()
+            .flat_map(|(block, connectivity)| repeat_n(block, connectivity.number_of_elements()))
+            .collect();
+        Ok(mesh
+            .node_element_connectivity()
+            .iter()
+            .map(|elements| elements.iter().any(|&element| frozen[blocks[element]]))
+            .collect())
+    }
+    /// Smooths one step at a time, a shrinking and inflating pair for Taubin,
+    /// and after each step damps the moves of nodes of any element pushed
+    /// below the minimum scaled Jacobian, halving them until they are
+    /// reverted. Returns the iterations done and the number of nodes damped.
+    fn guarded(
+        &self,
+        mesh: &mut Mesh<3>,
+        minimum: f64,
+        origin: &Coordinates<3>,
+        adjacency: &[Vec<usize>],
+    ) -> Result<[usize; 2], Error> {
+        let step = match self.method {
+            SmoothingMethod::Laplace | SmoothingMethod::Hc => 1,
+            SmoothingMethod::Taubin => 2,
+        };
+        let elements: Vec<Vec<usize>> = mesh
+            .connectivities()
+            .iter()
+            .flat_map(|block| block.iter().map(|element| block.element_nodes(element)))
+            .collect();
+        let mut quality = scaled_jacobians(mesh);
+        let mut done = 0;
+        let mut damped = vec![false; mesh.number_of_nodes()];
+        while done < self.iterations {
+            let iterations = step.min(self.iterations - done);
+            let before = mesh.coordinates().clone();
+            self.step(mesh, iterations, origin, adjacency)?;
+            done += iterations;
+            let after = mesh.coordinates().clone();
+            let mut halvings = vec![0; before.len()];
+            let current = loop {
+                let current = scaled_jacobians(mesh);
+                let mut offending = vec![false; before.len()];
+                current
+                    .iter()
+                    .zip(&quality)
+                    .zip(&elements)
+                    .filter(|((new, old), _)| **new < minimum && new < old)
+                    .for_each(|(_, nodes)| nodes.iter().for_each(|&node| offending[node] = true));
+                if !offending.contains(&true) {
+                    break current;
+                }
+                offending
+                    .iter()
+                    .zip(halvings.iter_mut())
+                    .filter(|(offending, _)| **offending)
+                    .for_each(|(_, halving)| *halving += 1);
+                let coordinates = before
+                    .iter()
+                    .zip(after.iter())
+                    .zip(&halvings)
+                    .map(|((before, after), &halving)| {
+                        let factor = if halving > GUARD_HALVINGS {
+                            0.0
+                        } else {
+                            0.5_f64.powi(halving as i32)
+                        };
+                        Coordinate::from([
+                            before[0] + (after[0] - before[0]) * factor,
+                            before[1] + (after[1] - before[1]) * factor,
+                            before[2] + (after[2] - before[2]) * factor,
+                        ])
+                    })
+                    .collect();
+                replace_coordinates(mesh, coordinates);
+            };
+            halvings
+                .iter()
+                .zip(damped.iter_mut())
+                .for_each(|(&halving, damped)| *damped |= halving > 0);
+            let stalled = (worst(&current) - worst(&quality)).abs() < GUARD_TOLERANCE
+                && (mean(&current) - mean(&quality)).abs() < GUARD_TOLERANCE;
+            quality = current;
+            if stalled {
+                break;
+            }
+        }
+        Ok([done, damped.iter().filter(|&&damped| damped).count()])
+    }
+}
+
+/// Volume of each block.
+fn block_volumes(mesh: &Mesh<3>) -> Vec<f64> {
+    mesh.volumes()
+        .iter()
+        .map(|volumes| volumes.iter().sum())
+        .collect()
+}
+
+/// Faces on the surface of each block, those of only one of its elements,
+/// with the centroid of that element to orient them outward.
+fn block_surfaces(mesh: &Mesh<3>) -> Vec<Vec<(Vec<usize>, [f64; 3])>> {
+    let points = points(mesh.coordinates());
+    mesh.connectivities()
+        .iter()
+        .map(|connectivity| {
+            let mut faces: HashMap<Vec<usize>, (Vec<usize>, [f64; 3], usize)> = HashMap::new();
+            connectivity.iter().for_each(|nodes| {
+                let count = nodes.len() as f64;
+                let centroid = [0, 1, 2]
+                    .map(|i| nodes.iter().map(|&node| points[node][i]).sum::<f64>() / count);
+                connectivity
+                    .element_faces(nodes)
+                    .into_iter()
+                    .for_each(|face| {
+                        let mut key = face.clone();
+                        key.sort_unstable();
+                        faces.entry(key).or_insert((face, centroid, 0)).2 += 1
+                    })
+            });
+            faces
+                .into_values()
+                .filter(|(_, _, count)| *count == 1)
+                .map(|(face, centroid, _)| (face, centroid))
+                .collect()
+        })
+        .collect()
+}
+
+/// Solves a small symmetric system by Gaussian elimination with partial
+/// pivoting, leaving unknowns with no pivot, such as blocks with no free
+/// nodes on their surface, at zero.
+pub(crate) fn solve(mut matrix: Vec<Vec<f64>>, mut vector: Vec<f64>) -> Vec<f64> {
+    let size = vector.len();
+    let scale = (0..size).map(|i| matrix[i][i].abs()).fold(0.0, f64::max);
+    let mut pivots = vec![None; size];
+    let mut row = 0;
+    for column in 0..size {
+        let Some(pivot) = (row..size)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+        else {
+            break;
+        };
+        if matrix[pivot][column].abs() <= scale * 1e-12 {
+            continue;
+        }
+        matrix.swap(row, pivot);
+        vector.swap(row, pivot);
+        let (above, below) = matrix.split_at_mut(row + 1);
+        let pivot = &above[row];
+        for (other, entries) in below.iter_mut().enumerate() {
+            let factor = entries[column] / pivot[column];
+            entries
+                .iter_mut()
+                .zip(pivot)
+                .skip(column)
+                .for_each(|(entry, pivot)| *entry -= factor * pivot);
+            vector[row + 1 + other] -= factor * vector[row];
         }
-    };
-    mesh.smooth(smoothing)?;
-    crate::echo!(quiet, "        \x1b[1;92mDone\x1b[0m {:?}", time.elapsed());
-    Ok(())
+        pivots[column] = Some(row);
+        row += 1;
+    }
+    let mut solution = vec![0.0; size];
+    for column in (0..size).rev() {
+        if let Some(row) = pivots[column] {
+            let sum = (column + 1..size)
+                .map(|k| matrix[row][k] * solution[k])
+                .sum::<f64>();
+            solution[column] = (vector[row] - sum) / matrix[row][column];
+        }
+    }
+    solution
+}
+
+/// Area vector of a face, pointing away from the centroid of its element.
+fn outward_area(face: &[usize], centroid: &[f64; 3], points: &[[f64; 3]]) -> [f64; 3] {
+    let origin = &points[face[0]];
+    let area = (1..face.len() - 1).fold([0.0; 3], |area, corner| {
+        let u = sub(&points[face[corner]], origin);
+        let v = sub(&points[face[corner + 1]], origin);
+        add(&area, &scale(&cross(&u, &v), 0.5))
+    });
+    let sum = face
+        .iter()
+        .fold([0.0; 3], |sum, &node| add(&sum, &points[node]));
+    let outward = sub(&scale(&sum, 1.0 / face.len() as f64), centroid);
+    if dot(&area, &outward) < 0.0 {
+        scale(&area, -1.0)
+    } else {
+        area
+    }
+}
+
+/// Cotangent weight of each edge of the triangles of a mesh.
+fn cotangent_weights(mesh: &Mesh<3>, points: &[[f64; 3]]) -> HashMap<(usize, usize), f64> {
+    let mut weights = HashMap::new();
+    mesh.connectivities().iter().for_each(|block| {
+        block.iter().for_each(|element| {
+            let triangle = block.element_nodes(element);
+            (0..3).for_each(|local| {
+                let [i, j, k] = [0, 1, 2].map(|offset| triangle[(local + offset) % 3]);
+                let u = sub(&points[i], &points[k]);
+                let v = sub(&points[j], &points[k]);
+                let dot = dot(&u, &v);
+                let cross = norm(&cross(&u, &v));
+                *weights.entry(edge(i, j)).or_insert(0.0) += dot / cross.max(f64::EPSILON);
+            })
+        })
+    });
+    weights
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+pub(crate) fn points(coordinates: &Coordinates<3>) -> Vec<[f64; 3]> {
+    coordinates
+        .iter()
+        .map(|coordinate| [coordinate[0], coordinate[1], coordinate[2]])
+        .collect()
+}
+
+/// Minimum scaled Jacobian of each element, over all blocks in order.
+fn scaled_jacobians(mesh: &Mesh<3>) -> Vec<f64> {
+    mesh.minimum_scaled_jacobians()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn worst(quality: &[f64]) -> f64 {
+    quality.iter().copied().fold(f64::INFINITY, f64::min)
+}
+
+fn mean(quality: &[f64]) -> f64 {
+    quality.iter().sum::<f64>() / quality.len().max(1) as f64
+}
+
+/// Moves the nodes of a mesh, keeping its blocks, node sets, and side sets.
+pub(crate) fn replace_coordinates(mesh: &mut Mesh<3>, coordinates: Coordinates<3>) {
+    let node_sets = numbered(mesh.node_sets().to_vec(), mesh.node_set_numbers());
+    let side_sets = numbered(mesh.side_sets().to_vec(), mesh.side_set_numbers());
+    let (connectivities, _): (Connectivities, Coordinates<3>) =
+        replace(mesh, Mesh::from((Vec::new(), empty().collect()))).into();
+    *mesh = Mesh::from((connectivities, Set::from(coordinates)));
+    mesh.set_node_sets(node_sets);
+    mesh.set_side_sets(side_sets);
+}
+
+fn numbered<S>(members: S, numbers: Option<&[usize]>) -> Set<S> {
+    match numbers {
+        Some(numbers) => Set::from((members, numbers.to_vec())),
+        None => Set::from(members),
+    }
 }
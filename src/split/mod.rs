@@ -0,0 +1,236 @@
+use super::{
+    Error,
+    vector::{Point, add, cross, dot, scale, sub},
+};
+use conspire::{
+    geometry::{
+        Coordinate, Coordinates,
+        mesh::{Connectivities, Connectivity, Mesh, PrimitiveConnectivity},
+    },
+    math::Set,
+};
+use std::{array::from_fn, collections::HashMap};
+
+const PYRAMID_TETS: [[usize; 4]; 2] = [[0, 1, 2, 4], [0, 2, 3, 4]];
+
+const WEDGE_TETS: [[usize; 4]; 3] = [[0, 1, 2, 3], [1, 4, 5, 3], [1, 2, 5, 3]];
+
+const LATTICE_TETS: [[usize; 4]; 6] = [
+    [0, 1, 2, 6],
+    [0, 2, 3, 6],
+    [0, 3, 7, 6],
+    [0, 7, 4, 6],
+    [0, 4, 5, 6],
+    [0, 5, 1, 6],
+];
+
+const HEX_FACES: [[usize; 4]; 6] = [
+    [0, 1, 5, 4],
+    [1, 2, 6, 5],
+    [2, 3, 7, 6],
+    [3, 0, 4, 7],
+    [0, 3, 2, 1],
+    [4, 5, 6, 7],
+];
+
+/// Local tetrahedra splitting an element with the given number of nodes.
+///
+/// Tetrahedra, pyramids, wedges, and hexahedra (about the diagonal from node 0
+/// to node 6) are split; other elements give none.
+pub(crate) fn element_tetrahedra(nodes: usize) -> Option<&'static [[usize; 4]]> {
+    match nodes {
+        4 => Some(&[[0, 1, 2, 3]]),
+        5 => Some(&PYRAMID_TETS),
+        6 => Some(&WEDGE_TETS),
+        8 => Some(&LATTICE_TETS),
+        _ => None,
+    }
+}
+
+/// Splits each hexahedron of a lattice-aligned mesh into six tetrahedra.
+///
+/// Every hexahedron is split about its diagonal from node 0 to node 6, so face
+/// diagonals match between neighbors as long as all hexahedra share the same
+/// local orientation, as those meshed from a segmentation do.
+pub fn lattice_to_tetrahedra(mesh: Mesh<3>) -> Result<Mesh<3>, Error> {
+    let (blocks, numbers, points) = hexahedra(mesh)?;
+    let blocks = blocks
+        .iter()
+        .map(|hexahedra| {
+            hexahedra
+                .iter()
+                .flat_map(|hexahedron| {
+                    LATTICE_TETS.map(|tet| oriented(tet.map(|node| hexahedron[node]), &points))
+                })
+                .collect()
+        })
+        .collect();
+    Ok(tetrahedral(blocks, numbers, points))
+}
+
+/// Splits each hexahedron into 24 tetrahedra about its face and cell centers.
+///
+/// Face centers are shared between neighbors, so any conforming mesh of
+/// hexahedra, however oriented, splits into a conforming mesh of tetrahedra.
+pub fn hexahedra_to_tetrahedra(mesh: Mesh<3>) -> Result<Mesh<3>, Error> {
+    let (blocks, numbers, mut points) = hexahedra(mesh)?;
+    let mut centers: HashMap<[usize; 4], usize> = HashMap::new();
+    let mut tets = Vec::with_capacity(blocks.len());
+    for hexahedra in &blocks {
+        let mut block = Vec::with_capacity(24 * hexahedra.len());
+        for hexahedron in hexahedra {
+            let cell = points.len();
+            points.push(centroid(hexahedron, &points));
+            for face in HEX_FACES {
+                let nodes = face.map(|node| hexahedron[node]);
+                let mut key = nodes;
+                key.sort_unstable();
+                let center = *centers.entry(key).or_insert_with(|| {
+                    points.push(centroid(&nodes, &points));
+                    points.len() - 1
+                });
+                (0..4).for_each(|edge| {
+                    block.push(oriented(
+                        [nodes[edge], nodes[(edge + 1) % 4], center, cell],
+                        &points,
+                    ))
+                });
+            }
+        }
+        tets.push(block);
+    }
+    Ok(tetrahedral(tets, numbers, points))
+}
+
+/// Splits each tetrahedron into four hexahedra, one about each vertex.
+///
+/// Each hexahedron joins a vertex to the midpoints of its three edges, the
+/// centroids of its three faces, and the centroid of the tetrahedron. Edge
+/// midpoints and face centroids are shared between neighbors, so a conforming
+/// mesh of tetrahedra splits into a conforming mesh of hexahedra.
+pub fn tetrahedra_to_hexahedra(mesh: Mesh<3>) -> Result<Mesh<3>, Error> {
+    let (connectivities, coordinates): (Connectivities, Coordinates<3>) = mesh.into();
+    let (connectivities, numbers) = connectivities.into();
+    let mut points: Vec<Point> = coordinates
+        .into_iter()
+        .map(|coordinate| from_fn(|axis| coordinate[axis]))
+        .collect();
+    let mut centers: HashMap<Vec<usize>, usize> = HashMap::new();
+    let mut center = |nodes: &[usize], points: &mut Vec<Point>| {
+        let mut key = nodes.to_vec();
+        key.sort_unstable();
+        *centers.entry(key).or_insert_with(|| {
+            points.push(centroid(nodes, points));
+            points.len() - 1
+        })
+    };
+    let mut blocks = Vec::with_capacity(connectivities.len());
+    for connectivity in connectivities {
+        let Connectivity::Tetrahedral(tetrahedra) = connectivity else {
+            return Err(Error::Mesh(
+                "Splitting into hexahedra requires an all-tetrahedral mesh".into(),
+            ));
+        };
+        let mut block = Vec::with_capacity(4 * tetrahedra.iter().len());
+        for &tet in tetrahedra.iter() {
+            let cell = center(&tet, &mut points);
+            for vertex in 0..4 {
+                let [i, j, k, l] = from_fn(|offset| tet[(vertex + offset) % 4]);
+                let hexahedron = [
+                    i,
+                    center(&[i, j], &mut points),
+                    center(&[i, j, k], &mut points),
+                    center(&[i, k], &mut points),
+                    center(&[i, l], &mut points),
+                    center(&[i, j, l], &mut points),
+                    cell,
+                    center(&[i, k, l], &mut points),
+                ];
+                block.push(right_handed(hexahedron, &points));
+            }
+        }
+        blocks.push(Connectivity::Hexahedral(PrimitiveConnectivity::from(block)));
+    }
+    let connectivities = match numbers {
+        Some(numbers) => Connectivities::from((blocks, numbers)),
+        None => Connectivities::from(blocks),
+    };
+    let coordinates: Coordinates<3> = points.into_iter().map(Coordinate::from).collect();
+    Ok(Mesh::from((connectivities, Set::from(coordinates))))
+}
+
+type Hexahedra = (Vec<Vec<[usize; 8]>>, Option<Vec<usize>>, Vec<Point>);
+
+/// Takes apart an all-hexahedral mesh into its blocks and points.
+fn hexahedra(mesh: Mesh<3>) -> Result<Hexahedra, Error> {
+    let (connectivities, coordinates): (Connectivities, Coordinates<3>) = mesh.into();
+    let (connectivities, numbers) = connectivities.into();
+    let blocks = connectivities
+        .into_iter()
+        .map(|connectivity| match connectivity {
+            Connectivity::Hexahedral(hexahedra) => Ok(hexahedra.iter().copied().collect()),
+            _ => Err(Error::Mesh(
+                "Splitting into tetrahedra requires an all-hexahedral mesh".into(),
+            )),
+        })
+        .collect::<Result<Vec<Vec<[usize; 8]>>, Error>>()?;
+    let points = coordinates
+        .into_iter()
+        .map(|coordinate| from_fn(|axis| coordinate[axis]))
+        .collect();
+    Ok((blocks, numbers, points))
+}
+
+/// Assembles blocks of tetrahedra into a mesh, keeping the block numbers.
+fn tetrahedral(
+    blocks: Vec<Vec<[usize; 4]>>,
+    numbers: Option<Vec<usize>>,
+    points: Vec<Point>,
+) -> Mesh<3> {
+    let connectivities: Vec<Connectivity> = blocks
+        .into_iter()
+        .map(|tets| Connectivity::Tetrahedral(PrimitiveConnectivity::from(tets)))
+        .collect();
+    let connectivities = match numbers {
+        Some(numbers) => Connectivities::from((connectivities, numbers)),
+        None => Connectivities::from(connectivities),
+    };
+    let coordinates: Coordinates<3> = points.into_iter().map(Coordinate::from).collect();
+    Mesh::from((connectivities, Set::from(coordinates)))
+}
+
+fn centroid(nodes: &[usize], points: &[Point]) -> Point {
+    let sum = nodes
+        .iter()
+        .fold([0.0; 3], |sum, &node| add(&sum, &points[node]));
+    scale(&sum, 1.0 / nodes.len() as f64)
+}
+
+/// Orders the nodes of a tetrahedron to give it a positive volume.
+fn oriented(mut tet: [usize; 4], points: &[Point]) -> [usize; 4] {
+    if volume(tet, points) < 0.0 {
+        tet.swap(1, 2)
+    }
+    tet
+}
+
+/// Mirrors the node order of a hexahedron if its first corner is left-handed.
+fn right_handed(hexahedron: [usize; 8], points: &[Point]) -> [usize; 8] {
+    let [a, b, c, d, e, f, g, h] = hexahedron;
+    if volume([a, b, d, e], points) < 0.0 {
+        [a, d, c, b, e, h, g, f]
+    } else {
+        hexahedron
+    }
+}
+
+/// Six times the signed volume of a tetrahedron.
+fn volume(tet: [usize; 4], points: &[Point]) -> f64 {
+    let [a, b, c, d] = tet.map(|node| points[node]);
+    orientation(&a, &b, &c, &d)
+}
+
+/// Six times the signed volume of the tetrahedron with the given vertices.
+pub(crate) fn orientation(a: &Point, b: &Point, c: &Point, d: &Point) -> f64 {
+    dot(&sub(b, a), &cross(&sub(c, a), &sub(d, a)))
+}
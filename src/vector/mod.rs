@@ -0,0 +1,51 @@
+use std::f64::consts::PI;
+
+/// A point, or a vector between points, in three dimensions.
+pub(crate) type Point = [f64; 3];
+
+pub(crate) fn add(a: &Point, b: &Point) -> Point {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn sub(a: &Point, b: &Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: &Point, factor: f64) -> Point {
+    a.map(|component| component * factor)
+}
+
+pub(crate) fn dot(a: &Point, b: &Point) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: &Point, b: &Point) -> Point {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn norm(a: &Point) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Angle in degrees between two vectors.
+pub(crate) fn angle(a: &Point, b: &Point) -> f64 {
+    (dot(a, b) / (norm(a) * norm(b))).clamp(-1.0, 1.0).acos() * 180.0 / PI
+}
+
+pub(crate) fn distance(a: &Point, b: &Point) -> f64 {
+    norm(&sub(a, b))
+}
+
+/// The unit vector along a vector, which is NaN for a zero vector.
+pub(crate) fn normalized(a: &Point) -> Point {
+    scale(a, 1.0 / norm(a))
+}
+
+/// The unit vector along a vector, or none for a zero vector.
+pub(crate) fn direction(a: &Point) -> Option<Point> {
+    (norm(a) > 0.0).then(|| normalized(a))
+}
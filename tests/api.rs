@@ -0,0 +1,103 @@
+//! In-process tests of the library API against fixtures in tests/input.
+
+use automesh::{
+    DefeatureOptions, DiffOptions, Element, Error, ExtractOptions, FeatureOptions, MeshOptions,
+    RemeshOptions, SegmentOptions, Sizing, SmoothOptions, SmoothingMethod,
+    conspire::geometry::mesh::{IsotropicSizing, Mesh, Remeshing, RemeshingMetric},
+    read_mesh, read_segmentation,
+};
+use std::path::PathBuf;
+
+fn input(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("input")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn mesh_smooth_and_segment() -> Result<(), Error> {
+    let mut mesh = MeshOptions::new(Element::Hexahedra)
+        .remove(vec![0])
+        .mesh(&input("letter_f_3d.npy"), true)?;
+    assert_eq!(mesh.number_of_elements(), 39);
+    SmoothOptions::default()
+        .method(SmoothingMethod::Laplace)
+        .iterations(2)
+        .smooth(&mut mesh, true)?;
+    let segmented = SegmentOptions::new(1.0).segment(&mesh, true)?;
+    assert!(segmented.voxels.nel().iter().all(|&nel| nel > 0));
+    Ok(())
+}
+
+#[test]
+fn defeature_extract_and_diff_voxels() -> Result<(), Error> {
+    let read = || read_segmentation(&input("letter_f_3d.npy"), None, None, None, true, false);
+    let (voxels, _) = read()?;
+    let defeatured = DefeatureOptions::new(1).defeature_voxels(read()?.0, true);
+    let (extracted, _) = ExtractOptions::new([0, 0, 0], [1, 1, 1]).extract_voxels(&voxels, None)?;
+    assert_eq!(extracted.nel(), &[2, 2, 2]);
+    let difference = DiffOptions::default().diff_voxels(&voxels, &defeatured)?;
+    assert_eq!(difference.nel(), voxels.nel());
+    assert!(matches!(
+        DiffOptions::default().diff_voxels(&voxels, &extracted),
+        Err(Error::InvalidData(_))
+    ));
+    assert!(matches!(
+        ExtractOptions::new([0, 0, 0], [9, 9, 9]).extract_voxels(&voxels, None),
+        Err(Error::InvalidOption(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn invalid_options_are_typed() {
+    assert!(matches!(
+        "bilateral".parse::<SmoothingMethod>(),
+        Err(Error::InvalidOption(_))
+    ));
+    assert!(matches!(
+        MeshOptions::new(Element::Hexahedra).mesh(&input("letter_f_3d.foo"), true),
+        Err(Error::InvalidInput { .. })
+    ));
+}
+
+#[test]
+fn remesh_without_features_matches_conspire() -> Result<(), Error> {
+    let sphere = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("book")
+        .join("examples")
+        .join("remesh")
+        .join("sphere_radius_1.stl")
+        .to_string_lossy()
+        .into_owned();
+    let remeshed = RemeshOptions::default()
+        .iterations(2)
+        .sizing(Sizing::Uniform { size: Some(0.2) })
+        .features(FeatureOptions::default())
+        .remesh(read_mesh(&sphere, true, false)?, true)?;
+    let expected = read_mesh(&sphere, true, false)?
+        .remesh(Remeshing {
+            iterations: 2,
+            metric: RemeshingMetric::Isotropic(IsotropicSizing::Uniform { length: Some(0.2) }),
+        })
+        .expect("conspire failed to remesh");
+    let parts = |mesh: &Mesh<3>| {
+        let elements: Vec<Vec<usize>> = mesh
+            .connectivities()
+            .iter()
+            .flatten()
+            .map(|element| element.to_vec())
+            .collect();
+        let points: Vec<[f64; 3]> = mesh
+            .coordinates()
+            .into_iter()
+            .map(|point| [point[0], point[1], point[2]])
+            .collect();
+        (elements, points)
+    };
+    assert_eq!(parts(&remeshed), parts(&expected));
+    Ok(())
+}
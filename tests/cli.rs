@@ -1,7 +1,7 @@
 //! End-to-end smoke tests driving the compiled binary against fixtures in tests/input.
 
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
     sync::atomic::{AtomicUsize, Ordering},
 };
@@ -49,6 +49,35 @@ fn assert_nonempty(path: &PathBuf) {
     assert!(metadata.len() > 0, "output file is empty: {path:?}");
 }
 
+/// Meshes the letter F with the given element type and no smoothing (inp).
+fn letter_f(element: &str) -> PathBuf {
+    let output = out("inp");
+    run(&[
+        "mesh",
+        element,
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    output
+}
+
+/// Runs the binary with the given args and a metrics file, returning the
+/// minimum scaled Jacobian of the output.
+fn minimum_scaled_jacobian(args: &[&str]) -> f64 {
+    let metrics = out("csv");
+    run(&[args, &["--metrics", metrics.to_str().unwrap()]].concat());
+    std::fs::read_to_string(&metrics)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap().parse::<f64>().unwrap())
+        .fold(f64::INFINITY, f64::min)
+}
+
 #[test]
 fn mesh_hex_to_exo() {
     let output = out("exo");
@@ -63,6 +92,168 @@ fn mesh_hex_to_exo() {
     assert_nonempty(&output);
 }
 
+#[test]
+fn mesh_tet_to_inp() {
+    let output = out("inp");
+    run(&[
+        "mesh",
+        "tet",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    // Each of the 39 voxels of the letter splits into six tetrahedra.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    assert!(text.contains("type=C3D4"));
+    let elements = text
+        .lines()
+        .skip_while(|line| !line.starts_with("*Element"))
+        .take_while(|line| !line.starts_with("*Nset"))
+        .filter(|line| !line.starts_with('*'))
+        .take_while(|line| !line.is_empty())
+        .count();
+    assert_eq!(elements, 6 * 39);
+}
+
+#[test]
+fn convert_mesh_tet_to_hex() {
+    let tets = out("inp");
+    run(&[
+        "mesh",
+        "tet",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        tets.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    let output = out("inp");
+    run(&[
+        "convert",
+        "mesh",
+        "-i",
+        tets.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--hex",
+    ]);
+    // Each of the six tetrahedra of the 39 voxels splits into four hexahedra.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    assert!(text.contains("type=C3D8"));
+    let elements = text
+        .lines()
+        .skip_while(|line| !line.starts_with("*Element"))
+        .filter(|line| !line.starts_with('*'))
+        .take_while(|line| !line.is_empty())
+        .count();
+    assert_eq!(elements, 4 * 6 * 39);
+}
+
+#[test]
+fn mesh_hex_sets_to_inp() {
+    let output = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("quadruple_2_blocks_void.spn").to_str().unwrap(),
+        "-x",
+        "4",
+        "-y",
+        "1",
+        "-z",
+        "1",
+        "-o",
+        output.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    // Voxels 11, 21, void, 11 give boundary, void, and interface_11_21 sets.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    let sides = |name: &str| {
+        text.lines()
+            .skip_while(|line| *line != format!("*Surface, type=ELEMENT, name={name}"))
+            .skip(1)
+            .take_while(|line| !line.starts_with('*'))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sides("boundary").len(), 14);
+    assert_eq!(sides("void"), ["2, S6", "3, S4"]);
+    assert_eq!(sides("interface_11_21"), ["1, S4"]);
+    assert!(!text.contains("name=SURF") && !text.contains("nset=NSET"));
+    let nodes = text
+        .lines()
+        .skip_while(|line| *line != "*Nset, nset=interface_11_21")
+        .nth(1);
+    assert_eq!(nodes, Some("2, 7, 12, 17"));
+}
+
+#[test]
+fn convert_mesh_sidesets_to_inp() {
+    let mesh = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        mesh.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    let output = out("inp");
+    run(&[
+        "convert",
+        "mesh",
+        "-i",
+        mesh.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--sideset",
+        "bottom:z<0.01",
+        "--sideset",
+        "down:nz<-0.9",
+        "--sideset",
+        "floor:z<=0",
+        "--nodeset",
+        "bottom:z <= 0.01",
+    ]);
+    // The letter is extruded, so every downward face is on the bottom plane.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    let lines = |header: &str| {
+        text.lines()
+            .skip_while(|line| *line != header)
+            .skip(1)
+            .take_while(|line| !line.starts_with('*'))
+            .collect::<Vec<_>>()
+    };
+    let bottom = lines("*Surface, type=ELEMENT, name=bottom");
+    assert_eq!(bottom.len(), 20);
+    assert_eq!(bottom, lines("*Surface, type=ELEMENT, name=down"));
+    assert_eq!(bottom, lines("*Surface, type=ELEMENT, name=floor"));
+    assert!(!lines("*Nset, nset=bottom").is_empty());
+    // The strict comparison leaves out the faces on the plane z = 0.
+    let status = Command::new(BIN)
+        .args([
+            "convert",
+            "mesh",
+            "-i",
+            mesh.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "--sideset",
+            "floor:z<0",
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(!status.success(), "z<0 selected the faces on z = 0");
+}
+
 #[test]
 fn mesh_tri_to_stl() {
     let output = out("stl");
@@ -188,14 +379,20 @@ fn smooth_poly() {
         metrics.to_str().unwrap(),
     ]);
     assert_nonempty(&output);
-    // Polyhedra have no Verdict metrics, so every column is NaN rather than a panic.
+    // Polyhedra have no Verdict metrics, so those columns are NaN rather than a
+    // panic, but their volumes come from their faces.
     let table = std::fs::read_to_string(&metrics).expect("metrics file was not created");
     let mut rows = table.lines().skip(1).peekable();
     assert!(rows.peek().is_some(), "metrics file has no rows");
     rows.for_each(|row| {
+        let values: Vec<&str> = row.split(',').map(str::trim).collect();
+        assert!(
+            values[..3].iter().all(|&value| value == "NaN"),
+            "expected NaN shape metrics, got {row:?}"
+        );
         assert!(
-            row.split(',').all(|value| value.trim() == "NaN"),
-            "expected all-NaN row, got {row:?}"
+            values[3].parse::<f64>().unwrap() > 0.0,
+            "expected a positive volume, got {row:?}"
         )
     });
 }
@@ -261,6 +458,177 @@ fn metrics_csv_and_npy() {
     }
 }
 
+#[test]
+fn metrics_report_gates() {
+    let inp = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        inp.to_str().unwrap(),
+    ]);
+    run(&[
+        "metrics",
+        "-i",
+        inp.to_str().unwrap(),
+        "--report",
+        "--gate",
+        "jacobian>=0.2",
+    ]);
+    // Unit cubes have a scaled Jacobian of one, so cannot pass this gate.
+    let status = Command::new(BIN)
+        .args([
+            "metrics",
+            "-i",
+            inp.to_str().unwrap(),
+            "--gate",
+            "jacobian>1",
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(!status.success(), "failing gate exited successfully");
+}
+
+#[test]
+fn metrics_columns() {
+    let inp = out("inp");
+    run(&[
+        "mesh",
+        "tet",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        inp.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    let csv = out("csv");
+    run(&[
+        "metrics",
+        "-i",
+        inp.to_str().unwrap(),
+        "-o",
+        csv.to_str().unwrap(),
+        "--columns",
+        "condition",
+        "min_dihedral",
+        "max_dihedral",
+    ]);
+    let text = std::fs::read_to_string(&csv).expect("metrics output was not created");
+    let mut lines = text.lines();
+    assert_eq!(
+        lines.next(),
+        Some("condition number,minimum dihedral angle,maximum dihedral angle")
+    );
+    // Each tetrahedron of a split cube has dihedral angles of 45 to 90 degrees.
+    lines.for_each(|line| {
+        let values: Vec<f64> = line
+            .split(',')
+            .map(|value| value.trim().parse().unwrap())
+            .collect();
+        assert!(values[0] >= 1.0);
+        assert!((values[1] - 45.0).abs() < 1e-6 && (values[2] - 90.0).abs() < 1e-6);
+    });
+}
+
+#[test]
+fn metrics_polyhedral() {
+    let vtu = out("vtu");
+    run(&[
+        "mesh",
+        "poly",
+        "-i",
+        input("single.stl").to_str().unwrap(),
+        "-o",
+        vtu.to_str().unwrap(),
+    ]);
+    let csv = out("csv");
+    run(&[
+        "metrics",
+        "-i",
+        vtu.to_str().unwrap(),
+        "-o",
+        csv.to_str().unwrap(),
+        "--columns",
+        "volume",
+        "planarity",
+        "centroid_ratio",
+        "non_orthogonality",
+        "fv_skewness",
+    ]);
+    let text = std::fs::read_to_string(&csv).expect("metrics output was not created");
+    let mut lines = text.lines();
+    assert_eq!(
+        lines.next(),
+        Some(
+            "element volume,maximum face nonplanarity,centroid face distance ratio,\
+             maximum non-orthogonality,maximum finite volume skewness"
+        )
+    );
+    lines.for_each(|line| {
+        let values: Vec<f64> = line
+            .split(',')
+            .map(|value| value.trim().parse().unwrap())
+            .collect();
+        assert!(values[0] > 0.0);
+        assert!(values[1] < 1e-6);
+        assert!(values[2] > 0.0 && values[2] <= 1.0 + 1e-12);
+        assert!((0.0..90.0).contains(&values[3]));
+        assert!((0.0..1.0).contains(&values[4]));
+    });
+    // The scaled Jacobian does not apply to polyhedra, so none fail its gate.
+    run(&[
+        "metrics",
+        "-i",
+        vtu.to_str().unwrap(),
+        "--gate",
+        "jacobian>=0.2",
+        "--gate",
+        "volume>0",
+    ]);
+}
+
+#[test]
+fn metric_fields_in_vtu() {
+    let vtu = out("vtu");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        vtu.to_str().unwrap(),
+        "--metric-fields",
+    ]);
+    let text = std::fs::read_to_string(&vtu).expect("mesh output was not created");
+    [
+        "block_id",
+        "maximum_edge_ratio",
+        "minimum_scaled_jacobian",
+        "maximum_skew",
+        "volume",
+    ]
+    .iter()
+    .for_each(|name| {
+        assert!(
+            text.contains(&format!("Name=\"{name}\"")),
+            "no {name} field"
+        )
+    });
+    let metrics = out("vtu");
+    run(&[
+        "metrics",
+        "-i",
+        vtu.to_str().unwrap(),
+        "-o",
+        metrics.to_str().unwrap(),
+    ]);
+    assert_nonempty(&metrics);
+}
+
 #[test]
 fn smooth_taubin() {
     let inp = out("inp");
@@ -286,7 +654,7 @@ fn smooth_taubin() {
 }
 
 #[test]
-fn remesh_triangles() {
+fn smooth_hc_triangles() {
     let stl = out("stl");
     run(&[
         "mesh",
@@ -296,88 +664,423 @@ fn remesh_triangles() {
         "-o",
         stl.to_str().unwrap(),
     ]);
-    let output = out("stl");
+    for weighting in ["uniform", "cotangent", "inverse-distance"] {
+        let output = out("stl");
+        run(&[
+            "smooth",
+            "-i",
+            stl.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "-m",
+            "hc",
+            "-w",
+            weighting,
+            "-n",
+            "10",
+        ]);
+        assert_nonempty(&output);
+    }
+}
+
+#[test]
+fn smooth_freezes_block() {
+    let output = out("inp");
     run(&[
-        "remesh",
+        "mesh",
+        "hex",
         "-i",
-        stl.to_str().unwrap(),
+        input("cube_with_inclusion.spn").to_str().unwrap(),
+        "-x",
+        "3",
+        "-y",
+        "3",
+        "-z",
+        "3",
         "-o",
         output.to_str().unwrap(),
-        "uniform",
+        "smooth",
+        "--freeze",
+        "88",
+        "--preserve-boundary",
         "-n",
-        "2",
+        "10",
     ]);
-    assert_nonempty(&output);
+    // The nodes of the inclusion, the center voxel, stay on the lattice.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    let nodes: Vec<&str> = text
+        .lines()
+        .skip_while(|line| *line != "*Node")
+        .skip(1)
+        .take_while(|line| !line.starts_with('*'))
+        .collect();
+    for (node, coordinates) in [
+        (22, "1, 1, 1"),
+        (23, "2, 1, 1"),
+        (26, "1, 2, 1"),
+        (27, "2, 2, 1"),
+        (38, "1, 1, 2"),
+        (39, "2, 1, 2"),
+        (42, "1, 2, 2"),
+        (43, "2, 2, 2"),
+    ] {
+        assert_eq!(nodes[node - 1], format!("{node}, {coordinates}"));
+    }
+    assert_ne!(nodes[0], "1, 0, 0, 0");
 }
 
 #[test]
-fn segment_mesh_to_segmentation() {
-    let exo = out("exo");
+fn smooth_preserves_volume() {
+    let output = out("inp");
+    let metrics = out("csv");
     run(&[
         "mesh",
         "hex",
         "-i",
-        input("letter_f_3d.npy").to_str().unwrap(),
-        "-o",
-        exo.to_str().unwrap(),
-    ]);
-    let output = out("npy");
-    run(&[
-        "segment",
-        "-i",
-        exo.to_str().unwrap(),
+        input("cube_with_inclusion.spn").to_str().unwrap(),
+        "-x",
+        "3",
+        "-y",
+        "3",
+        "-z",
+        "3",
         "-o",
         output.to_str().unwrap(),
-        "-s",
-        "1.0",
+        "--metrics",
+        metrics.to_str().unwrap(),
+        "smooth",
+        "-n",
+        "10",
+        "--preserve-volume",
+        "0.001",
     ]);
-    assert_nonempty(&output);
+    // Without the correction, the smoothing shrinks the cube to about 24.65.
+    let volume: f64 = std::fs::read_to_string(&metrics)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(3).unwrap().parse::<f64>().unwrap())
+        .sum();
+    assert!((volume - 27.0).abs() < 0.027, "volume {volume}");
 }
 
 #[test]
-fn diff_segmentations() {
-    let output = out("npy");
-    run(&[
-        "diff",
+fn smooth_taubin_guarded() {
+    let inp = letter_f("hex");
+    let output = out("inp");
+    let minimum = minimum_scaled_jacobian(&[
+        "smooth",
         "-i",
-        input("letter_f_3d.npy").to_str().unwrap(),
-        input("letter_f_3d.npy").to_str().unwrap(),
+        inp.to_str().unwrap(),
         "-o",
         output.to_str().unwrap(),
+        "-n",
+        "60",
+        "-s",
+        "0.9",
+        "--guard",
+        "0.3",
     ]);
-    assert_nonempty(&output);
+    // Unguarded, this much smoothing inverts elements of the thin letter.
+    assert!(minimum >= 0.3, "minimum scaled Jacobian {minimum}");
 }
 
 #[test]
-fn extract_subrange() {
-    let output = out("npy");
+fn improve_untangles_smoothed_hex() {
+    let inp = letter_f("hex");
+    let tangled = out("inp");
     run(&[
-        "extract",
+        "smooth",
         "-i",
-        input("letter_f_3d.npy").to_str().unwrap(),
+        inp.to_str().unwrap(),
+        "-o",
+        tangled.to_str().unwrap(),
+        "-n",
+        "10",
+        "-s",
+        "0.9",
+    ]);
+    let output = out("inp");
+    let minimum = minimum_scaled_jacobian(&[
+        "improve",
+        "-i",
+        tangled.to_str().unwrap(),
         "-o",
         output.to_str().unwrap(),
-        "--xmin",
-        "0",
-        "--xmax",
-        "1",
-        "--ymin",
-        "0",
-        "--ymax",
-        "1",
-        "--zmin",
-        "0",
-        "--zmax",
-        "1",
     ]);
-    assert_nonempty(&output);
+    // The smoothing inverts 15 of the 39 elements.
+    assert!(minimum > 0.0, "minimum scaled Jacobian {minimum}");
 }
 
 #[test]
-fn defeature_segmentation() {
-    let output = out("npy");
-    run(&[
-        "defeature",
+fn improve_untangles_smoothed_tet() {
+    let inp = letter_f("tet");
+    let tangled = out("inp");
+    let before = minimum_scaled_jacobian(&[
+        "smooth",
+        "-i",
+        inp.to_str().unwrap(),
+        "-o",
+        tangled.to_str().unwrap(),
+        "-n",
+        "10",
+        "-s",
+        "1.1",
+    ]);
+    assert!(before < 0.0, "smoothing left every tetrahedron valid");
+    let output = out("inp");
+    let after = minimum_scaled_jacobian(&[
+        "improve",
+        "-i",
+        tangled.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(after > 0.0, "minimum scaled Jacobian {after}");
+}
+
+#[test]
+fn remesh_triangles() {
+    let stl = out("stl");
+    run(&[
+        "mesh",
+        "tri",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        stl.to_str().unwrap(),
+    ]);
+    let output = out("stl");
+    run(&[
+        "remesh",
+        "-i",
+        stl.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "uniform",
+        "-n",
+        "2",
+    ]);
+    assert_nonempty(&output);
+}
+
+/// Number of elements of a mesh, from the rows of its metrics.
+fn elements(mesh: &Path) -> usize {
+    let csv = out("csv");
+    run(&[
+        "metrics",
+        "-i",
+        mesh.to_str().unwrap(),
+        "-o",
+        csv.to_str().unwrap(),
+    ]);
+    std::fs::read_to_string(&csv).unwrap().lines().count() - 1
+}
+
+#[test]
+fn remesh_field_and_anisotropic() {
+    let sphere = sphere();
+    let count = elements(&sphere);
+    let remeshed = [0.15, 0.3].map(|size: f64| {
+        let field = out("npy");
+        let mut header =
+            format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({count},), }}");
+        header.push_str(&" ".repeat(63 - (10 + header.len()) % 64));
+        header.push('\n');
+        let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+        npy.extend((header.len() as u16).to_le_bytes());
+        npy.extend(header.as_bytes());
+        (0..count).for_each(|_| npy.extend(size.to_le_bytes()));
+        std::fs::write(&field, npy).unwrap();
+        let output = out("stl");
+        run(&[
+            "remesh",
+            "-i",
+            sphere.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "field",
+            "-f",
+            field.to_str().unwrap(),
+        ]);
+        elements(&output)
+    });
+    assert!(
+        remeshed[0] > remeshed[1],
+        "finer size field gave {} elements, coarser gave {}",
+        remeshed[0],
+        remeshed[1]
+    );
+    let output = out("stl");
+    run(&[
+        "remesh",
+        "-i",
+        sphere.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "anisotropic",
+        "--minimum",
+        "0.05",
+        "--maximum",
+        "0.3",
+        "-t",
+        "0.01",
+    ]);
+    assert_nonempty(&output);
+}
+
+#[test]
+fn remesh_rejects_a_negative_gradation() {
+    let sphere = sphere();
+    let output = out("stl");
+    let status = Command::new(BIN)
+        .args([
+            "remesh",
+            "-i",
+            sphere.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "adaptive",
+            "--minimum",
+            "0.05",
+            "--maximum",
+            "0.3",
+            "--gradation=-1",
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(!status.success(), "remesh accepted a negative gradation");
+}
+
+#[test]
+fn remesh_keeps_features() {
+    let stl = out("stl");
+    run(&[
+        "mesh",
+        "tri",
+        "-i",
+        input("cube.spn").to_str().unwrap(),
+        "-x",
+        "2",
+        "-y",
+        "2",
+        "-z",
+        "2",
+        "-o",
+        stl.to_str().unwrap(),
+    ]);
+    let output = out("inp");
+    run(&[
+        "remesh",
+        "-i",
+        stl.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--feature-angle",
+        "30",
+        "uniform",
+        "-s",
+        "0.25",
+    ]);
+    // The corners of the box survive, and its edges stay sharp.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    let extremes: Vec<usize> = text
+        .lines()
+        .skip_while(|line| *line != "*Node")
+        .skip(1)
+        .take_while(|line| !line.starts_with('*'))
+        .map(|line| {
+            line.split(", ")
+                .skip(1)
+                .map(|value| value.parse::<f64>().unwrap())
+                .filter(|&value| value == 0.0 || value == 2.0)
+                .count()
+        })
+        .collect();
+    assert_eq!(extremes.iter().filter(|&&count| count == 3).count(), 8);
+    assert!(extremes.iter().filter(|&&count| count == 2).count() >= 12 * 4);
+    let smoothed = out("stl");
+    run(&[
+        "smooth",
+        "-i",
+        output.to_str().unwrap(),
+        "-o",
+        smoothed.to_str().unwrap(),
+        "--feature-angle",
+        "30",
+    ]);
+    assert_nonempty(&smoothed);
+}
+
+#[test]
+fn segment_mesh_to_segmentation() {
+    let exo = out("exo");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        exo.to_str().unwrap(),
+    ]);
+    let output = out("npy");
+    run(&[
+        "segment",
+        "-i",
+        exo.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-s",
+        "1.0",
+    ]);
+    assert_nonempty(&output);
+}
+
+#[test]
+fn diff_segmentations() {
+    let output = out("npy");
+    run(&[
+        "diff",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert_nonempty(&output);
+}
+
+#[test]
+fn extract_subrange() {
+    let output = out("npy");
+    run(&[
+        "extract",
+        "-i",
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--xmin",
+        "0",
+        "--xmax",
+        "1",
+        "--ymin",
+        "0",
+        "--ymax",
+        "1",
+        "--zmin",
+        "0",
+        "--zmax",
+        "1",
+    ]);
+    assert_nonempty(&output);
+}
+
+#[test]
+fn defeature_segmentation() {
+    let output = out("npy");
+    run(&[
+        "defeature",
         "-i",
         input("letter_f_3d.npy").to_str().unwrap(),
         "-o",
@@ -387,3 +1090,483 @@ fn defeature_segmentation() {
     ]);
     assert_nonempty(&output);
 }
+
+#[test]
+fn diff_meshes() {
+    let vtu = out("vtu");
+    run(&[
+        "mesh",
+        "poly",
+        "-i",
+        sphere().to_str().unwrap(),
+        "-o",
+        vtu.to_str().unwrap(),
+        "-s",
+        "5",
+    ]);
+    let output = out("vtu");
+    let stdout = Command::new(BIN)
+        .args([
+            "diff",
+            "-i",
+            sphere().to_str().unwrap(),
+            vtu.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .output()
+        .expect("failed to spawn automesh")
+        .stdout;
+    let stdout = String::from_utf8_lossy(&stdout);
+    // The dual mesh fits the unit sphere closely, so its surface nodes lie
+    // within a fraction of the radius of it.
+    let hausdorff: f64 = stdout
+        .lines()
+        .find_map(|line| line.split_once("Hausdorff\x1b[0m "))
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .expect("no Hausdorff distance was reported")
+        .parse()
+        .unwrap();
+    assert!(hausdorff > 0.0 && hausdorff < 0.2, "{hausdorff}");
+    let text = std::fs::read_to_string(&output).expect("diff output was not created");
+    assert!(text.contains("<PointData>") && text.contains("Name=\"distance\""));
+    // A segmentation cannot be compared with a mesh.
+    let status = Command::new(BIN)
+        .args([
+            "diff",
+            "-i",
+            input("letter_f_3d.npy").to_str().unwrap(),
+            vtu.to_str().unwrap(),
+            "-o",
+            out("vtu").to_str().unwrap(),
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(
+        !status.success(),
+        "diff compared a segmentation with a mesh"
+    );
+}
+
+#[test]
+fn diff_meshes_with_node_sets() {
+    let inp = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        input("quadruple_2_blocks_void.spn").to_str().unwrap(),
+        "-x",
+        "4",
+        "-y",
+        "1",
+        "-z",
+        "1",
+        "-o",
+        inp.to_str().unwrap(),
+        "-r",
+        "0",
+    ]);
+    let output = out("vtu");
+    run(&[
+        "diff",
+        "-i",
+        inp.to_str().unwrap(),
+        inp.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    // The distance joins the node sets in the point data the writer gives.
+    let text = std::fs::read_to_string(&output).expect("diff output was not created");
+    assert_eq!(text.matches("<PointData>").count(), 1);
+    let point_data = &text[text.find("<PointData>").unwrap()..text.find("</PointData>").unwrap()];
+    assert!(point_data.contains("Name=\"NodeSet1\"") && point_data.contains("Name=\"distance\""));
+}
+
+/// A little-endian grayscale TIFF of one page and one strip of the given
+/// pixel bytes, with the given dimensions, bits per sample, and next directory.
+fn tiff(width: u32, height: u32, bits: u32, next: u32, pixels: &[u8]) -> PathBuf {
+    let tiff = out("tif");
+    let mut bytes = b"II*\x00".to_vec();
+    bytes.extend(8_u32.to_le_bytes());
+    bytes.extend(5_u16.to_le_bytes());
+    [
+        (256, 4, width),
+        (257, 4, height),
+        (258, 3, bits),
+        (273, 4, 74),
+        (279, 4, pixels.len() as u32),
+    ]
+    .iter()
+    .for_each(|&(tag, kind, value): &(u16, u16, u32)| {
+        bytes.extend(tag.to_le_bytes());
+        bytes.extend(kind.to_le_bytes());
+        bytes.extend(1_u32.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    });
+    bytes.extend(next.to_le_bytes());
+    bytes.extend(pixels);
+    std::fs::write(&tiff, bytes).unwrap();
+    tiff
+}
+
+/// Standard error of meshing a file that is expected to fail.
+fn mesh_hex_error(input: &Path) -> String {
+    let output = Command::new(BIN)
+        .args([
+            "mesh",
+            "hex",
+            "-i",
+            input.to_str().unwrap(),
+            "-o",
+            out("inp").to_str().unwrap(),
+        ])
+        .arg("--quiet")
+        .output()
+        .expect("failed to spawn automesh");
+    assert!(!output.status.success(), "{} was read", input.display());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn mesh_hex_rejects_a_tiff_directory_loop() {
+    // A one-pixel TIFF whose image file directory names itself as the next.
+    let error = mesh_hex_error(&tiff(1, 1, 8, 8, &[1]));
+    assert!(error.contains("loop"), "{error}");
+}
+
+#[test]
+fn mesh_hex_rejects_tiff_dimensions_too_large_to_read() {
+    let error = mesh_hex_error(&tiff(u32::MAX, u32::MAX, 16, 0, &[1]));
+    assert!(error.contains("too large"), "{error}");
+}
+
+#[test]
+fn mesh_hex_32_bit_tiff_labels() {
+    let output = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        tiff(1, 1, 32, 0, &7_u32.to_le_bytes()).to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert_nonempty(&output);
+    let error = mesh_hex_error(&tiff(1, 1, 32, 0, &70_000_u32.to_le_bytes()));
+    assert!(error.contains("out of range for 16-bit labels"), "{error}");
+}
+
+#[test]
+fn diff_tiff_stack_matches_npy() {
+    let output = out("npy");
+    run(&[
+        "diff",
+        "-i",
+        input("letter_f_3d_*.tif").to_str().unwrap(),
+        input("letter_f_3d.npy").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    // The slices stack to the same voxels as the npy, so every difference is zero.
+    let bytes = std::fs::read(&output).expect("diff output was not created");
+    let header = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let data = &bytes[10 + header..];
+    assert_eq!(data.len(), 4 * 5 * 3);
+    assert!(
+        data.iter().all(|&voxel| voxel == 0),
+        "tif stack differs from npy"
+    );
+}
+
+#[test]
+fn mesh_hex_from_nifti_header() {
+    let nifti = out("nii.gz");
+    run(&[
+        "segment",
+        "-i",
+        input("letter_f_3d.inp").to_str().unwrap(),
+        "-o",
+        nifti.to_str().unwrap(),
+        "-s",
+        "0.5",
+    ]);
+    let output = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        nifti.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    // The header carries the 0.5 spacing, so the second node sits half a unit along x.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    let mut nodes = text.lines().skip_while(|line| *line != "*Node").skip(1);
+    assert_eq!(nodes.next(), Some("1, 0, 0, 0"));
+    assert_eq!(nodes.next(), Some("2, 0.5, 0, 0"));
+}
+
+#[test]
+fn mesh_hex_rejects_a_flipped_nifti() {
+    let nifti = out("nii");
+    run(&[
+        "segment",
+        "-i",
+        input("letter_f_3d.inp").to_str().unwrap(),
+        "-o",
+        nifti.to_str().unwrap(),
+        "-s",
+        "0.5",
+    ]);
+    // Negating the first entry of the sform mirrors the image along x.
+    let mut bytes = std::fs::read(&nifti).unwrap();
+    let flipped = -f32::from_le_bytes(bytes[280..284].try_into().unwrap());
+    bytes[280..284].copy_from_slice(&flipped.to_le_bytes());
+    std::fs::write(&nifti, bytes).unwrap();
+    let status = Command::new(BIN)
+        .args([
+            "mesh",
+            "hex",
+            "-i",
+            nifti.to_str().unwrap(),
+            "-o",
+            out("inp").to_str().unwrap(),
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(!status.success(), "a flipped NIfTI image was meshed");
+}
+
+#[test]
+fn convert_nifti_scales_labels_and_rejects_empty_dimensions() {
+    let nifti = out("nii");
+    run(&[
+        "segment",
+        "-i",
+        input("letter_f_3d.inp").to_str().unwrap(),
+        "-o",
+        nifti.to_str().unwrap(),
+        "-s",
+        "0.5",
+    ]);
+    let labels = |nifti: &Path| {
+        let spn = out("spn");
+        run(&[
+            "convert",
+            "segmentation",
+            "-i",
+            nifti.to_str().unwrap(),
+            "-o",
+            spn.to_str().unwrap(),
+        ]);
+        std::fs::read_to_string(&spn)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse::<u16>().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let original = labels(&nifti);
+    // A scl_slope of 2 and scl_inter of 1 map each stored value v to 2v + 1.
+    let bytes = std::fs::read(&nifti).unwrap();
+    let mut scaled = bytes.clone();
+    scaled[112..116].copy_from_slice(&2_f32.to_le_bytes());
+    scaled[116..120].copy_from_slice(&1_f32.to_le_bytes());
+    let scaled_nifti = out("nii");
+    std::fs::write(&scaled_nifti, scaled).unwrap();
+    assert_eq!(
+        labels(&scaled_nifti),
+        original
+            .iter()
+            .map(|label| 2 * label + 1)
+            .collect::<Vec<_>>()
+    );
+    // A zero dimension along y is an empty image, not one voxel thick.
+    let mut empty = bytes;
+    empty[44..46].copy_from_slice(&0_i16.to_le_bytes());
+    let empty_nifti = out("nii");
+    std::fs::write(&empty_nifti, empty).unwrap();
+    let status = Command::new(BIN)
+        .args([
+            "convert",
+            "segmentation",
+            "-i",
+            empty_nifti.to_str().unwrap(),
+            "-o",
+            out("spn").to_str().unwrap(),
+        ])
+        .arg("--quiet")
+        .status()
+        .expect("failed to spawn automesh");
+    assert!(
+        !status.success(),
+        "a NIfTI image with a zero dimension was read"
+    );
+}
+
+#[test]
+fn mesh_hex_16_bit_labels() {
+    let segmentation = out("npy");
+    let header = "{'descr': '<u2', 'fortran_order': False, 'shape': (2, 1, 1), }";
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend((header.len() as u16 + 1).to_le_bytes());
+    bytes.extend(header.bytes().chain(Some(b'\n')));
+    bytes.extend([1_u16, 300].iter().flat_map(|label| label.to_le_bytes()));
+    std::fs::write(&segmentation, bytes).unwrap();
+    let output = out("inp");
+    run(&[
+        "mesh",
+        "hex",
+        "-i",
+        segmentation.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    // Label 300 is kept as is, rather than wrapping around to 44.
+    let text = std::fs::read_to_string(&output).expect("mesh output was not created");
+    assert_eq!(text.matches("*Element").count(), 2);
+    let spn = out("spn");
+    run(&[
+        "convert",
+        "segmentation",
+        "-i",
+        segmentation.to_str().unwrap(),
+        "-o",
+        spn.to_str().unwrap(),
+    ]);
+    assert_eq!(std::fs::read_to_string(&spn).unwrap().trim(), "1\n300");
+}
+
+#[test]
+fn segment_grid_majority_vote() {
+    let spn = |tie: &str| {
+        let output = out("spn");
+        run(&[
+            "segment",
+            "-i",
+            input("letter_f_3d.inp").to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "-s",
+            "2",
+            "-g",
+            "2",
+            "-t",
+            tie,
+        ]);
+        std::fs::read_to_string(&output)
+            .expect("segmentation output was not created")
+            .split_whitespace()
+            .map(|label| label.parse().unwrap())
+            .collect::<Vec<u8>>()
+    };
+    // Voxels half inside the letter are void under lowest and solid under highest.
+    assert_eq!(spn("lowest"), [1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(spn("highest"), [1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn segment_volume_fractions() {
+    let output = out("spn");
+    let fractions = out("npy");
+    run(&[
+        "segment",
+        "-i",
+        input("letter_f_3d.inp").to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-s",
+        "2",
+        "-g",
+        "2",
+        "-f",
+        fractions.to_str().unwrap(),
+    ]);
+    let bytes = std::fs::read(&fractions).expect("fractions output was not created");
+    let header = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert!(String::from_utf8_lossy(&bytes[10..10 + header]).contains("(2, 3, 2, 1, )"));
+    let data: Vec<f64> = bytes[10 + header..]
+        .chunks_exact(8)
+        .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    // Tied voxels of the majority vote are exactly half full, and the 2x2x2
+    // voxels together hold the 39 unit voxels of the letter.
+    assert_eq!(data[..3], [0.75, 0.5, 0.875]);
+    assert_eq!(8.0 * data.iter().sum::<f64>(), 39.0);
+}
+
+#[test]
+fn run_recipe_with_stages() {
+    let recipe = out("toml");
+    let output = out("inp");
+    let metrics = out("csv");
+    // Outputs are named relative to the recipe, which sits beside them.
+    let name = |path: &PathBuf| path.file_name().unwrap().to_str().unwrap().to_string();
+    std::fs::write(
+        &recipe,
+        format!(
+            "input = {:?}\nremove = [0]\noutputs = [{:?}]\nmetrics = {:?}\n\n\
+             [mesh]\nelement = \"hex\"\n\n\
+             [[stages]]\nstage = \"smooth\"\nmethod = \"laplace\"\niterations = 2\n",
+            input("letter_f_3d.npy").to_str().unwrap(),
+            name(&output),
+            name(&metrics),
+        ),
+    )
+    .unwrap();
+    run(&["run", recipe.to_str().unwrap()]);
+    assert_nonempty(&output);
+    let rows = std::fs::read_to_string(&metrics).unwrap().lines().count();
+    assert_eq!(rows, 40);
+}
+
+#[test]
+fn batch_recipe_over_manifest() {
+    let recipe = out("toml");
+    let manifest = recipe.with_extension("csv");
+    let summary = out("csv");
+    let stem = recipe.file_stem().unwrap().to_str().unwrap().to_string();
+    std::fs::write(
+        &manifest,
+        format!(
+            "input,name\n{},{stem}_f\n{},{stem}_single\n{},{stem}_broken\n",
+            input("letter_f_3d.npy").to_str().unwrap(),
+            input("single.npy").to_str().unwrap(),
+            input("encrypted.npy").to_str().unwrap(),
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        &recipe,
+        format!(
+            "input = {:?}\nremove = [0]\noutputs = [\"{{name}}.inp\"]\n\n\
+             [mesh]\nelement = \"hex\"\n",
+            manifest.file_name().unwrap().to_str().unwrap(),
+        ),
+    )
+    .unwrap();
+    // The broken case fails the command but not the other cases.
+    let output = Command::new(BIN)
+        .args([
+            "--quiet",
+            "batch",
+            recipe.to_str().unwrap(),
+            "--jobs",
+            "2",
+            "--summary",
+            summary.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 3 cases failed"));
+    assert_nonempty(&recipe.with_file_name(format!("{stem}_f.inp")));
+    assert_nonempty(&recipe.with_file_name(format!("{stem}_single.inp")));
+    let summary = std::fs::read_to_string(&summary).unwrap();
+    assert_eq!(summary.lines().count(), 4);
+    assert!(summary.contains(&format!("\"{stem}_f\",")));
+    assert!(summary.contains("\",done,39,102,"));
+    assert!(summary.contains(&format!("\"{stem}_broken\",")));
+}
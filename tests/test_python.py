@@ -0,0 +1,66 @@
+"""Tests of the Python bindings, run against an installed wheel."""
+
+import subprocess
+from pathlib import Path
+
+import automesh
+import numpy as np
+import pytest
+
+INPUT = Path(__file__).parent / "input"
+
+
+def letter_f():
+    return np.load(INPUT / "letter_f_3d.npy")
+
+
+def test_mesh_from_segmentation():
+    mesh = automesh.Mesh.from_segmentation(letter_f(), remove=[0])
+    assert mesh.nodes.shape == (102, 3)
+    assert mesh.elements.shape == (39, 8)
+    assert mesh.blocks.shape == (39,)
+    assert np.all(mesh.blocks == 1)
+
+
+def test_mesh_round_trips_through_arrays():
+    mesh = automesh.Mesh.from_segmentation(letter_f(), remove=[0])
+    copy = automesh.Mesh(mesh.nodes, mesh.elements, mesh.blocks)
+    assert np.array_equal(copy.nodes, mesh.nodes)
+    assert np.array_equal(copy.elements, mesh.elements)
+
+
+def test_smooth_and_metrics():
+    mesh = automesh.Mesh.from_segmentation(letter_f(), remove=[0])
+    before = mesh.nodes
+    mesh.smooth(method="laplace", iterations=2)
+    assert not np.array_equal(before, mesh.nodes)
+    metrics = mesh.metrics()
+    assert set(metrics) == {
+        "maximum_edge_ratio",
+        "minimum_scaled_jacobian",
+        "maximum_skew",
+        "volume",
+    }
+    assert np.all(metrics["volume"] > 0)
+
+
+def test_defeature_keeps_shape():
+    labels = letter_f()
+    defeatured = automesh.defeature(labels, 2)
+    assert defeatured.shape == labels.shape
+
+
+def test_invalid_input():
+    with pytest.raises(ValueError):
+        automesh.Mesh.from_segmentation(letter_f(), element="prism")
+    with pytest.raises(TypeError):
+        automesh.Mesh.from_segmentation(np.zeros((2, 2, 2)))
+
+
+def test_console_script(tmp_path):
+    output = tmp_path / "letter_f_3d.inp"
+    subprocess.run(
+        ["automesh", "mesh", "hex", "-i", INPUT / "letter_f_3d.npy", "-o", output, "-q"],
+        check=True,
+    )
+    assert output.stat().st_size > 0